@@ -0,0 +1,47 @@
+#version 330 core
+
+// Atributos por vértice
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec2 textureCoordinates;
+layout(location = 2) in vec3 normal;
+// Atributos por instancia (divisor 1), un mat4 ocupa las locations 3, 4, 5 y 6
+layout(location = 3) in mat4 transformationMatrix;
+layout(location = 7) in vec2 offset;
+
+out vec2 pass_textureCoordinates;
+out vec3 surfaceNormal;
+out vec3 toLightVector[4];
+out vec3 toCameraVector;
+out float visibility;
+
+uniform mat4 projectionMatrix;
+uniform mat4 viewMatrix;
+uniform vec3 lightPosition[4];
+
+uniform float useFakeLighting;
+uniform float numberOfRows;
+
+const float density = 0.0035;
+const float gradient = 5.0;
+
+void main(void) {
+    vec4 worldPosition = transformationMatrix * vec4(position, 1.0);
+    vec4 positionRelativeToCam = viewMatrix * worldPosition;
+    gl_Position = projectionMatrix * positionRelativeToCam;
+    pass_textureCoordinates = (textureCoordinates / numberOfRows) + offset;
+
+    vec3 actualNormal = normal;
+    if (useFakeLighting > 0.5) {
+        actualNormal = vec3(0.0, 1.0, 0.0);
+    }
+
+    surfaceNormal = (transformationMatrix * vec4(actualNormal, 0.0)).xyz;
+    for (int i = 0; i < 4; i++) {
+        toLightVector[i] = lightPosition[i] - worldPosition.xyz;
+    }
+    toCameraVector = (inverse(viewMatrix) * vec4(0.0, 0.0, 0.0, 1.0)).xyz - worldPosition.xyz;
+
+    float distance = length(positionRelativeToCam.xyz);
+    visibility = exp(-pow((distance * density), gradient));
+    visibility = clamp(visibility, 0.0, 1.0);
+}
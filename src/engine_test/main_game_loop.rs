@@ -1,26 +1,43 @@
-use cgmath::{vec2, vec3};
+use cgmath::{vec2, vec3, EuclideanSpace};
+use glfw::{Action, Key};
 use rand::Rng;
 
+use std::rc::Rc;
+
 use crate::entities::camera::Camera;
 use crate::entities::entity::Entity;
 use crate::entities::light::Light;
 use crate::entities::player::Player;
 use crate::guis::gui_renderer::GuiRenderer;
 use crate::guis::gui_texture::GuiTexture;
+use crate::models::lod_chain::LodMetric;
 use crate::models::raw_model::RawModel;
 use crate::models::textured_model::TexturedModel;
+use crate::render_engine::asset_manager::{AssetManager, Mesh, Model};
+use crate::render_engine::async_loader::{AsyncLoader, LoadState};
 use crate::render_engine::display_manager::DisplayManager;
+use crate::render_engine::gltf_loader::GLTFLoader;
+use crate::render_engine::gl_resources::Texture;
 use crate::render_engine::loader::Loader;
 use crate::render_engine::master_renderer::MasterRenderer;
-use crate::render_engine::objloader::OBJLoader;
+use crate::terrains::height_generator::HeightGenerator;
 use crate::terrains::terrain::Terrain;
+use crate::terrains::terrain_descriptor::{NormalFilter, TerrainDescriptor};
+use crate::terrains::terrain_world::TerrainWorld;
 use crate::textures::model_texture::ModelTexture;
 use crate::textures::terrain_texture::TerrainTexture;
 use crate::textures::terrain_texture_pack::TerrainTexturePack;
+use crate::textures::texture_options::{TextureFilter, TextureOptions, TextureWrap};
+use crate::toolbox::mesh_exporter::{self, PlyFormat};
 use crate::toolbox::mouse_picker::MousePicker;
 
 type V3CG = cgmath::Vector3<f32>;
 
+// Pendiente máxima en grados para plantar vegetación
+const MAX_VEGETATION_SLOPE: f32 = 35.0;
+// Intentos de encontrar un sitio con menos pendiente antes de quedarse con el último
+const PLACEMENT_ATTEMPTS: usize = 10;
+
 pub struct MainGameLoop {
     dm: DisplayManager,
     renderer: MasterRenderer,
@@ -28,10 +45,13 @@ pub struct MainGameLoop {
     guis: Vec<GuiTexture>,
     model: RawModel,
     loader: Loader,
+    assets: AssetManager,
+    textures: Vec<Rc<Texture>>,
+    meshes: Vec<Rc<Mesh>>,
+    models: Vec<Rc<Model>>,
     camera: Camera,
     lights: Vec<Light>,
-    terrain: Terrain,
-    //terrain2: Terrain,
+    terrain_world: TerrainWorld,
     entities: Vec<Entity>,
     player: Player,
     picker: MousePicker,
@@ -44,86 +64,163 @@ impl MainGameLoop {
         dm.create_display();
 
         let mut loader = Loader::new();
+        // Los recursos se piden al AssetManager, que no repite cargas de la misma ruta.
+        // Guardamos los handles en textures/meshes para que vivan mientras dure el juego
+        let mut assets = AssetManager::new();
+        let mut textures: Vec<Rc<Texture>> = vec![];
+        let mut meshes: Vec<Rc<Mesh>> = vec![];
+        let mut models: Vec<Rc<Model>> = vec![];
+        // El heightmap se decodifica en otro hilo mientras se cargan las texturas del terreno
+        let mut async_loader = AsyncLoader::new(1);
+        let heightmap_ticket = async_loader.request_heightmap("res/textures/heightmap.png");
+        // El low_poly_tree también, se sube a la GPU al terminar con el terreno
+        let low_poly_mesh_ticket = async_loader.request_mesh("res/models/lowPolyTree.obj");
+        let low_poly_texture_ticket =
+            async_loader.request_texture("res/textures/lowPolyTree.png", &TextureOptions::new());
 // ----------------------------- TERRAIN TEXTURE STUFF -----------------------------------------
-
-        let background_texture =
-            TerrainTexture::new(loader.load_texture("res/textures/grassy.png").unwrap());
-        let r_texture =
-            TerrainTexture::new(loader.load_texture("res/textures/dirt.png").unwrap());
-        let g_texture =
-            TerrainTexture::new(loader.load_texture("res/textures/pinkFlowers.png").unwrap());
-        let b_texture =
-            TerrainTexture::new(loader.load_texture("res/textures/path.png").unwrap());
+        // El suelo se ve muy de lado, con filtrado anisótropo no se emborrona a lo lejos
+        let mut terrain_options = TextureOptions::new();
+        terrain_options.set_anisotropy(4.0);
+
+        let texture = assets.load_texture_with_options(&mut loader, "res/textures/grassy.png",
+                                                       &terrain_options).unwrap();
+        let background_texture = TerrainTexture::new(texture.get_id());
+        textures.push(texture);
+        let texture = assets.load_texture_with_options(&mut loader, "res/textures/dirt.png",
+                                                       &terrain_options).unwrap();
+        let r_texture = TerrainTexture::new(texture.get_id());
+        textures.push(texture);
+        let texture = assets.load_texture_with_options(&mut loader, "res/textures/pinkFlowers.png",
+                                                       &terrain_options).unwrap();
+        let g_texture = TerrainTexture::new(texture.get_id());
+        textures.push(texture);
+        let texture = assets.load_texture_with_options(&mut loader, "res/textures/path.png",
+                                                       &terrain_options).unwrap();
+        let b_texture = TerrainTexture::new(texture.get_id());
+        textures.push(texture);
 
         let texture_pack = TerrainTexturePack::new(background_texture, r_texture, g_texture, b_texture);
-        let blend_map =
-            TerrainTexture::new(loader.load_texture("res/textures/blendMap.png").unwrap());
+        let texture = assets.load_texture(&mut loader, "res/textures/blendMap.png").unwrap();
+        let blend_map = TerrainTexture::new(texture.get_id());
+        textures.push(texture);
 
-        let terrain =
-            Terrain::new(0, -1, &mut loader, texture_pack.clone(), blend_map.clone(),
-                         "res/textures/heightmap.png");
+        if let LoadState::Failed(error) = async_loader.wait(heightmap_ticket, &mut loader) {
+            panic!("{}", error);
+        }
+        let heightmap = async_loader.take_heightmap(heightmap_ticket).unwrap();
+        // Tiles de 800 x 800 con alturas de -40 a 40. El heightmap es de 8 bits: con Sobel no se
+        // ven los escalones en la iluminación
+        let mut descriptor = TerrainDescriptor::new();
+        descriptor.set_normal_filter(NormalFilter::Sobel);
+        // Dos tiles con el mismo heightmap, a los dos lados de x = 0
+        let mut terrain_world = TerrainWorld::new(&descriptor);
+        terrain_world.add_terrain(
+            Terrain::with_heightmap(0, -1, &mut loader, texture_pack, blend_map,
+                                    heightmap.clone(), &descriptor).unwrap());
+        terrain_world.add_terrain(
+            Terrain::with_heightmap(-1, -1, &mut loader, texture_pack, blend_map,
+                                    heightmap, &descriptor).unwrap());
+        // Más al fondo, dos tiles procedurales que encajan entre sí
+        let generator = HeightGenerator::new(2019);
+        let mut generated = descriptor;
+        generated.set_resolution(128, 128);
+        for grid_x in -1..1 {
+            terrain_world.add_terrain(
+                Terrain::with_generator(grid_x, -2, &mut loader, texture_pack,
+                                        blend_map, &generator, &generated));
+        }
+        // Normales de los bordes con las alturas de los tiles vecinos
+        terrain_world.stitch_normals(&mut loader);
+        for terrain in terrain_world.get_terrains() {
+            if let Some(report) = terrain.get_optimization_report() {
+                println!("Terrain ({}, {}): {}", terrain.get_grid_x(), terrain.get_grid_z(), report);
+            }
+        }
 // ----------------------------- player 0 ------------------------------------------------------
-        let mut obj_loader = OBJLoader::new();
-        let model = obj_loader.load_obj_model("res/models/stanfordBunny.obj", &mut loader);
+        let mesh = assets.load_mesh(&mut loader, "res/models/stanfordBunny.obj").unwrap();
+        let texture = assets.load_texture(&mut loader, "res/textures/white.png").unwrap();
 
         let stanford_bunny =
-            TexturedModel::new(model, ModelTexture::new(loader.load_texture
-            ("res/textures/white.png").unwrap()));
+            TexturedModel::new(mesh.get_raw_model(), ModelTexture::new(texture.get_id()));
+        meshes.push(mesh);
+        textures.push(texture);
 // ----------------------------- arbol 1 -------------------------------------------------------
-        let mut obj_loader = OBJLoader::new();
-        let model = obj_loader.load_obj_model("res/models/tree.obj", &mut loader);
+        // Mitad de triángulos a partir de 60 unidades y un cuarto a partir de 150
+        let mesh = assets.load_lod_mesh(&mut loader, "res/models/tree.obj",
+                                        &[(0.5, 60.0), (0.25, 150.0)], LodMetric::Distance)
+            .unwrap();
+        let texture = assets.load_texture(&mut loader, "res/textures/tree.png").unwrap();
 
-        let static_model =
-            TexturedModel::new(model, ModelTexture::new(loader.load_texture
-            ("res/textures/tree.png").unwrap()));
+        let static_model = mesh.create_textured_model(ModelTexture::new(texture.get_id()));
+        meshes.push(mesh);
+        textures.push(texture);
 
 // ----------------------------- hierbas 2 -----------------------------------------------------
-        let mut obj_loader = OBJLoader::new();
-        let model = obj_loader.load_obj_model("res/models/grassModel.obj", &mut loader);
+        let mesh = assets.load_mesh(&mut loader, "res/models/grassModel.obj").unwrap();
+        let texture = assets.load_texture(&mut loader, "res/textures/grassTexture.png").unwrap();
 
         let grass =
-            TexturedModel::new(model, ModelTexture::new(loader.load_texture
-            ("res/textures/grassTexture.png").unwrap()));
+            TexturedModel::new(mesh.get_raw_model(), ModelTexture::new(texture.get_id()));
+        meshes.push(mesh);
+        textures.push(texture);
 
         grass.get_texture().set_has_transparency(true);
         grass.get_texture().set_use_fake_lighting(true);
 
 // ----------------------------- helecho 3 -- (atlas) ------------------------------------------
-        let mut obj_loader = OBJLoader::new();
-        let model = obj_loader.load_obj_model("res/models/fern.obj", &mut loader);
+        let mesh = assets.load_mesh(&mut loader, "res/models/fern.obj").unwrap();
+        let texture = assets.load_texture(&mut loader, "res/textures/fern.png").unwrap();
 
-        let mut fern_texture_atlas = ModelTexture::new(loader.load_texture(
-            "res/textures/fern.png").unwrap()); // hojas (atlas)
+        let mut fern_texture_atlas = ModelTexture::new(texture.get_id()); // hojas (atlas)
 
         fern_texture_atlas.set_number_of_rows(2);
 
-        let fern = TexturedModel::new(model, fern_texture_atlas);
+        let fern = TexturedModel::new(mesh.get_raw_model(), fern_texture_atlas);
+        meshes.push(mesh);
+        textures.push(texture);
 
         fern.get_texture().set_has_transparency(true);
-// ----------------------------- low_poly_tree 4 -----------------------------------------------
-        let mut obj_loader = OBJLoader::new();
-        let model = obj_loader.load_obj_model("res/models/lowPolyTree.obj", &mut loader);
+// ----------------------------- low_poly_tree 4 -- (async) ------------------------------------
+        // Pantalla de carga: se sube un recurso por vuelta hasta que no quede nada pendiente
+        while async_loader.get_pending_count() > 0 {
+            if async_loader.process_uploads(&mut loader, 1) == 0 {
+                std::thread::yield_now();
+            }
+        }
+        let mesh = match async_loader.take_mesh(low_poly_mesh_ticket) {
+            Some(mesh) => Rc::new(mesh),
+            None => panic!("{:?}", async_loader.poll(low_poly_mesh_ticket)),
+        };
+        let texture = match async_loader.take_texture(low_poly_texture_ticket) {
+            Some(texture) => Rc::new(texture),
+            None => panic!("{:?}", async_loader.poll(low_poly_texture_ticket)),
+        };
 
         let low_poly_tree =
-            TexturedModel::new(model, ModelTexture::new(loader.load_texture
-            ("res/textures/lowPolyTree.png").unwrap()));
+            TexturedModel::new(mesh.get_raw_model(), ModelTexture::new(texture.get_id()));
+        meshes.push(mesh);
+        textures.push(texture);
 // ----------------- flores, usa el mismo obj que hierbas pero otra textura 5 ------------------
-        let mut obj_loader = OBJLoader::new();
-        let model = obj_loader.load_obj_model("res/models/grassModel.obj", &mut loader);
+        let mesh = assets.load_mesh(&mut loader, "res/models/grassModel.obj").unwrap(); // ya en caché
+        let texture = assets.load_texture(&mut loader, "res/textures/flower.png").unwrap();
 
         let grass =
-            TexturedModel::new(model, ModelTexture::new(loader.load_texture
-            ("res/textures/flower.png").unwrap()));
+            TexturedModel::new(mesh.get_raw_model(), ModelTexture::new(texture.get_id()));
+        meshes.push(mesh);
+        textures.push(texture);
 
         grass.get_texture().set_has_transparency(true);
         grass.get_texture().set_use_fake_lighting(true);
 // ---------------------------------------- lampara --------------------------------------------
-        let mut obj_loader = OBJLoader::new();
-        let model = obj_loader.load_obj_model("res/models/lamp.obj", &mut loader);
-
-        let lamp =
-            TexturedModel::new(model, ModelTexture::new(loader.load_texture
-            ("res/textures/lamp.png").unwrap()));
+        // Mitad de triángulos cuando ocupa menos del 10% de la altura de la pantalla
+        let mesh = assets.load_lod_mesh(&mut loader, "res/models/lamp.obj", &[(0.5, 0.1)],
+                                        LodMetric::ScreenSize).unwrap();
+        let texture = assets.load_texture(&mut loader, "res/textures/lamp.png").unwrap();
+        let model = mesh.get_raw_model();
+
+        let lamp = mesh.create_textured_model(ModelTexture::new(texture.get_id()));
+        meshes.push(mesh);
+        textures.push(texture);
 // ---------------------------------------------------------------------------------------------
         let mut entities: Vec<Entity> = vec![];
         let mut rng = rand::thread_rng();
@@ -132,57 +229,46 @@ impl MainGameLoop {
         for i in 0..500 {
             if i % 20 == 0 { // 25 arboles
                 // ------------------------- arbol ------------------------------------
-                let x = rng.gen_range(0.0, 1.0) * 800.0 - 400.0;
-                let z = rng.gen_range(0.0, 1.0) * -600.0;
-                let y = terrain.get_height_of_terrain(x, z);
+                let position = MainGameLoop::random_position(&mut rng, &terrain_world);
 
                 entities.push(Entity::new(1,                     // ID, creado por mi (player = 0)
                                           static_model,          // arbol
-                                          vec3(x, y, z),         // Posición
+                                          position,              // Posición
                                           vec3(0.0, 0.0, 0.0),   // Rotación
                                           vec3(8.0, 8.0, 8.0))); // Escala
             }
             if i % 5 == 0 { // 100 hierbas
                 // ------------------------- hierbas -----------------------------------
-                let x = rng.gen_range(0.0, 1.0) * 800.0 - 400.0;
-                let z = rng.gen_range(0.0, 1.0) * -600.0;
-                let y = terrain.get_height_of_terrain(x, z);
+                let position = MainGameLoop::random_position(&mut rng, &terrain_world);
                 entities.push(Entity::new(2,                    // ID, creado por mi
                                           grass,                // hierbas
-                                          vec3(x, y, z),        // Posición
+                                          position,             // Posición
                                           vec3(0.0, 0.0, 0.0),  // Rotación
                                           vec3(1.0, 1.0, 1.0)));// Escala
             }
 
             // ------------------------- helecho -----------------------------------
-            let x = rng.gen_range(0.0, 1.0) * 800.0 - 400.0;
-            let z = rng.gen_range(0.0, 1.0) * -600.0;
-            let y = terrain.get_height_of_terrain(x, z);
+            let position = MainGameLoop::random_position(&mut rng, &terrain_world);
             entities.push(Entity::new2(3,                    // ID, creado por mi
                                        fern,                 // helecho
                                        rng.gen_range(0, 4),
-                                       vec3(x, y, z),        // Posición
+                                       position,             // Posición
                                        vec3(0.0, 0.0, 0.0),  // Rotación
                                        vec3(1.0, 1.0, 1.0)));// Escala
             // ------------------------- low_poly_tree ---------------------------
             if i % 50 == 0 { // 10 low_poly_tree
-                let x = rng.gen_range(0.0, 1.0) * 800.0 - 400.0;
-
-                let z = rng.gen_range(0.0, 1.0) * -600.0;
-                let y = terrain.get_height_of_terrain(x, z);
+                let position = MainGameLoop::random_position(&mut rng, &terrain_world);
                 entities.push(Entity::new(4,                    // ID, creado por mi
                                           low_poly_tree,                 // helecho
-                                          vec3(x, y, z),        // Posición
+                                          position,             // Posición
                                           vec3(0.0, 0.0, 0.0),  // Rotación
                                           vec3(3.0, 3.0, 3.0)));// Escala
             }
             // ------------------------- flores -----------------------------------
-            let x = rng.gen_range(0.0, 1.0) * 800.0 - 400.0;
-            let z = rng.gen_range(0.0, 1.0) * -600.0;
-            let y = terrain.get_height_of_terrain(x, z);
+            let position = MainGameLoop::random_position(&mut rng, &terrain_world);
             entities.push(Entity::new(5,                    // ID, creado por mi
                                       grass,                // hierbas
-                                      vec3(x, y, z),        // Posición
+                                      position,             // Posición
                                       vec3(0.0, 0.0, 0.0),  // Rotación
                                       vec3(1.0, 1.0, 1.0)));// Escala
         }
@@ -213,7 +299,7 @@ impl MainGameLoop {
 // -- Objetos lámpara --
         let x = 185.0;
         let z = -293.0;
-        let y = terrain.get_height_of_terrain(x, z);
+        let y = terrain_world.get_height_of_terrain(x, z);
 
         entities.push(Entity::new(6,                    // ID, creado por mi
                                   lamp,                // lámpara
@@ -224,7 +310,7 @@ impl MainGameLoop {
 
         let x = 370.0;
         let z = -300.0;
-        let y = terrain.get_height_of_terrain(x, z);
+        let y = terrain_world.get_height_of_terrain(x, z);
         entities.push(Entity::new(7,                    // ID, creado por mi
                                   lamp,                // lámpara
                                   vec3(x, y, z),        // Posición
@@ -232,13 +318,51 @@ impl MainGameLoop {
                                   vec3(1.0, 1.0, 1.0)));// Escala
         let x = 293.0;
         let z = -305.0;
-        let y = terrain.get_height_of_terrain(x, z);
+        let y = terrain_world.get_height_of_terrain(x, z);
         entities.push(Entity::new(8,                    // ID, creado por mi
                                   lamp,                // lámpara
                                   vec3(x, y, z),        // Posición
                                   vec3(0.0, 0.0, 0.0),  // Rotación
                                   vec3(1.0, 1.0, 1.0)));// Escala
 
+// -- Casa con materiales MTL, una entidad por material --
+        // Es opcional: si no está el OBJ se avisa y se sigue sin ella
+        match assets.load_model(&mut loader, "res/models/house.obj") {
+            Ok(house) => {
+                let x = 240.0;
+                let z = -330.0;
+                let y = terrain_world.get_height_of_terrain(x, z);
+                for textured_model in house.get_textured_models() {
+                    entities.push(Entity::new(9,                    // ID, creado por mi
+                                              textured_model,       // parte de la casa
+                                              vec3(x, y, z),        // Posición
+                                              vec3(0.0, 0.0, 0.0),  // Rotación
+                                              vec3(4.0, 4.0, 4.0)));// Escala
+                }
+                models.push(house);
+            }
+            Err(error) => eprintln!("{}", error),
+        }
+
+// -- Farol glTF, cada parte con la transformación de su nodo --
+        // También opcional
+        match GLTFLoader::new().load_gltf_model("res/models/lantern.glb", &mut loader) {
+            Ok(lantern) => {
+                let x = 210.0;
+                let z = -310.0;
+                let y = terrain_world.get_height_of_terrain(x, z);
+                for part in lantern.get_parts() {
+                    let mut entity = Entity::new(10,                   // ID, creado por mi
+                                                 part.get_textured_model(),
+                                                 vec3(x, y, z),        // Posición
+                                                 vec3(0.0, 0.0, 0.0),  // Rotación
+                                                 vec3(1.0, 1.0, 1.0)); // Escala
+                    entity.set_local_transform(part.get_transform());
+                    entities.push(entity);
+                }
+            }
+            Err(error) => eprintln!("{}", error),
+        }
 
 // ---------------------------- player y cámara -----------------------------------------
 
@@ -251,10 +375,20 @@ impl MainGameLoop {
         let mut camera = Camera::new();
 // ------------------------- GUI --------------------------------------------------------
         let mut guis: Vec<GuiTexture> = vec![];
-        let gui = GuiTexture::new(loader.load_texture("res/textures/Marmntrans.png").unwrap(),
-                                  vec2(-0.8, -0.5), vec2(0.2, 0.4));
-        let gui2 = GuiTexture::new(loader.load_texture("res/textures/Barcelona.png").unwrap(),
-                                   vec2(0.8, 0.8), vec2(0.05, 0.1));
+        // Las GUIs se ven a su tamaño: sin mipmaps, y sin repetir para que los bordes no se
+        // mezclen con el lado contrario
+        let mut gui_options = TextureOptions::new();
+        gui_options.set_wrap(TextureWrap::ClampToEdge);
+        gui_options.set_filter(TextureFilter::Bilinear);
+        gui_options.set_lod_bias(0.0);
+        let texture = assets.load_texture_with_options(&mut loader, "res/textures/Marmntrans.png",
+                                                       &gui_options).unwrap();
+        let gui = GuiTexture::new(texture.get_id(), vec2(-0.8, -0.5), vec2(0.2, 0.4));
+        textures.push(texture);
+        let texture = assets.load_texture_with_options(&mut loader, "res/textures/Barcelona.png",
+                                                       &gui_options).unwrap();
+        let gui2 = GuiTexture::new(texture.get_id(), vec2(0.8, 0.8), vec2(0.05, 0.1));
+        textures.push(texture);
         guis.push(gui);
         guis.push(gui2);
 
@@ -262,7 +396,7 @@ impl MainGameLoop {
 // --------------------------------------------------------------------------------------
         let renderer = MasterRenderer::new(&dm, &mut loader);
 // ------------------------------ Para picar con el ratón -------------------------------
-        let picker = MousePicker::new(&mut camera, renderer.get_projection_matrix(), &terrain);
+        let picker = MousePicker::new(&mut camera, renderer.get_projection_matrix());
 
         MainGameLoop {
             dm,
@@ -271,10 +405,13 @@ impl MainGameLoop {
             guis,
             model,
             loader,
+            assets,
+            textures,
+            meshes,
+            models,
             camera,
             lights,
-            terrain,
-            //terrain2,
+            terrain_world,
             entities,
             player,
             picker,
@@ -283,6 +420,7 @@ impl MainGameLoop {
     }
 
     pub fn main_game_loop(&mut self) {
+        let mut export_pressed = false;
         while !self.dm.window.should_close() {
             //self.dm.procesa_eventos(&self.dm.events, self.camera);
             //self.entity.increase_position(vec3(0.01, 0.0, 0.0));
@@ -290,9 +428,9 @@ impl MainGameLoop {
             //self.entity.increase_position(vec3(0.05, 0.0, 0.0));
             //self.camera.mover(&mut self.dm.window);
             self.camera.mover_camara(&self.player);
-            self.player.mover(&mut self.dm, &self.terrain);
+            self.player.mover(&mut self.dm, &self.terrain_world);
 
-            self.picker.update(&mut self.camera);
+            self.picker.update(&mut self.camera, &self.terrain_world);
             let terrain_point: Option<V3CG> = self.picker.get_current_terrain_point();
             if terrain_point.is_some() {
                 let p_terrain = terrain_point.unwrap();
@@ -306,11 +444,45 @@ impl MainGameLoop {
             } else {
                 //dbg!(self.picker.get_current_ray());
             }
+            // Con la P pulsada se dice qué entidad hay bajo el ratón, la más cercana cuya caja
+            // corta el rayo
+            if self.dm.window.get_key(Key::P) == Action::Press {
+                let origin = self.camera.get_position().to_vec();
+                let ray = self.picker.get_current_ray();
+                let picked = self.entities.iter()
+                    .filter_map(|entity| entity.get_world_bounding_box().intersect_ray(origin, ray)
+                        .map(|distance| (distance, entity.id)))
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                if let Some((distance, id)) = picked {
+                    println!("Entidad {} a {:.1}", id, distance);
+                }
+            }
 
+            // La O exporta el tile que pisa el jugador a terrain.obj y terrain.ply, una vez por
+            // pulsación
+            let export_key = self.dm.window.get_key(Key::O) == Action::Press;
+            if export_key && !export_pressed {
+                let position = self.player.entity.get_position();
+                if let Some(terrain) = self.terrain_world.get_terrain(position.x, position.z) {
+                    let data = terrain.to_model_data();
+                    let result = mesh_exporter::export_obj(&data, "terrain.obj").and_then(|_|
+                        mesh_exporter::export_ply(&data, "terrain.ply", PlyFormat::BinaryLittleEndian));
+                    match result {
+                        Ok(()) => println!("Tile ({}, {}) exportado a terrain.obj y terrain.ply",
+                                           terrain.get_grid_x(), terrain.get_grid_z()),
+                        Err(error) => println!("{}", error),
+                    }
+                }
+            }
+            export_pressed = export_key;
+
+            // Con la I pulsada se dibuja entity a entity, para comparar con el instancing
+            self.renderer.set_instancing(self.dm.window.get_key(Key::I) != Action::Press);
             self.renderer.process_entity(&self.player.entity);
 
-            self.renderer.process_terrain(&self.terrain);
-            //self.renderer.process_terrain(&self.terrain2);
+            for terrain in self.terrain_world.get_terrains() {
+                self.renderer.process_terrain(terrain);
+            }
             for entity in &mut self.entities {
                 self.renderer.process_entity(entity);
             }
@@ -332,8 +504,28 @@ impl MainGameLoop {
         }
         self.gui_renderer.cleanup();
         self.renderer.cleanup();
+        // Soltamos los handles antes de cerrar el contexto OpenGL
+        self.models.clear();
+        self.meshes.clear();
+        self.textures.clear();
+        self.assets.purge();
         self.loader.cleanup();
         self.loader.unbind_vao();
         self.dm.close_display();
     }
+
+    // Punto al azar sobre los tiles de delante (x de -400 a 400, z de -600 a 0) donde la pendiente
+    // no pasa de MAX_VEGETATION_SLOPE; si no lo encuentra se queda con el último
+    fn random_position<R: Rng>(rng: &mut R, terrain_world: &TerrainWorld) -> V3CG {
+        let mut position = vec3(0.0, 0.0, 0.0);
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let x = rng.gen_range(0.0, 1.0) * 800.0 - 400.0;
+            let z = rng.gen_range(0.0, 1.0) * -600.0;
+            position = vec3(x, terrain_world.get_height_of_terrain(x, z), z);
+            if terrain_world.get_slope_of_terrain(x, z) <= MAX_VEGETATION_SLOPE {
+                break;
+            }
+        }
+        position
+    }
 }
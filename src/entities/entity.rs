@@ -1,6 +1,13 @@
+use cgmath::{InnerSpace, SquareMatrix};
+
+use crate::models::bounds::{BoundingBox, BoundingSphere};
+use crate::models::lod_chain::LodChain;
+use crate::models::raw_model::RawModel;
 use crate::models::textured_model::TexturedModel;
+use crate::toolbox::maths::create_transformation_matrix;
 
 type V3CG = cgmath::Vector3<f32>;
+type M4CG = cgmath::Matrix4<f32>;
 
 #[derive(Debug, Copy)]
 pub struct Entity {
@@ -10,12 +17,12 @@ pub struct Entity {
     rotation: V3CG,
     scale: V3CG,
     texture_index: i32,
+    local_transform: M4CG, // se aplica al modelo antes que la posición, rotación y escala
 }
 
 impl Clone for Entity {
     fn clone(&self) -> Self {
-        Entity::new2(self.id, self.model, self.texture_index, self.position,
-                     self.rotation, self.scale, )
+        *self
     }
 }
 
@@ -30,6 +37,7 @@ impl Entity {
             rotation,
             scale,
             texture_index: 0,
+            local_transform: M4CG::identity(),
         }
     }
 
@@ -42,6 +50,7 @@ impl Entity {
             rotation,
             scale,
             texture_index: index,
+            local_transform: M4CG::identity(),
         }
     }
 
@@ -123,4 +132,53 @@ impl Entity {
     pub fn _set_scale(&mut self, scale: V3CG) {
         self.scale = scale;
     }
+
+    // Para modelos cuyas partes tienen su propia transformación (los nodos de un glTF)
+    pub fn set_local_transform(&mut self, local_transform: M4CG) {
+        self.local_transform = local_transform;
+    }
+
+    // Modelo -> mundo: transformación local, después posición, rotación en grados y escala
+    pub fn get_transformation_matrix(&self) -> M4CG {
+        create_transformation_matrix(self.position, self.rotation.x, self.rotation.y,
+                                     self.rotation.z, self.scale) * self.local_transform
+    }
+
+    // Caja del modelo girada, escalada y trasladada, alineada de nuevo con los ejes del mundo
+    pub fn get_world_bounding_box(&self) -> BoundingBox {
+        self.model.get_raw_model().get_bounding_box().transform(&self.get_transformation_matrix())
+    }
+
+    pub fn get_world_bounding_sphere(&self) -> BoundingSphere {
+        self.model.get_raw_model().get_bounding_sphere()
+            .transform(&self.get_transformation_matrix())
+    }
+
+    // Nivel de detalle según la distancia de la cámara a la esfera envolvente o el tamaño de
+    // esta en pantalla. projection_scale es projection_matrix[1][1] (1 / tan(fov / 2)), con él
+    // radio * projection_scale / distancia es la fracción de la altura de la pantalla que ocupa
+    pub fn get_lod_level(&self, camera_position: V3CG, projection_scale: f32) -> usize {
+        match self.model.get_lod_chain() {
+            Some(chain) => self.select_lod_level(&chain, camera_position, projection_scale),
+            None => 0,
+        }
+    }
+
+    // Como get_lod_level pero con la cadena dada, que puede no ser la del modelo de la entity:
+    // la igualdad de TexturedModel no mira la cadena, así que un lote puede mezclar varias
+    pub fn select_lod_level(&self, chain: &LodChain, camera_position: V3CG,
+                            projection_scale: f32) -> usize {
+        let sphere = self.get_world_bounding_sphere();
+        let distance = (sphere.get_center() - camera_position).magnitude();
+        let screen_size = if distance > sphere.get_radius() {
+            sphere.get_radius() * projection_scale / distance
+        } else {
+            f32::INFINITY
+        };
+        chain.select_level(distance, screen_size)
+    }
+
+    pub fn get_lod_model(&self, camera_position: V3CG, projection_scale: f32) -> RawModel {
+        self.model.get_lod_model(self.get_lod_level(camera_position, projection_scale))
+    }
 }
\ No newline at end of file
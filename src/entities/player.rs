@@ -5,7 +5,7 @@ use glfw::ffi::KEY_ESCAPE;
 use crate::entities::entity::Entity;
 use crate::models::textured_model::TexturedModel;
 use crate::render_engine::display_manager::DisplayManager;
-use crate::terrains::terrain::Terrain;
+use crate::terrains::terrain_world::TerrainWorld;
 use crate::toolbox::teclado::Teclado;
 
 type V3CG = cgmath::Vector3<f32>;
@@ -36,7 +36,7 @@ impl Player {
             is_in_air: false, // Para que no llegue al cielo al saltar
         }
     }
-    pub fn mover(&mut self, dm: &mut DisplayManager, terrain: &Terrain) {
+    pub fn mover(&mut self, dm: &mut DisplayManager, world: &TerrainWorld) {
         self.check_inputs(&mut dm.window);
         self.entity.increase_rotation(
             vec3(0.0, self.current_turn_speed * dm.get_frame_time_seconds(), 0.0));
@@ -53,8 +53,9 @@ impl Player {
         self.entity.increase_position(
             vec3(0.0, self.upwards_speed * dm.get_frame_time_seconds(), 0.0));
 
+        // Fuera de los tiles el suelo está a 0
         let terrain_height =
-            terrain.get_height_of_terrain(self.entity.get_position().x, self.entity.get_position().z);
+            world.get_height_of_terrain(self.entity.get_position().x, self.entity.get_position().z);
 
         if self.entity.get_position().y < terrain_height {
             self.upwards_speed = 0.0;
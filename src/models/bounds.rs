@@ -0,0 +1,185 @@
+use cgmath::{vec3, InnerSpace, Matrix};
+
+type V3CG = cgmath::Vector3<f32>;
+type M4CG = cgmath::Matrix4<f32>;
+
+// Caja alineada con los ejes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    min: V3CG,
+    max: V3CG,
+}
+
+impl BoundingBox {
+    pub fn new(min: V3CG, max: V3CG) -> BoundingBox {
+        BoundingBox {
+            min,
+            max,
+        }
+    }
+
+    // Caja vacía en el origen, para mallas sin vértices
+    pub fn empty() -> BoundingBox {
+        BoundingBox::new(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0))
+    }
+
+    // positions: x, y, z seguidos, como en ModelData
+    pub fn from_positions(positions: &[f32]) -> BoundingBox {
+        let mut points = positions.chunks(3).map(|p| vec3(p[0], p[1], p[2]));
+        let first = match points.next() {
+            Some(first) => first,
+            None => return BoundingBox::empty(),
+        };
+        points.fold(BoundingBox::new(first, first), |bounds, point| bounds.expand(point))
+    }
+
+    pub fn get_min(&self) -> V3CG {
+        self.min
+    }
+
+    pub fn get_max(&self) -> V3CG {
+        self.max
+    }
+
+    pub fn get_center(&self) -> V3CG {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn get_size(&self) -> V3CG {
+        self.max - self.min
+    }
+
+    // Mitad del tamaño
+    pub fn get_extents(&self) -> V3CG {
+        self.get_size() * 0.5
+    }
+
+    pub fn _get_corners(&self) -> [V3CG; 8] {
+        let (a, b) = (self.min, self.max);
+        [vec3(a.x, a.y, a.z), vec3(b.x, a.y, a.z), vec3(a.x, b.y, a.z), vec3(b.x, b.y, a.z),
+            vec3(a.x, a.y, b.z), vec3(b.x, a.y, b.z), vec3(a.x, b.y, b.z), vec3(b.x, b.y, b.z)]
+    }
+
+    pub fn expand(&self, point: V3CG) -> BoundingBox {
+        let min = vec3(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z));
+        let max = vec3(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z));
+        BoundingBox::new(min, max)
+    }
+
+    pub fn _union(&self, other: &BoundingBox) -> BoundingBox {
+        self.expand(other.min).expand(other.max)
+    }
+
+    pub fn _contains_point(&self, point: V3CG) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x &&
+            point.y >= self.min.y && point.y <= self.max.y &&
+            point.z >= self.min.z && point.z <= self.max.z
+    }
+
+    pub fn _intersects(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x &&
+            self.min.y <= other.max.y && self.max.y >= other.min.y &&
+            self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+
+    // Caja alineada que contiene la caja transformada (centro transformado y semiejes
+    // proyectados con el valor absoluto de la matriz)
+    pub fn transform(&self, matrix: &M4CG) -> BoundingBox {
+        let center = (matrix * self.get_center().extend(1.0)).truncate();
+        let extents = self.get_extents();
+        let mut world_extents = vec3(0.0, 0.0, 0.0);
+        for axis in 0..3 {
+            let row = matrix.row(axis);
+            world_extents[axis] = row.x.abs() * extents.x + row.y.abs() * extents.y +
+                row.z.abs() * extents.z;
+        }
+        BoundingBox::new(center - world_extents, center + world_extents)
+    }
+
+    // Distancia desde origin a lo largo de direction hasta la caja (0 si origin está dentro)
+    pub fn intersect_ray(&self, origin: V3CG, direction: V3CG) -> Option<f32> {
+        let mut near = f32::NEG_INFINITY;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            if direction[axis].abs() < f32::EPSILON {
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (self.min[axis] - origin[axis]) / direction[axis];
+            let t2 = (self.max[axis] - origin[axis]) / direction[axis];
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+        if near > far || far < 0.0 { None } else { Some(near.max(0.0)) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    center: V3CG,
+    radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: V3CG, radius: f32) -> BoundingSphere {
+        BoundingSphere {
+            center,
+            radius,
+        }
+    }
+
+    pub fn empty() -> BoundingSphere {
+        BoundingSphere::new(vec3(0.0, 0.0, 0.0), 0.0)
+    }
+
+    // Centrada en la caja, con el radio hasta el vértice más lejano
+    pub fn from_positions(positions: &[f32]) -> BoundingSphere {
+        let center = BoundingBox::from_positions(positions).get_center();
+        let radius2 = positions.chunks(3)
+            .map(|p| (vec3(p[0], p[1], p[2]) - center).magnitude2())
+            .fold(0.0, f32::max);
+        BoundingSphere::new(center, radius2.sqrt())
+    }
+
+    pub fn get_center(&self) -> V3CG {
+        self.center
+    }
+
+    pub fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn _contains_point(&self, point: V3CG) -> bool {
+        (point - self.center).magnitude2() <= self.radius * self.radius
+    }
+
+    pub fn _intersects(&self, other: &BoundingSphere) -> bool {
+        let distance = self.radius + other.radius;
+        (other.center - self.center).magnitude2() <= distance * distance
+    }
+
+    // El radio se multiplica por la mayor escala de la matriz
+    pub fn transform(&self, matrix: &M4CG) -> BoundingSphere {
+        let center = (matrix * self.center.extend(1.0)).truncate();
+        let scale = matrix.x.truncate().magnitude()
+            .max(matrix.y.truncate().magnitude())
+            .max(matrix.z.truncate().magnitude());
+        BoundingSphere::new(center, self.radius * scale)
+    }
+
+    // direction normalizada. 0 si origin está dentro
+    pub fn _intersect_ray(&self, origin: V3CG, direction: V3CG) -> Option<f32> {
+        let to_center = self.center - origin;
+        let projection = to_center.dot(direction);
+        let distance2 = to_center.magnitude2() - projection * projection;
+        let radius2 = self.radius * self.radius;
+        if distance2 > radius2 {
+            return None;
+        }
+        let half_chord = (radius2 - distance2).sqrt();
+        let far = projection + half_chord;
+        if far < 0.0 { None } else { Some((projection - half_chord).max(0.0)) }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::models::raw_model::RawModel;
+
+// Niveles de detalle por modelo, contando el original
+pub const MAX_LODS: usize = 4;
+
+// Cómo se elige el nivel de detalle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LodMetric {
+    // Umbral = distancia de la cámara a partir de la que se usa el nivel
+    Distance,
+    // Umbral = fracción de la altura de la pantalla que ocupa la esfera envolvente por debajo
+    // de la que se usa el nivel
+    ScreenSize,
+}
+
+// Mallas de un modelo de más a menos detalle. El nivel 0 es el original y no tiene umbral
+#[derive(Debug, Clone, Copy)]
+pub struct LodChain {
+    models: [RawModel; MAX_LODS],
+    thresholds: [f32; MAX_LODS],
+    count: usize,
+    metric: LodMetric,
+}
+
+impl LodChain {
+    pub fn new(base: RawModel, metric: LodMetric) -> LodChain {
+        LodChain {
+            models: [base; MAX_LODS],
+            thresholds: [0.0; MAX_LODS],
+            count: 1,
+            metric,
+        }
+    }
+
+    // Los niveles se añaden de más a menos detalle: con Distance cada umbral tiene que ser
+    // mayor que el anterior y con ScreenSize menor
+    pub fn add_level(&mut self, raw_model: RawModel, threshold: f32) -> Result<(), String> {
+        if self.count == MAX_LODS {
+            return Err(format!("A model can have at most {} levels of detail", MAX_LODS));
+        }
+        if self.count > 1 {
+            let previous = self.thresholds[self.count - 1];
+            let ordered = match self.metric {
+                LodMetric::Distance => threshold > previous,
+                LodMetric::ScreenSize => threshold < previous,
+            };
+            if !ordered {
+                return Err(format!("LOD threshold {} is out of order (previous {})",
+                                   threshold, previous));
+            }
+        }
+        self.models[self.count] = raw_model;
+        self.thresholds[self.count] = threshold;
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn get_level_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn _get_metric(&self) -> LodMetric {
+        self.metric
+    }
+
+    pub fn get_raw_model(&self, level: usize) -> RawModel {
+        self.models[level.min(self.count - 1)]
+    }
+
+    pub fn _get_threshold(&self, level: usize) -> f32 {
+        self.thresholds[level.min(self.count - 1)]
+    }
+
+    // distance: de la cámara al centro de la esfera envolvente
+    // screen_size: fracción de la altura de la pantalla que ocupa la esfera
+    pub fn select_level(&self, distance: f32, screen_size: f32) -> usize {
+        (1..self.count).rev()
+            .find(|&level| match self.metric {
+                LodMetric::Distance => distance >= self.thresholds[level],
+                LodMetric::ScreenSize => screen_size < self.thresholds[level],
+            })
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tres niveles con VAOs falsos, el id del VAO es el nivel
+    fn chain(metric: LodMetric, thresholds: [f32; 2]) -> LodChain {
+        let mut chain = LodChain::new(RawModel::new(0, 30), metric);
+        chain.add_level(RawModel::new(1, 15), thresholds[0]).unwrap();
+        chain.add_level(RawModel::new(2, 6), thresholds[1]).unwrap();
+        chain
+    }
+
+    #[test]
+    fn distance_selects_the_last_threshold_passed() {
+        let chain = chain(LodMetric::Distance, [60.0, 150.0]);
+        assert_eq!(chain.select_level(10.0, 0.0), 0);
+        assert_eq!(chain.select_level(60.0, 0.0), 1);
+        assert_eq!(chain.select_level(149.0, 0.0), 1);
+        assert_eq!(chain.select_level(500.0, 0.0), 2);
+        assert_eq!(chain.get_raw_model(2).get_vao_id(), 2);
+    }
+
+    #[test]
+    fn screen_size_selects_smaller_levels_as_the_model_shrinks() {
+        let chain = chain(LodMetric::ScreenSize, [0.2, 0.05]);
+        assert_eq!(chain.select_level(0.0, f32::INFINITY), 0);
+        assert_eq!(chain.select_level(0.0, 0.2), 0);
+        assert_eq!(chain.select_level(0.0, 0.1), 1);
+        assert_eq!(chain.select_level(0.0, 0.01), 2);
+    }
+
+    #[test]
+    fn levels_must_be_ordered_and_limited() {
+        let mut distance = LodChain::new(RawModel::new(0, 30), LodMetric::Distance);
+        distance.add_level(RawModel::new(1, 15), 60.0).unwrap();
+        assert!(distance.add_level(RawModel::new(2, 6), 30.0).is_err());
+        let mut screen_size = chain(LodMetric::ScreenSize, [0.2, 0.05]);
+        assert!(screen_size.add_level(RawModel::new(3, 3), 0.1).is_err());
+        screen_size.add_level(RawModel::new(3, 3), 0.01).unwrap();
+        assert!(screen_size.add_level(RawModel::new(4, 1), 0.001).is_err());
+        assert_eq!(screen_size.get_level_count(), MAX_LODS);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod bounds;
+pub mod lod_chain;
 pub mod raw_model;
 pub mod textured_model;
\ No newline at end of file
@@ -1,14 +1,31 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use gl;
+use gl::types::*;
+
+use std::hash::{Hash, Hasher};
+
+use crate::models::bounds::{BoundingBox, BoundingSphere};
+
+#[derive(Debug, Clone, Copy)]
 pub struct RawModel {
     vao_id: u32,
     vertex_count: i32,
+    index_type: GLenum, // gl::UNSIGNED_SHORT o gl::UNSIGNED_INT, para glDrawElements
+    bounding_box: BoundingBox,       // en espacio del modelo
+    bounding_sphere: BoundingSphere, // en espacio del modelo
 }
 
 impl RawModel {
     pub fn new(vao_id: u32, vertex_count: i32) -> RawModel {
+        RawModel::with_index_type(vao_id, vertex_count, gl::UNSIGNED_INT)
+    }
+
+    pub fn with_index_type(vao_id: u32, vertex_count: i32, index_type: GLenum) -> RawModel {
         RawModel {
             vao_id,
             vertex_count,
+            index_type,
+            bounding_box: BoundingBox::empty(),
+            bounding_sphere: BoundingSphere::empty(),
         }
     }
 
@@ -19,4 +36,46 @@ impl RawModel {
     pub fn get_vertex_count(&self) -> i32 {
         self.vertex_count
     }
-}
\ No newline at end of file
+
+    // Para modelos dinámicos, cuyo número de índices cambia al actualizarlos
+    pub fn _set_vertex_count(&mut self, vertex_count: i32) {
+        self.vertex_count = vertex_count;
+    }
+
+    pub fn get_index_type(&self) -> GLenum {
+        self.index_type
+    }
+
+    pub fn get_bounding_box(&self) -> BoundingBox {
+        self.bounding_box
+    }
+
+    pub fn get_bounding_sphere(&self) -> BoundingSphere {
+        self.bounding_sphere
+    }
+
+    // Los pone el Loader al subir la malla; los modelos dinámicos los actualizan al cambiarla
+    pub fn set_bounds(&mut self, bounding_box: BoundingBox, bounding_sphere: BoundingSphere) {
+        self.bounding_box = bounding_box;
+        self.bounding_sphere = bounding_sphere;
+    }
+}
+
+// Mismo modelo si es el mismo VAO con el mismo número y tipo de índices, los volúmenes
+// salen de los vértices
+impl PartialEq for RawModel {
+    fn eq(&self, other: &RawModel) -> bool {
+        self.vao_id == other.vao_id && self.vertex_count == other.vertex_count &&
+            self.index_type == other.index_type
+    }
+}
+
+impl Eq for RawModel {}
+
+impl Hash for RawModel {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.vao_id.hash(state);
+        self.vertex_count.hash(state);
+        self.index_type.hash(state);
+    }
+}
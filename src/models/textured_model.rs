@@ -1,3 +1,6 @@
+use std::hash::{Hash, Hasher};
+
+use crate::models::lod_chain::LodChain;
 use crate::models::raw_model::RawModel;
 use crate::textures::model_texture::ModelTexture;
 
@@ -5,6 +8,7 @@ use crate::textures::model_texture::ModelTexture;
 pub struct TexturedModel {
     pub raw_model: RawModel,
     texture: ModelTexture,
+    lod_chain: Option<LodChain>,
 }
 
 impl TexturedModel {
@@ -12,6 +16,7 @@ impl TexturedModel {
         TexturedModel {
             raw_model,
             texture,
+            lod_chain: None,
         }
     }
 
@@ -22,4 +27,41 @@ impl TexturedModel {
     pub fn get_texture(&self) -> ModelTexture {
         self.texture
     }
-}
\ No newline at end of file
+
+    pub fn get_lod_chain(&self) -> Option<LodChain> {
+        self.lod_chain
+    }
+
+    // El nivel 0 de la cadena debería ser raw_model
+    pub fn set_lod_chain(&mut self, lod_chain: LodChain) {
+        self.lod_chain = Some(lod_chain);
+    }
+
+
+    // Malla del nivel de detalle, raw_model si no hay cadena
+    pub fn get_lod_model(&self, level: usize) -> RawModel {
+        match self.lod_chain {
+            Some(chain) if level > 0 => chain.get_raw_model(level),
+            _ => self.raw_model,
+        }
+    }
+}
+
+// Dos TexturedModel son el mismo lote si comparten VAO y textura (los niveles de detalle se
+// separan al renderizar), así el MasterRenderer puede
+// agrupar las entities por modelo en un HashMap
+impl PartialEq for TexturedModel {
+    fn eq(&self, other: &TexturedModel) -> bool {
+        self.raw_model.get_vao_id() == other.raw_model.get_vao_id() &&
+            self.texture.get_id() == other.texture.get_id()
+    }
+}
+
+impl Eq for TexturedModel {}
+
+impl Hash for TexturedModel {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.raw_model.get_vao_id().hash(state);
+        self.texture.get_id().hash(state);
+    }
+}
@@ -0,0 +1,383 @@
+use cgmath::vec3;
+use memmap::Mmap;
+
+use std::fs::{self, File};
+use std::path::Path;
+use std::slice;
+
+use crate::models::bounds::{BoundingBox, BoundingSphere};
+use crate::obj_converter::model_data::ModelData;
+use crate::obj_converter::obj_file_loader::OBJFileLoader;
+use crate::obj_converter::obj_load_options::{NormalMode, ObjLoadOptions};
+use crate::render_engine::vertex_layout::as_bytes;
+
+const MAGIC: [u8; 4] = *b"RMSH";
+// Subir al cambiar la disposición del fichero, las cachés antiguas se regeneran solas
+pub const BINARY_MESH_VERSION: u32 = 4;
+pub const BINARY_MESH_EXTENSION: &str = "mesh";
+
+// Cabecera, todo en little endian y palabras de 4 bytes:
+//   0  magic "RMSH"
+//   1  versión
+//   2  número de vértices
+//   3  número de índices
+//   4  opciones de carga del OBJ: bit 0 normales planas, bit 1 forzar normales, bit 2 tangentes,
+//      bit 3 optimizada para la caché de vértices, bit 4 optimizada para el overdraw. El bit 5
+//      indica si el fichero trae tangentes, que puede no pasar aunque se pidieran (sin uv)
+//   5  ángulo de pliegue (f32)
+//   6  furthest_point (f32)
+//   7  mínimo x, y, z (f32)
+//   10 máximo x, y, z (f32)
+//   13 centro x, y, z y radio de la esfera envolvente (f32)
+// Detrás van posiciones (3 f32), uv (2 f32), normales (3 f32) y, con el bit 5, tangentes (4 f32)
+// de cada vértice y al final los índices (u32)
+const HEADER_WORDS: usize = 17;
+const TANGENTS_BIT: u32 = 4;
+const HAS_TANGENTS_BIT: u32 = 32;
+
+// Bytes de la malla: el fichero mapeado en memoria o, si no se pudo escribir, una copia
+enum MeshBytes {
+    Mapped(Mmap),
+    Owned(Vec<u32>), // u32 para que los f32 queden alineados
+}
+
+// Malla en formato binario. Los arrays se leen directamente de los bytes, sin copiarlos, y
+// se pueden pasar tal cual a Loader::load_binary_mesh
+pub struct BinaryMesh {
+    bytes: MeshBytes,
+    vertex_count: usize,
+    index_count: usize,
+}
+
+impl BinaryMesh {
+    pub fn open(path: &str) -> Result<BinaryMesh, String> {
+        let file = File::open(path).map_err(|e| format!("Could not open mesh {}: {}", path, e))?;
+        // El fichero no se debe modificar mientras esté mapeado: la caché se reescribe entera
+        // en un fichero nuevo y se renombra
+        let mmap = unsafe { Mmap::map(&file) }
+            .map_err(|e| format!("Could not map mesh {}: {}", path, e))?;
+        BinaryMesh::from_bytes(MeshBytes::Mapped(mmap))
+            .map_err(|e| format!("Invalid mesh {}: {}", path, e))
+    }
+
+    pub fn write(path: &str, data: &ModelData, options: &ObjLoadOptions) -> Result<(), String> {
+        let words = BinaryMesh::encode(data, options);
+        let temporary = format!("{}.tmp", path);
+        fs::write(&temporary, as_bytes(&words))
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| format!("Could not write mesh {}: {}", path, e))
+    }
+
+    pub fn from_model_data(data: &ModelData, options: &ObjLoadOptions)
+                           -> Result<BinaryMesh, String> {
+        BinaryMesh::from_bytes(MeshBytes::Owned(BinaryMesh::encode(data, options)))
+    }
+
+    // "res/bunny.obj" -> "res/bunny.mesh"
+    pub fn get_cache_path(obj_file_name: &str) -> String {
+        Path::new(obj_file_name).with_extension(BINARY_MESH_EXTENSION)
+            .to_string_lossy().into_owned()
+    }
+
+    // Usa la caché junto al OBJ si es más nueva que él y se hizo con las mismas opciones; si no,
+    // lee el OBJ y la regenera. Si no se puede escribir, se avisa por stderr y la malla se queda
+    // en memoria
+    pub fn load_cached(obj_file_name: &str, options: &ObjLoadOptions)
+                       -> Result<BinaryMesh, String> {
+        let cache_path = BinaryMesh::get_cache_path(obj_file_name);
+        if BinaryMesh::is_cache_fresh(obj_file_name, &cache_path) {
+            if let Ok(mesh) = BinaryMesh::open(&cache_path) {
+                // Se comparan las opciones pedidas, no si al final hubo tangentes
+                if mesh.get_options_word() & !HAS_TANGENTS_BIT == BinaryMesh::options_word(options)
+                    && mesh.get_crease_angle() == options.get_crease_angle() {
+                    return Ok(mesh);
+                }
+            }
+        }
+        let data = OBJFileLoader::load_obj_with_options(obj_file_name, options)
+            .map_err(|e| format!("Could not load model {}: {}", obj_file_name, e))?;
+        match BinaryMesh::write(&cache_path, &data, options) {
+            Ok(()) => BinaryMesh::open(&cache_path),
+            Err(e) => {
+                eprintln!("{}", e);
+                BinaryMesh::from_model_data(&data, options)
+                    .map_err(|e| format!("Invalid mesh {}: {}", obj_file_name, e))
+            }
+        }
+    }
+
+    pub fn _get_vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    pub fn _get_index_count(&self) -> usize {
+        self.index_count
+    }
+
+    pub fn get_vertices(&self) -> &[f32] {
+        self.get_floats(HEADER_WORDS, self.vertex_count * 3)
+    }
+
+    pub fn get_texture_coords(&self) -> &[f32] {
+        self.get_floats(HEADER_WORDS + self.vertex_count * 3, self.vertex_count * 2)
+    }
+
+    pub fn get_normals(&self) -> &[f32] {
+        self.get_floats(HEADER_WORDS + self.vertex_count * 5, self.vertex_count * 3)
+    }
+
+    // Vacío si el OBJ se cargó sin generar tangentes
+    pub fn get_tangents(&self) -> &[f32] {
+        let count = if self.has_tangents() { self.vertex_count * 4 } else { 0 };
+        self.get_floats(HEADER_WORDS + self.vertex_count * 8, count)
+    }
+
+    pub fn has_tangents(&self) -> bool {
+        self.get_options_word() & HAS_TANGENTS_BIT != 0
+    }
+
+    pub fn get_indices(&self) -> &[u32] {
+        let start = HEADER_WORDS + self.vertex_count * self.get_vertex_words();
+        &self.get_words()[start..start + self.index_count]
+    }
+
+    pub fn get_furthest_point(&self) -> f32 {
+        f32::from_bits(self.get_words()[6])
+    }
+
+    pub fn get_bounding_box(&self) -> BoundingBox {
+        let bounds = self.get_floats(7, 6);
+        BoundingBox::new(vec3(bounds[0], bounds[1], bounds[2]),
+                         vec3(bounds[3], bounds[4], bounds[5]))
+    }
+
+    pub fn get_bounding_sphere(&self) -> BoundingSphere {
+        let sphere = self.get_floats(13, 4);
+        BoundingSphere::new(vec3(sphere[0], sphere[1], sphere[2]), sphere[3])
+    }
+
+    // Copia a ModelData, para quien necesite modificar la malla
+    pub fn to_model_data(&self) -> ModelData {
+        ModelData::with_tangents(self.get_vertices().to_vec(),
+                                 self.get_texture_coords().to_vec(),
+                                 self.get_normals().to_vec(),
+                                 self.get_tangents().to_vec(),
+                                 self.get_indices().to_vec(),
+                                 self.get_furthest_point())
+    }
+
+    fn get_options_word(&self) -> u32 {
+        self.get_words()[4]
+    }
+
+    // Floats de cada vértice
+    fn get_vertex_words(&self) -> usize {
+        if self.has_tangents() { 12 } else { 8 }
+    }
+
+    fn get_crease_angle(&self) -> f32 {
+        f32::from_bits(self.get_words()[5])
+    }
+
+    fn get_words(&self) -> &[u32] {
+        match &self.bytes {
+            // El mapa empieza en un límite de página y from_bytes ya comprobó el tamaño
+            MeshBytes::Mapped(mmap) => unsafe {
+                slice::from_raw_parts(mmap.as_ptr() as *const u32, mmap.len() / 4)
+            },
+            MeshBytes::Owned(words) => words,
+        }
+    }
+
+    fn get_floats(&self, start: usize, count: usize) -> &[f32] {
+        let words = &self.get_words()[start..start + count];
+        unsafe { slice::from_raw_parts(words.as_ptr() as *const f32, count) }
+    }
+
+    fn from_bytes(bytes: MeshBytes) -> Result<BinaryMesh, String> {
+        // Los arrays se leen sin convertir el orden de los bytes
+        if cfg!(target_endian = "big") {
+            return Err("el formato binario es little endian".to_string());
+        }
+        let length = match &bytes {
+            MeshBytes::Mapped(mmap) => mmap.len(),
+            MeshBytes::Owned(words) => words.len() * 4,
+        };
+        let mut mesh = BinaryMesh { bytes, vertex_count: 0, index_count: 0 };
+        if length < HEADER_WORDS * 4 || length % 4 != 0 {
+            return Err(format!("tamaño {} no válido", length));
+        }
+        let (magic, version, vertex_count, index_count) = {
+            let words = mesh.get_words();
+            (words[0].to_le_bytes(), words[1], words[2] as usize, words[3] as usize)
+        };
+        let vertex_words = mesh.get_vertex_words();
+        if magic != MAGIC {
+            return Err("no es una malla binaria".to_string());
+        }
+        if version != BINARY_MESH_VERSION {
+            return Err(format!("versión {}, se esperaba {}", version, BINARY_MESH_VERSION));
+        }
+        let expected = (HEADER_WORDS + vertex_count * vertex_words + index_count) * 4;
+        if length != expected {
+            return Err(format!("tiene {} bytes y debería tener {}", length, expected));
+        }
+        if index_count % 3 != 0 {
+            return Err(format!("{} índices no forman triángulos", index_count));
+        }
+        mesh.vertex_count = vertex_count;
+        mesh.index_count = index_count;
+        let out_of_range = mesh.get_indices().iter()
+            .find(|&&index| index as usize >= vertex_count);
+        if let Some(&index) = out_of_range {
+            return Err(format!("índice {} fuera de rango (hay {} vértices)", index, vertex_count));
+        }
+        Ok(mesh)
+    }
+
+    fn encode(data: &ModelData, options: &ObjLoadOptions) -> Vec<u32> {
+        let vertices = data.get_vertices();
+        let vertex_count = vertices.len() / 3;
+        let (min, max) = (data.get_bounding_box().get_min(), data.get_bounding_box().get_max());
+        let sphere = data.get_bounding_sphere();
+        let center = sphere.get_center();
+
+        let mut words: Vec<u32> = Vec::with_capacity(
+            HEADER_WORDS + vertex_count * 12 + data.get_indices().len());
+        words.push(u32::from_le_bytes(MAGIC));
+        words.push(BINARY_MESH_VERSION);
+        words.push(vertex_count as u32);
+        words.push(data.get_indices().len() as u32);
+        let tangents = if data.has_tangents() { HAS_TANGENTS_BIT } else { 0 };
+        words.push(BinaryMesh::options_word(options) | tangents);
+        words.push(options.get_crease_angle().to_bits());
+        words.push(data.get_furthest_point().to_bits());
+        words.extend([min.x, min.y, min.z, max.x, max.y, max.z].iter().map(|value| value.to_bits()));
+        words.extend([center.x, center.y, center.z, sphere.get_radius()].iter()
+            .map(|value| value.to_bits()));
+        words.extend(vertices.iter().map(|value| value.to_bits()));
+        words.extend(data.get_texture_coords().iter().map(|value| value.to_bits()));
+        words.extend(data.get_normals().iter().map(|value| value.to_bits()));
+        words.extend(data.get_tangents().iter().map(|value| value.to_bits()));
+        words.extend(data.get_indices().iter());
+        words
+    }
+
+    fn options_word(options: &ObjLoadOptions) -> u32 {
+        let flat = if options.get_normal_mode() == NormalMode::Flat { 1 } else { 0 };
+        let force = if options.is_force_normals() { 2 } else { 0 };
+        let tangents = if options.is_generate_tangents() { TANGENTS_BIT } else { 0 };
+        let optimize = if options.is_optimize() { 8 } else { 0 };
+        let overdraw = if options.is_optimize() && options.is_optimize_overdraw() { 16 } else { 0 };
+        flat | force | tangents | optimize | overdraw
+    }
+
+    // La caché vale si existe y no es más antigua que el OBJ
+    fn is_cache_fresh(obj_file_name: &str, cache_path: &str) -> bool {
+        let modified = |path: &str| fs::metadata(path).and_then(|metadata| metadata.modified());
+        match (modified(obj_file_name), modified(cache_path)) {
+            (Ok(obj), Ok(cache)) => cache >= obj,
+            // Sin el OBJ (solo se distribuye la caché) vale lo que haya
+            (Err(_), Ok(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    fn triangle(tangents: bool) -> ModelData {
+        ModelData::with_tangents(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.0],
+                                 vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+                                 vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0],
+                                 if tangents { [1.0, 0.0, 0.0, 1.0].repeat(3) } else { vec![] },
+                                 vec![0, 1, 2], 2.0)
+    }
+
+    fn assert_same(mesh: &BinaryMesh, data: &ModelData) {
+        assert_eq!(mesh.get_vertices(), &data.get_vertices()[..]);
+        assert_eq!(mesh.get_texture_coords(), &data.get_texture_coords()[..]);
+        assert_eq!(mesh.get_normals(), &data.get_normals()[..]);
+        assert_eq!(mesh.get_tangents(), &data.get_tangents()[..]);
+        assert_eq!(mesh.get_indices(), &data.get_indices()[..]);
+        assert_eq!(mesh.get_furthest_point(), data.get_furthest_point());
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for &tangents in [false, true].iter() {
+            let data = triangle(tangents);
+            let mesh = BinaryMesh::from_model_data(&data, &ObjLoadOptions::new()).unwrap();
+            assert_eq!(mesh.has_tangents(), tangents);
+            assert_eq!((mesh._get_vertex_count(), mesh._get_index_count()), (3, 3));
+            assert_same(&mesh, &data);
+            assert_same(&mesh, &mesh.to_model_data());
+            assert_eq!(mesh.get_bounding_box().get_max(), vec3(1.0, 2.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn written_file_maps_back() {
+        let name = format!("binary_mesh_round_trip_{}.mesh", std::process::id());
+        let path = env::temp_dir().join(name).to_string_lossy().into_owned();
+        let data = triangle(true);
+        BinaryMesh::write(&path, &data, &ObjLoadOptions::new()).unwrap();
+        assert_same(&BinaryMesh::open(&path).unwrap(), &data);
+    }
+
+    #[test]
+    fn rejects_malformed_meshes() {
+        let words = BinaryMesh::encode(&triangle(false), &ObjLoadOptions::new());
+        let decode = |words: Vec<u32>| BinaryMesh::from_bytes(MeshBytes::Owned(words));
+        assert!(decode(words.clone()).is_ok());
+
+        let mut magic = words.clone();
+        magic[0] = 0;
+        assert!(decode(magic).is_err());
+        let mut version = words.clone();
+        version[1] = BINARY_MESH_VERSION + 1;
+        assert!(decode(version).is_err());
+        assert!(decode(words[..words.len() - 1].to_vec()).is_err());
+        assert!(decode(words[..HEADER_WORDS - 1].to_vec()).is_err());
+        // Las tangentes cambian el tamaño esperado
+        let mut tangents = words.clone();
+        tangents[4] |= HAS_TANGENTS_BIT;
+        assert!(decode(tangents).is_err());
+        let mut out_of_range = words.clone();
+        *out_of_range.last_mut().unwrap() = 3;
+        assert!(decode(out_of_range).is_err());
+        // Dos índices no forman un triángulo
+        let mut partial = words.clone();
+        partial[3] = 2;
+        partial.pop();
+        assert!(decode(partial).is_err());
+    }
+
+    #[test]
+    fn cache_is_reused_when_tangents_could_not_be_generated() {
+        // Sin caras no hay tangentes aunque se pidan; la caché tiene que valer igualmente
+        let directory = env::temp_dir();
+        let name = format!("binary_mesh_no_faces_{}.obj", std::process::id());
+        let obj = directory.join(name).to_string_lossy().into_owned();
+        fs::write(&obj, "v 0 0 0\n").unwrap();
+        let cache = BinaryMesh::get_cache_path(&obj);
+        let _ = fs::remove_file(&cache);
+        let mut options = ObjLoadOptions::new();
+        options._set_generate_tangents(true);
+        assert!(!BinaryMesh::load_cached(&obj, &options).unwrap().has_tangents());
+
+        // Se marca la caché para saber si la siguiente carga la usa o la regenera
+        let mut words = BinaryMesh::open(&cache).unwrap().get_words().to_vec();
+        words[6] = 7.0f32.to_bits();
+        fs::write(&cache, as_bytes(&words)).unwrap();
+        assert_eq!(BinaryMesh::load_cached(&obj, &options).unwrap().get_furthest_point(), 7.0);
+
+        // Con otras opciones sí se regenera
+        options._set_generate_tangents(false);
+        assert_eq!(BinaryMesh::load_cached(&obj, &options).unwrap().get_furthest_point(), 0.0);
+    }
+}
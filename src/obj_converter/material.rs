@@ -0,0 +1,137 @@
+use cgmath::vec3;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::obj_converter::obj_error::ObjError;
+
+type V3CG = cgmath::Vector3<f32>;
+
+// Material de un fichero MTL (newmtl)
+#[derive(Debug, Clone)]
+pub struct Material {
+    name: String,
+    diffuse_colour: V3CG,        // Kd
+    specular_colour: V3CG,       // Ks
+    specular_exponent: f32,      // Ns
+    dissolve: f32,               // d, 1 = opaco (Tr es 1 - d)
+    diffuse_map: Option<String>, // map_Kd, con la ruta ya relativa al directorio de trabajo
+    alpha_map: bool,             // map_d
+}
+
+impl Material {
+    pub fn new(name: &str) -> Material {
+        Material {
+            name: name.to_string(),
+            diffuse_colour: vec3(1.0, 1.0, 1.0),
+            specular_colour: vec3(0.0, 0.0, 0.0),
+            specular_exponent: 1.0,
+            dissolve: 1.0,
+            diffuse_map: None,
+            alpha_map: false,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_diffuse_colour(&self) -> V3CG {
+        self.diffuse_colour
+    }
+
+    pub fn get_specular_colour(&self) -> V3CG {
+        self.specular_colour
+    }
+
+    pub fn get_specular_exponent(&self) -> f32 {
+        self.specular_exponent
+    }
+
+    pub fn get_dissolve(&self) -> f32 {
+        self.dissolve
+    }
+
+    pub fn get_diffuse_map(&self) -> Option<&String> {
+        self.diffuse_map.as_ref()
+    }
+
+    // Semitransparente o con máscara de alfa
+    pub fn is_transparent(&self) -> bool {
+        self.dissolve < 1.0 || self.alpha_map
+    }
+
+    // Lee todos los materiales del fichero. Las rutas de las texturas son relativas al .mtl
+    pub fn load_mtl(mtl_file_name: &str) -> Result<Vec<Material>, ObjError> {
+        let fichero = File::open(mtl_file_name)
+            .map_err(|error| ObjError::Io(mtl_file_name.to_string(), error))?;
+        let reader = BufReader::new(fichero);
+        let directory = Path::new(mtl_file_name).parent().unwrap_or(Path::new(""));
+
+        let mut materials: Vec<Material> = vec![];
+        for (number, line) in reader.lines().enumerate() {
+            let line_number = number + 1;
+            let line: String =
+                line.map_err(|error| ObjError::Io(mtl_file_name.to_string(), error))?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let keyword = match tokens.first() {
+                Some(keyword) => *keyword,
+                None => continue,
+            };
+            if keyword == "newmtl" {
+                let name = tokens[1..].join(" ");
+                materials.push(Material::new(&name));
+                continue;
+            }
+            if keyword.starts_with('#') {
+                continue;
+            }
+            let material = match materials.last_mut() {
+                Some(material) => material,
+                None => return Err(ObjError::Parse {
+                    line: line_number,
+                    message: format!("{} antes de newmtl", keyword),
+                }),
+            };
+            match keyword {
+                "Kd" => material.diffuse_colour = Material::parse_colour(&tokens, line_number)?,
+                "Ks" => material.specular_colour = Material::parse_colour(&tokens, line_number)?,
+                "Ns" => material.specular_exponent = Material::parse_float(&tokens, line_number)?,
+                "d" => material.dissolve = Material::parse_float(&tokens, line_number)?,
+                "Tr" => material.dissolve = 1.0 - Material::parse_float(&tokens, line_number)?,
+                // Las opciones (-s, -o, -bm...) van antes del nombre, que es el último
+                "map_Kd" => material.diffuse_map = tokens.last()
+                    .filter(|_| tokens.len() > 1)
+                    .map(|file| directory.join(file).to_string_lossy().into_owned()),
+                "map_d" => material.alpha_map = true,
+                // Ka, Ke, illum, Ni, mapas que no usamos...
+                _ => {}
+            }
+        }
+        Ok(materials)
+    }
+
+    fn parse_float(tokens: &[&str], line: usize) -> Result<f32, ObjError> {
+        tokens.get(1).and_then(|token| token.parse::<f32>().ok()).ok_or(ObjError::Parse {
+            line,
+            message: format!("valor no válido en {}", tokens[0]),
+        })
+    }
+
+    // "Kd r g b" o "Kd v" (gris)
+    fn parse_colour(tokens: &[&str], line: usize) -> Result<V3CG, ObjError> {
+        let values = tokens[1..].iter().take(3)
+            .map(|token| token.parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|_| ObjError::Parse {
+                line,
+                message: format!("color no válido en {}", tokens[0]),
+            })?;
+        match values.len() {
+            1 => Ok(vec3(values[0], values[0], values[0])),
+            3 => Ok(vec3(values[0], values[1], values[2])),
+            _ => Err(ObjError::Parse { line, message: format!("color incompleto en {}", tokens[0]) }),
+        }
+    }
+}
@@ -1,3 +1,7 @@
+pub mod binary_mesh;
+pub mod material;
 pub mod model_data;
+pub mod obj_error;
 pub mod obj_file_loader;
+pub mod obj_load_options;
 pub mod vertex;
\ No newline at end of file
@@ -1,43 +1,108 @@
-/*
+use crate::models::bounds::{BoundingBox, BoundingSphere};
+use crate::toolbox::mesh_optimizer::OptimizationReport;
 
-package objConverter;
+// Datos de una malla en CPU, listos para Loader::load_to_vao. No tocan OpenGL, así que se pueden
+// preparar en otro hilo
+#[derive(Clone)]
+pub struct ModelData {
+    vertices: Vec<f32>,
+    texture_coords: Vec<f32>,
+    normals: Vec<f32>,
+    tangents: Vec<f32>, // 4 por vértice o vacío si no se generaron
+    indices: Vec<u32>,
+    furthest_point: f32,
+    bounding_box: BoundingBox,
+    bounding_sphere: BoundingSphere,
+    optimization_report: Option<OptimizationReport>, // si se optimizó al cargarla
+}
+
+impl ModelData {
+    pub fn new(vertices: Vec<f32>, texture_coords: Vec<f32>, normals: Vec<f32>, indices: Vec<u32>,
+               furthest_point: f32) -> ModelData {
+        ModelData::with_tangents(vertices, texture_coords, normals, vec![], indices, furthest_point)
+    }
+
+    pub fn with_tangents(vertices: Vec<f32>, texture_coords: Vec<f32>, normals: Vec<f32>,
+                         tangents: Vec<f32>, indices: Vec<u32>, furthest_point: f32) -> ModelData {
+        let bounding_box = BoundingBox::from_positions(&vertices);
+        let bounding_sphere = BoundingSphere::from_positions(&vertices);
+        ModelData {
+            bounding_box,
+            bounding_sphere,
+            vertices,
+            texture_coords,
+            normals,
+            tangents,
+            indices,
+            furthest_point,
+            optimization_report: None,
+        }
+    }
+
+    pub fn get_vertices(&self) -> &Vec<f32> {
+        &self.vertices
+    }
 
-public class ModelData {
+    pub fn get_texture_coords(&self) -> &Vec<f32> {
+        &self.texture_coords
+    }
 
-	private float[] vertices;
-	private float[] textureCoords;
-	private float[] normals;
-	private int[] indices;
-	private float furthestPoint;
+    pub fn get_normals(&self) -> &Vec<f32> {
+        &self.normals
+    }
 
-	public ModelData(float[] vertices, float[] textureCoords, float[] normals, int[] indices,
-			float furthestPoint) {
-		this.vertices = vertices;
-		this.textureCoords = textureCoords;
-		this.normals = normals;
-		this.indices = indices;
-		this.furthestPoint = furthestPoint;
-	}
+    pub fn get_tangents(&self) -> &Vec<f32> {
+        &self.tangents
+    }
 
-	public float[] getVertices() {
-		return vertices;
-	}
+    pub fn has_tangents(&self) -> bool {
+        !self.tangents.is_empty()
+    }
 
-	public float[] getTextureCoords() {
-		return textureCoords;
-	}
+    pub fn get_indices(&self) -> &Vec<u32> {
+        &self.indices
+    }
 
-	public float[] getNormals() {
-		return normals;
-	}
+    pub fn get_furthest_point(&self) -> f32 {
+        self.furthest_point
+    }
 
-	public int[] getIndices() {
-		return indices;
-	}
+    pub fn get_bounding_box(&self) -> BoundingBox {
+        self.bounding_box
+    }
 
-	public float getFurthestPoint() {
-		return furthestPoint;
-	}
+    pub fn get_bounding_sphere(&self) -> BoundingSphere {
+        self.bounding_sphere
+    }
+
+    pub fn get_optimization_report(&self) -> Option<OptimizationReport> {
+        self.optimization_report
+    }
+
+    pub fn set_optimization_report(&mut self, optimization_report: OptimizationReport) {
+        self.optimization_report = Some(optimization_report);
+    }
+}
 
+// Rejilla de size x size vértices en el plano xz, con y = x * z * bend y dos triángulos por
+// celda, para los tests de las herramientas de mallas
+#[cfg(test)]
+pub fn test_grid(size: usize, bend: f32) -> ModelData {
+    let (mut vertices, mut texture_coords, mut normals, mut indices) =
+        (vec![], vec![], vec![], vec![]);
+    for x in 0..size {
+        for z in 0..size {
+            vertices.extend_from_slice(&[x as f32, (x * z) as f32 * bend, z as f32]);
+            texture_coords.extend_from_slice(&[x as f32, z as f32]);
+            normals.extend_from_slice(&[0.0, 1.0, 0.0]);
+        }
+    }
+    for x in 0..size as u32 - 1 {
+        for z in 0..size as u32 - 1 {
+            let (a, b) = (x * size as u32 + z, x * size as u32 + z + 1);
+            let (c, d) = (a + size as u32, b + size as u32);
+            indices.extend_from_slice(&[a, b, c, c, b, d]);
+        }
+    }
+    ModelData::new(vertices, texture_coords, normals, indices, 0.0)
 }
-*/
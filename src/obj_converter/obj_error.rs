@@ -0,0 +1,25 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// Errores al leer un OBJ. Los de contenido llevan el número de línea (empezando en 1)
+#[derive(Debug)]
+pub enum ObjError {
+    Io(String, io::Error),
+    Parse { line: usize, message: String },
+    IndexOutOfRange { line: usize, kind: &'static str, index: i64, count: usize },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(path, error) => write!(f, "no se puede leer {:?}: {}", path, error),
+            ObjError::Parse { line, message } => write!(f, "línea {}: {}", line, message),
+            ObjError::IndexOutOfRange { line, kind, index, count } =>
+                write!(f, "línea {}: índice de {} {} fuera de rango (hay {})",
+                       line, kind, index, count),
+        }
+    }
+}
+
+impl Error for ObjError {}
@@ -1,166 +1,652 @@
-/*
-
-package objConverter;
-
-import java.io.BufferedReader;
-import java.io.File;
-import java.io.FileNotFoundException;
-import java.io.FileReader;
-import java.io.IOException;
-import java.util.ArrayList;
-import java.util.List;
-
-import org.lwjgl.util.vector.Vector2f;
-import org.lwjgl.util.vector.Vector3f;
-
-public class OBJFileLoader {
-
-	private static final String RES_LOC = "res/";
-
-	public static ModelData loadOBJ(String objFileName) {
-		FileReader isr = null;
-		File objFile = new File(RES_LOC + objFileName + ".obj");
-		try {
-			isr = new FileReader(objFile);
-		} catch (FileNotFoundException e) {
-			System.err.println("File not found in res; don't use any extention");
-		}
-		BufferedReader reader = new BufferedReader(isr);
-		String line;
-		List<Vertex> vertices = new ArrayList<Vertex>();
-		List<Vector2f> textures = new ArrayList<Vector2f>();
-		List<Vector3f> normals = new ArrayList<Vector3f>();
-		List<Integer> indices = new ArrayList<Integer>();
-		try {
-			while (true) {
-				line = reader.readLine();
-				if (line.startsWith("v ")) {
-					String[] currentLine = line.split(" ");
-					Vector3f vertex = new Vector3f((float) Float.valueOf(currentLine[1]),
-							(float) Float.valueOf(currentLine[2]),
-							(float) Float.valueOf(currentLine[3]));
-					Vertex newVertex = new Vertex(vertices.size(), vertex);
-					vertices.add(newVertex);
-
-				} else if (line.startsWith("vt ")) {
-					String[] currentLine = line.split(" ");
-					Vector2f texture = new Vector2f((float) Float.valueOf(currentLine[1]),
-							(float) Float.valueOf(currentLine[2]));
-					textures.add(texture);
-				} else if (line.startsWith("vn ")) {
-					String[] currentLine = line.split(" ");
-					Vector3f normal = new Vector3f((float) Float.valueOf(currentLine[1]),
-							(float) Float.valueOf(currentLine[2]),
-							(float) Float.valueOf(currentLine[3]));
-					normals.add(normal);
-				} else if (line.startsWith("f ")) {
-					break;
-				}
-			}
-			while (line != null && line.startsWith("f ")) {
-				String[] currentLine = line.split(" ");
-				String[] vertex1 = currentLine[1].split("/");
-				String[] vertex2 = currentLine[2].split("/");
-				String[] vertex3 = currentLine[3].split("/");
-				processVertex(vertex1, vertices, indices);
-				processVertex(vertex2, vertices, indices);
-				processVertex(vertex3, vertices, indices);
-				line = reader.readLine();
-			}
-			reader.close();
-		} catch (IOException e) {
-			System.err.println("Error reading the file");
-		}
-		removeUnusedVertices(vertices);
-		float[] verticesArray = new float[vertices.size() * 3];
-		float[] texturesArray = new float[vertices.size() * 2];
-		float[] normalsArray = new float[vertices.size() * 3];
-		float furthest = convertDataToArrays(vertices, textures, normals, verticesArray,
-				texturesArray, normalsArray);
-		int[] indicesArray = convertIndicesListToArray(indices);
-		ModelData data = new ModelData(verticesArray, texturesArray, normalsArray, indicesArray,
-				furthest);
-		return data;
-	}
-
-	private static void processVertex(String[] vertex, List<Vertex> vertices, List<Integer> indices) {
-		int index = Integer.parseInt(vertex[0]) - 1;
-		Vertex currentVertex = vertices.get(index);
-		int textureIndex = Integer.parseInt(vertex[1]) - 1;
-		int normalIndex = Integer.parseInt(vertex[2]) - 1;
-		if (!currentVertex.isSet()) {
-			currentVertex.setTextureIndex(textureIndex);
-			currentVertex.setNormalIndex(normalIndex);
-			indices.add(index);
-		} else {
-			dealWithAlreadyProcessedVertex(currentVertex, textureIndex, normalIndex, indices,
-					vertices);
-		}
-	}
-
-	private static int[] convertIndicesListToArray(List<Integer> indices) {
-		int[] indicesArray = new int[indices.size()];
-		for (int i = 0; i < indicesArray.length; i++) {
-			indicesArray[i] = indices.get(i);
-		}
-		return indicesArray;
-	}
-
-	private static float convertDataToArrays(List<Vertex> vertices, List<Vector2f> textures,
-			List<Vector3f> normals, float[] verticesArray, float[] texturesArray,
-			float[] normalsArray) {
-		float furthestPoint = 0;
-		for (int i = 0; i < vertices.size(); i++) {
-			Vertex currentVertex = vertices.get(i);
-			if (currentVertex.getLength() > furthestPoint) {
-				furthestPoint = currentVertex.getLength();
-			}
-			Vector3f position = currentVertex.getPosition();
-			Vector2f textureCoord = textures.get(currentVertex.getTextureIndex());
-			Vector3f normalVector = normals.get(currentVertex.getNormalIndex());
-			verticesArray[i * 3] = position.x;
-			verticesArray[i * 3 + 1] = position.y;
-			verticesArray[i * 3 + 2] = position.z;
-			texturesArray[i * 2] = textureCoord.x;
-			texturesArray[i * 2 + 1] = 1 - textureCoord.y;
-			normalsArray[i * 3] = normalVector.x;
-			normalsArray[i * 3 + 1] = normalVector.y;
-			normalsArray[i * 3 + 2] = normalVector.z;
-		}
-		return furthestPoint;
-	}
-
-	private static void dealWithAlreadyProcessedVertex(Vertex previousVertex, int newTextureIndex,
-			int newNormalIndex, List<Integer> indices, List<Vertex> vertices) {
-		if (previousVertex.hasSameTextureAndNormal(newTextureIndex, newNormalIndex)) {
-			indices.add(previousVertex.getIndex());
-		} else {
-			Vertex anotherVertex = previousVertex.getDuplicateVertex();
-			if (anotherVertex != null) {
-				dealWithAlreadyProcessedVertex(anotherVertex, newTextureIndex, newNormalIndex,
-						indices, vertices);
-			} else {
-				Vertex duplicateVertex = new Vertex(vertices.size(), previousVertex.getPosition());
-				duplicateVertex.setTextureIndex(newTextureIndex);
-				duplicateVertex.setNormalIndex(newNormalIndex);
-				previousVertex.setDuplicateVertex(duplicateVertex);
-				vertices.add(duplicateVertex);
-				indices.add(duplicateVertex.getIndex());
-			}
-
-		}
-	}
-
-	private static void removeUnusedVertices(List<Vertex> vertices){
-		for(Vertex vertex:vertices){
-			if(!vertex.isSet()){
-				vertex.setTextureIndex(0);
-				vertex.setNormalIndex(0);
-			}
-		}
-	}
+use cgmath::{InnerSpace, vec2, vec3};
 
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::obj_converter::material::Material;
+use crate::obj_converter::model_data::ModelData;
+use crate::obj_converter::obj_error::ObjError;
+use crate::obj_converter::obj_load_options::{NormalMode, ObjLoadOptions};
+use crate::obj_converter::vertex::Vertex;
+use crate::toolbox::{mesh_optimizer, tangent_generator};
+
+type V2CG = cgmath::Vector2<f32>;
+type V3CG = cgmath::Vector3<f32>;
+
+// Esquina de una cara, con índices desde 0 y la línea para los errores
+#[derive(Debug, Clone, Copy)]
+struct FaceVertex {
+    position: usize,
+    texture: Option<usize>,
+    normal: Option<usize>,
+    line: usize,
+}
+
+// Triángulo con el smoothing group activo en su cara. None tras "s off" o "s 0"; antes de la
+// primera línea "s" todas las caras están en el mismo grupo
+struct Triangle {
+    corners: [FaceVertex; 3],
+    smoothing_group: Option<u32>,
+    material: Option<usize>, // índice en ParsedObj::materials
+}
+
+// Contenido del OBJ antes de crear los vértices
+struct ParsedObj {
+    positions: Vec<V3CG>,
+    textures: Vec<V2CG>, // con la textura por defecto al final
+    normals: Vec<V3CG>,  // con las normales generadas
+    materials: Vec<Material>,
+    triangles: Vec<Triangle>,
+    default_texture: usize,
+    warnings: Vec<String>,
+}
+
+// Malla de un material del OBJ
+pub struct ObjPart {
+    material: Option<Material>,
+    data: ModelData,
+}
+
+impl ObjPart {
+    pub fn get_material(&self) -> Option<&Material> {
+        self.material.as_ref()
+    }
+
+    pub fn get_data(&self) -> &ModelData {
+        &self.data
+    }
+}
+
+// Lector de OBJ que duplica los vértices cuya posición se usa con distintas texturas o normales,
+// así las costuras de UV y las aristas duras salen bien
+pub struct OBJFileLoader;
+
+impl OBJFileLoader {
+    #[allow(dead_code)] // de momento solo lo usan los tests
+    pub fn load_obj(obj_file_name: &str) -> Result<ModelData, ObjError> {
+        OBJFileLoader::load_obj_with_options(obj_file_name, &ObjLoadOptions::new())
+    }
+
+    // Todo el fichero en una sola malla, sin tener en cuenta los materiales (ni sus avisos)
+    pub fn load_obj_with_options(obj_file_name: &str, options: &ObjLoadOptions)
+                                 -> Result<ModelData, ObjError> {
+        let obj = OBJFileLoader::parse(obj_file_name, options)?;
+        let triangles: Vec<&Triangle> = obj.triangles.iter().collect();
+        Ok(OBJFileLoader::build_model_data(&obj, &triangles, options))
+    }
+
+    // Una malla por cada material usado (usemtl), en el orden en que aparecen. Las caras
+    // anteriores al primer usemtl forman una parte sin material, igual que las de un usemtl
+    // desconocido. Junto a las partes se devuelven los avisos: mtllib que no se pudo leer y
+    // materiales que no aparecen en ninguna
+    pub fn load_obj_parts(obj_file_name: &str, options: &ObjLoadOptions)
+                          -> Result<(Vec<ObjPart>, Vec<String>), ObjError> {
+        let obj = OBJFileLoader::parse(obj_file_name, options)?;
+        let mut order: Vec<Option<usize>> = vec![];
+        for triangle in obj.triangles.iter() {
+            if !order.contains(&triangle.material) {
+                order.push(triangle.material);
+            }
+        }
+        let parts = order.into_iter().map(|material| {
+            let triangles: Vec<&Triangle> = obj.triangles.iter()
+                .filter(|triangle| triangle.material == material)
+                .collect();
+            ObjPart {
+                material: material.map(|index| obj.materials[index].clone()),
+                data: OBJFileLoader::build_model_data(&obj, &triangles, options),
+            }
+        }).collect();
+        Ok((parts, obj.warnings))
+    }
+
+    // Lee el fichero entero (y sus mtllib) y deja las caras como triángulos con normal
+    fn parse(obj_file_name: &str, options: &ObjLoadOptions) -> Result<ParsedObj, ObjError> {
+        let fichero = File::open(obj_file_name)
+            .map_err(|error| ObjError::Io(obj_file_name.to_string(), error))?;
+        let reader = BufReader::new(fichero);
+        let directory = Path::new(obj_file_name).parent().unwrap_or(Path::new(""));
+
+        let mut positions: Vec<V3CG> = vec![];
+        let mut textures: Vec<V2CG> = vec![];
+        let mut normals: Vec<V3CG> = vec![];
+        let mut materials: Vec<Material> = vec![];
+        // Primero se leen todas las caras como triángulos, después se crean los vértices
+        let mut triangles: Vec<Triangle> = vec![];
+        let mut smoothing_group: Option<u32> = Some(0);
+        let mut material: Option<usize> = None;
+        let mut warnings: Vec<String> = vec![];
+
+        for (number, line) in reader.lines().enumerate() {
+            let line_number = number + 1;
+            let line: String =
+                line.map_err(|error| ObjError::Io(obj_file_name.to_string(), error))?;
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let values = OBJFileLoader::parse_floats(tokens, 3, 3, line_number)?;
+                    positions.push(vec3(values[0], values[1], values[2]));
+                }
+                Some("vt") => {
+                    // La v es opcional, la w se ignora
+                    let values = OBJFileLoader::parse_floats(tokens, 1, 2, line_number)?;
+                    textures.push(vec2(values[0], values.get(1).cloned().unwrap_or(0.0)));
+                }
+                Some("vn") => {
+                    let values = OBJFileLoader::parse_floats(tokens, 3, 3, line_number)?;
+                    normals.push(vec3(values[0], values[1], values[2]));
+                }
+                Some("f") => {
+                    let counts = (positions.len(), textures.len(), normals.len());
+                    let corners = tokens
+                        .map(|token| OBJFileLoader::parse_face_vertex(token, counts, line_number))
+                        .collect::<Result<Vec<FaceVertex>, ObjError>>()?;
+                    if corners.len() < 3 {
+                        return Err(ObjError::Parse {
+                            line: line_number,
+                            message: format!("cara con {} vértices", corners.len()),
+                        });
+                    }
+                    // Polígonos convexos en abanico desde el primer vértice
+                    for i in 1..corners.len() - 1 {
+                        triangles.push(Triangle {
+                            corners: [corners[0], corners[i], corners[i + 1]],
+                            smoothing_group,
+                            material,
+                        });
+                    }
+                }
+                Some("s") => {
+                    // Un valor que no se entiende deja el grupo que había
+                    match tokens.next() {
+                        Some("off") | Some("0") | None => smoothing_group = None,
+                        Some(group) => if let Ok(group) = group.parse::<u32>() {
+                            smoothing_group = Some(group);
+                        },
+                    }
+                }
+                Some("mtllib") => {
+                    // Puede haber varias librerías en la misma línea
+                    for library in tokens {
+                        let path = directory.join(library).to_string_lossy().into_owned();
+                        match Material::load_mtl(&path) {
+                            Ok(library) => materials.extend(library),
+                            Err(error) => warnings.push(
+                                format!("línea {}: se ignora mtllib: {}", line_number, error)),
+                        }
+                    }
+                }
+                Some("usemtl") => {
+                    let name = tokens.collect::<Vec<&str>>().join(" ");
+                    material = materials.iter().position(|m| m.get_name() == name);
+                    if material.is_none() {
+                        warnings.push(format!("línea {}: material desconocido {:?}, se usa el \
+                                               de por defecto", line_number, name));
+                    }
+                }
+                // Comentarios, grupos, objetos...
+                _ => {}
+            }
+        }
+
+        // Los índices positivos pueden apuntar a datos que aparecen después de la cara
+        for corner in triangles.iter().flat_map(|triangle| triangle.corners.iter()) {
+            OBJFileLoader::check_index(corner.line, "vértice", corner.position, positions.len())?;
+            if let Some(texture) = corner.texture {
+                OBJFileLoader::check_index(corner.line, "textura", texture, textures.len())?;
+            }
+            if let Some(normal) = corner.normal {
+                OBJFileLoader::check_index(corner.line, "normal", normal, normals.len())?;
+            }
+        }
+
+        // Las caras sin textura usan una por defecto añadida al final. Las que no tienen normal
+        // (o todas, si se fuerza) reciben normales generadas
+        let default_texture = textures.len();
+        textures.push(vec2(0.0, 0.0));
+        OBJFileLoader::generate_normals(&positions, &mut triangles, &mut normals, options);
+
+        Ok(ParsedObj {
+            positions, textures, normals, materials, triangles, default_texture, warnings,
+        })
+    }
+
+    // Crea los vértices de los triángulos. Solo entran las posiciones que usan, renumeradas
+    fn build_model_data(obj: &ParsedObj, triangles: &Vec<&Triangle>,
+                        options: &ObjLoadOptions) -> ModelData {
+        let mut local_positions: Vec<Option<usize>> = vec![None; obj.positions.len()];
+        let mut vertices: Vec<Vertex> = vec![];
+        let mut indices: Vec<u32> = vec![];
+        for corner in triangles.iter().flat_map(|triangle| triangle.corners.iter()) {
+            let index = match local_positions[corner.position] {
+                Some(index) => index,
+                None => {
+                    let index = vertices.len();
+                    vertices.push(Vertex::new(index, obj.positions[corner.position]));
+                    local_positions[corner.position] = Some(index);
+                    index
+                }
+            };
+            OBJFileLoader::process_vertex(index,
+                                          corner.texture.unwrap_or(obj.default_texture) as i32,
+                                          corner.normal.unwrap() as i32,
+                                          &mut vertices, &mut indices);
+        }
+        OBJFileLoader::remove_unused_vertices(&mut vertices);
+
+        let mut vertices_array: Vec<f32> = vec![0.0; vertices.len() * 3];
+        let mut textures_array: Vec<f32> = vec![0.0; vertices.len() * 2];
+        let mut normals_array: Vec<f32> = vec![0.0; vertices.len() * 3];
+        let furthest = OBJFileLoader::convert_data_to_arrays(
+            &vertices, &obj.textures, &obj.normals,
+            &mut vertices_array, &mut textures_array, &mut normals_array);
+        let mut data =
+            ModelData::new(vertices_array, textures_array, normals_array, indices, furthest);
+        if options.is_generate_tangents() {
+            data = tangent_generator::generate_tangents(&data);
+        }
+        if options.is_optimize() {
+            let (optimized, report) =
+                mesh_optimizer::optimize(&data, options.is_optimize_overdraw());
+            data = optimized;
+            data.set_optimization_report(report);
+        }
+        data
+    }
+
+    // Añade a normals las normales generadas y se las asigna a las esquinas sin normal.
+    // Suave: suma de las normales (sin normalizar, así pesan por área) de las caras que
+    // comparten la posición, el smoothing group y no forman un ángulo mayor que el de pliegue.
+    // Las caras con el suavizado desactivado ("s off") siempre son planas.
+    // Las esquinas que acaban con la misma normal en la misma posición comparten índice
+    fn generate_normals(positions: &[V3CG], triangles: &mut [Triangle],
+                        normals: &mut Vec<V3CG>, options: &ObjLoadOptions) {
+        let force = options.is_force_normals();
+        if !force && triangles.iter()
+            .all(|triangle| triangle.corners.iter().all(|corner| corner.normal.is_some())) {
+            return;
+        }
+
+        let face_normals: Vec<V3CG> = triangles.iter().map(|triangle| {
+            let p0 = positions[triangle.corners[0].position];
+            let p1 = positions[triangle.corners[1].position];
+            let p2 = positions[triangle.corners[2].position];
+            (p1 - p0).cross(p2 - p0)
+        }).collect();
+        let unit = |normal: V3CG| -> V3CG {
+            if normal.magnitude2() > 0.0 { normal.normalize() } else { vec3(0.0, 1.0, 0.0) }
+        };
+
+        // Triángulos que usan cada posición
+        let mut position_triangles: Vec<Vec<usize>> = vec![vec![]; positions.len()];
+        for (t, triangle) in triangles.iter().enumerate() {
+            for corner in triangle.corners.iter() {
+                position_triangles[corner.position].push(t);
+            }
+        }
+
+        let min_cos = options.get_crease_angle().to_radians().cos();
+        let mut generated: HashMap<(usize, [u32; 3]), usize> = HashMap::new();
+        for t in 0..triangles.len() {
+            let group = triangles[t].smoothing_group;
+            let face_unit = unit(face_normals[t]);
+            for c in 0..3 {
+                let corner = triangles[t].corners[c];
+                if corner.normal.is_some() && !force {
+                    continue;
+                }
+                let normal = if options.get_normal_mode() == NormalMode::Flat || group.is_none() {
+                    face_unit
+                } else {
+                    let mut sum = vec3(0.0, 0.0, 0.0);
+                    for &other in position_triangles[corner.position].iter() {
+                        if triangles[other].smoothing_group == group &&
+                            unit(face_normals[other]).dot(face_unit) >= min_cos {
+                            sum += face_normals[other];
+                        }
+                    }
+                    unit(sum)
+                };
+
+                let bits = [normal.x.to_bits(), normal.y.to_bits(), normal.z.to_bits()];
+                let key = (corner.position, bits);
+                let index = *generated.entry(key).or_insert_with(|| {
+                    normals.push(normal);
+                    normals.len() - 1
+                });
+                triangles[t].corners[c].normal = Some(index);
+            }
+        }
+    }
+
+    // Entre min y max floats, el resto de la línea se ignora
+    fn parse_floats<'a, I>(tokens: I, min: usize, max: usize, line: usize)
+                           -> Result<Vec<f32>, ObjError> where I: Iterator<Item=&'a str> {
+        let values = tokens.take(max)
+            .map(|token| token.parse::<f32>().map_err(|_| ObjError::Parse {
+                line,
+                message: format!("número no válido {:?}", token),
+            }))
+            .collect::<Result<Vec<f32>, ObjError>>()?;
+        if values.len() < min {
+            return Err(ObjError::Parse {
+                line,
+                message: format!("se esperaban {} números y hay {}", min, values.len()),
+            });
+        }
+        Ok(values)
+    }
+
+    // "v", "v/vt", "v//vn" o "v/vt/vn". counts: posiciones, texturas y normales leídas hasta
+    // esta línea, para los índices negativos (relativos al final)
+    fn parse_face_vertex(token: &str, counts: (usize, usize, usize), line: usize)
+                         -> Result<FaceVertex, ObjError> {
+        let parts: Vec<&str> = token.split('/').collect();
+        if parts.len() > 3 || parts[0].is_empty() {
+            let message = format!("vértice de cara no válido {:?}", token);
+            return Err(ObjError::Parse { line, message });
+        }
+        let position = OBJFileLoader::resolve_index(parts[0], counts.0, "vértice", line)?;
+        let texture = match parts.get(1) {
+            Some(part) if !part.is_empty() =>
+                Some(OBJFileLoader::resolve_index(part, counts.1, "textura", line)?),
+            _ => None,
+        };
+        let normal = match parts.get(2) {
+            Some(part) if !part.is_empty() =>
+                Some(OBJFileLoader::resolve_index(part, counts.2, "normal", line)?),
+            _ => None,
+        };
+        Ok(FaceVertex { position, texture, normal, line })
+    }
+
+    // Índice del OBJ (desde 1, o negativo desde el final) a índice desde 0
+    fn resolve_index(token: &str, count: usize, kind: &'static str, line: usize)
+                     -> Result<usize, ObjError> {
+        let index = token.parse::<i64>().map_err(|_| ObjError::Parse {
+            line,
+            message: format!("índice de {} no válido {:?}", kind, token),
+        })?;
+        let resolved = if index > 0 { index - 1 } else { count as i64 + index };
+        if index == 0 || resolved < 0 {
+            return Err(ObjError::IndexOutOfRange { line, kind, index, count });
+        }
+        Ok(resolved as usize)
+    }
+
+    fn check_index(line: usize, kind: &'static str, index: usize, count: usize)
+                   -> Result<(), ObjError> {
+        if index >= count {
+            return Err(ObjError::IndexOutOfRange { line, kind, index: index as i64 + 1, count });
+        }
+        Ok(())
+    }
+
+    fn process_vertex(index: usize, texture_index: i32, normal_index: i32,
+                      vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>) {
+        let current_vertex = &mut vertices[index];
+        if !current_vertex.is_set() {
+            current_vertex.set_texture_index(texture_index);
+            current_vertex.set_normal_index(normal_index);
+            indices.push(index as u32);
+        } else {
+            OBJFileLoader::deal_with_already_processed_vertex(index, texture_index, normal_index,
+                                                             indices, vertices);
+        }
+    }
+
+    // Recorre la cadena de duplicados de la posición buscando uno con la misma textura y
+    // normal. Si no hay ninguno se añade otro duplicado al final de la cadena
+    fn deal_with_already_processed_vertex(previous_vertex: usize, new_texture_index: i32,
+                                          new_normal_index: i32, indices: &mut Vec<u32>,
+                                          vertices: &mut Vec<Vertex>) {
+        let previous = &vertices[previous_vertex];
+        if previous.has_same_texture_and_normal(new_texture_index, new_normal_index) {
+            indices.push(previous.get_index() as u32);
+        } else {
+            match vertices[previous_vertex].get_duplicate_vertex() {
+                Some(another_vertex) => {
+                    OBJFileLoader::deal_with_already_processed_vertex(
+                        another_vertex, new_texture_index, new_normal_index, indices, vertices);
+                }
+                None => {
+                    let mut duplicate_vertex =
+                        Vertex::new(vertices.len(), vertices[previous_vertex].get_position());
+                    duplicate_vertex.set_texture_index(new_texture_index);
+                    duplicate_vertex.set_normal_index(new_normal_index);
+                    let duplicate_index = duplicate_vertex.get_index();
+                    vertices[previous_vertex].set_duplicate_vertex(duplicate_index);
+                    vertices.push(duplicate_vertex);
+                    indices.push(duplicate_index as u32);
+                }
+            }
+        }
+    }
+
+    // Posiciones que no usa ninguna cara: se les da textura y normal 0 para poder convertirlas
+    fn remove_unused_vertices(vertices: &mut [Vertex]) {
+        for vertex in vertices.iter_mut() {
+            if !vertex.is_set() {
+                vertex.set_texture_index(0);
+                vertex.set_normal_index(0);
+            }
+        }
+    }
+
+    // Rellena los arrays para load_to_vao. Devuelve la distancia al origen del vértice más lejano
+    fn convert_data_to_arrays(vertices: &[Vertex], textures: &[V2CG], normals: &[V3CG],
+                              vertices_array: &mut [f32], textures_array: &mut [f32],
+                              normals_array: &mut [f32]) -> f32 {
+        let mut furthest_point: f32 = 0.0;
+        for (i, current_vertex) in vertices.iter().enumerate() {
+            if current_vertex.get_length() > furthest_point {
+                furthest_point = current_vertex.get_length();
+            }
+            let position = current_vertex.get_position();
+            let texture_coord = textures[current_vertex.get_texture_index() as usize];
+            let normal_vector = normals[current_vertex.get_normal_index() as usize];
+            vertices_array[i * 3] = position.x;
+            vertices_array[i * 3 + 1] = position.y;
+            vertices_array[i * 3 + 2] = position.z;
+            textures_array[i * 2] = texture_coord.x;
+            textures_array[i * 2 + 1] = 1.0 - texture_coord.y;
+            normals_array[i * 3] = normal_vector.x;
+            normals_array[i * 3 + 1] = normal_vector.y;
+            normals_array[i * 3 + 2] = normal_vector.z;
+        }
+        furthest_point
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    // Escribe el OBJ en el directorio temporal; name tiene que ser distinto en cada test
+    fn write_obj(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("obj_file_loader_{}.obj", name));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    // Sin optimizar, así los vértices quedan en el orden en que aparecen en las caras
+    fn load(name: &str, contents: &str) -> Result<ModelData, ObjError> {
+        let mut options = ObjLoadOptions::new();
+        options._set_optimize(false);
+        OBJFileLoader::load_obj_with_options(&write_obj(name, contents), &options)
+    }
+
+    const QUAD: &str = "v 0 0 0\nv 1 0 0\nv 1 0 -1\nv 0 0 -1\nvn 0 1 0\n";
 
-*/
\ No newline at end of file
+    #[test]
+    fn shared_corners_reuse_vertices() {
+        let obj = format!("{}vt 0 0\nf 1/1/1 2/1/1 3/1/1\nf 1/1/1 3/1/1 4/1/1\n", QUAD);
+        let data = load("shared_corners", &obj).unwrap();
+        assert_eq!(data.get_vertices().len(), 4 * 3);
+        assert_eq!(data.get_indices(), &vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn uv_seams_duplicate_vertices() {
+        // Las esquinas 1 y 3 tienen una uv distinta en cada triángulo
+        let obj = format!("{}vt 0 0\nvt 1 0\nvt 1 1\nvt 0.5 0.5\n\
+                           f 1/1/1 2/2/1 3/3/1\nf 1/4/1 3/4/1 4/4/1\n", QUAD);
+        let data = load("uv_seams", &obj).unwrap();
+        assert_eq!(data.get_vertices().len(), 6 * 3);
+        // Los duplicados se añaden al final, en el orden de las caras
+        assert_eq!(data.get_indices(), &vec![0, 1, 2, 3, 4, 5]);
+        // y conservan la posición con su propia uv (con la v invertida)
+        assert_eq!(&data.get_vertices()[9..12], &[0.0, 0.0, 0.0]);
+        assert_eq!(&data.get_texture_coords()[0..2], &[0.0, 1.0]);
+        assert_eq!(&data.get_texture_coords()[6..8], &[0.5, 0.5]);
+    }
+
+    #[test]
+    fn faces_without_texture_use_the_default_one() {
+        let data = load("no_texture", &format!("{}f 1//1 2//1 3//1\n", QUAD)).unwrap();
+        assert_eq!(data.get_vertices().len(), 3 * 3);
+        assert_eq!(data.get_texture_coords(), &vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(&data.get_normals()[0..3], &[0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn missing_materials_are_warnings() {
+        let mtl = env::temp_dir().join("obj_file_loader_materials.mtl");
+        fs::write(&mtl, "newmtl rojo\nKd 1 0 0\n").unwrap();
+        let obj = format!("{}mtllib no_existe.mtl obj_file_loader_materials.mtl\n\
+                           usemtl rojo\nf 1 2 3\nusemtl azul\nf 1 3 4\n", QUAD);
+        let path = write_obj("materials", &obj);
+        let (parts, warnings) =
+            OBJFileLoader::load_obj_parts(&path, &ObjLoadOptions::new()).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("línea 6"));
+        assert!(warnings[1].starts_with("línea 9"));
+        // El usemtl desconocido se queda sin material
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].get_material().map(|material| material.get_name()), Some("rojo"));
+        assert!(parts[1].get_material().is_none());
+        // Sin materiales la malla se carga igual
+        assert_eq!(OBJFileLoader::load_obj(&path).unwrap().get_indices().len(), 6);
+    }
+
+    #[test]
+    fn polygons_are_triangulated_as_fans() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 2 0 -1\nv 1 0 -2\nv 0 0 -1\nf 1 2 3 4 5\n";
+        let data = load("fan", obj).unwrap();
+        assert_eq!(data.get_indices(), &vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+    }
+
+    #[test]
+    fn negative_and_forward_indices_are_resolved() {
+        // Los negativos cuentan desde lo leído hasta la cara; los positivos pueden ir por delante
+        let relative = load("relative", &format!("{}f -4//-1 -3//-1 -2//-1\n", QUAD)).unwrap();
+        let forward = load("forward", &format!("f 1//1 2//1 3//1\n{}", QUAD)).unwrap();
+        assert_eq!(relative.get_vertices(), &vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, -1.0]);
+        assert_eq!(forward.get_vertices(), relative.get_vertices());
+    }
+
+    #[test]
+    fn errors_report_their_line() {
+        match load("bad_number", "v 0 0 0\nv 1 x 0\n") {
+            Err(ObjError::Parse { line: 2, .. }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match load("short_vertex", "v 0 0\n") {
+            Err(ObjError::Parse { line: 1, .. }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match load("two_corners", &format!("{}f 1 2\n", QUAD)) {
+            Err(ObjError::Parse { line: 6, .. }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match load("bad_corner", &format!("{}f 1/2/3/4 2 3\n", QUAD)) {
+            Err(ObjError::Parse { line: 6, .. }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match load("out_of_range", &format!("{}\nf 1 2 9\n", QUAD)) {
+            Err(ObjError::IndexOutOfRange { line: 7, kind: "vértice", index: 9, count: 4 }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match load("zero_index", &format!("{}f 0 1 2\n", QUAD)) {
+            Err(ObjError::IndexOutOfRange { line: 6, index: 0, .. }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        match load("missing_normal", &format!("{}f 1//2 2//1 3//1\n", QUAD)) {
+            Err(ObjError::IndexOutOfRange { line: 6, kind: "normal", .. }) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        assert!(OBJFileLoader::load_obj("/no/existe.obj").is_err());
+    }
+
+    // Dos triángulos sin vn que comparten la arista 1-2, el segundo inclinado unos 17º
+    const HINGE: &str = "v 0 0 0\nv 1 0 0\nv 0 0 -1\nv 0 -0.3 1\n";
+    const HINGE_FACES: &str = "f 1 2 3\nf 1 4 2\n";
+
+    fn vertex_count(data: &ModelData) -> usize {
+        data.get_vertices().len() / 3
+    }
+
+    #[test]
+    fn smooth_normals_without_smoothing_groups() {
+        // Sin línea s todas las caras están en el mismo grupo: la arista comparte vértices
+        let data = load("smooth_default", &format!("{}{}", HINGE, HINGE_FACES)).unwrap();
+        assert_eq!(vertex_count(&data), 4);
+        let expected = vec3(0.0, 2.0, 0.3).normalize();
+        let normal = &data.get_normals()[0..3];
+        assert!((vec3(normal[0], normal[1], normal[2]) - expected).magnitude() < 1e-6);
+        // Los vértices que no comparten cara se quedan con la normal de la suya
+        assert_eq!(&data.get_normals()[6..9], &[0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn flat_normals_split_every_corner() {
+        let mut options = ObjLoadOptions::new();
+        options._set_optimize(false);
+        options._set_normal_mode(NormalMode::Flat);
+        let path = write_obj("flat", &format!("{}{}", HINGE, HINGE_FACES));
+        let data = OBJFileLoader::load_obj_with_options(&path, &options).unwrap();
+        assert_eq!(vertex_count(&data), 6);
+        assert_eq!(&data.get_normals()[0..3], &[0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn smoothing_groups_and_crease_angle_split_normals() {
+        let off = load("s_off", &format!("{}s off\n{}", HINGE, HINGE_FACES)).unwrap();
+        assert_eq!(vertex_count(&off), 6);
+        let groups = load("s_groups", &format!("{}s 1\nf 1 2 3\ns 2\nf 1 4 2\n", HINGE)).unwrap();
+        assert_eq!(vertex_count(&groups), 6);
+        let same = load("s_same", &format!("{}s 1\nf 1 2 3\ns 1\nf 1 4 2\n", HINGE)).unwrap();
+        assert_eq!(vertex_count(&same), 4);
+        // A 90º (más que el pliegue de 60º) no se suaviza aunque estén en el mismo grupo
+        let crease = "v 0 0 0\nv 1 0 0\nv 0 0 -1\nv 0 -1 0\n";
+        let folded = load("crease", &format!("{}s 1\n{}", crease, HINGE_FACES)).unwrap();
+        assert_eq!(vertex_count(&folded), 6);
+    }
+
+    #[test]
+    fn malformed_smoothing_groups_are_ignored() {
+        // El valor que no se entiende deja el grupo anterior
+        let off = load("s_bad_off", &format!("{}s off\ns suave\n{}", HINGE, HINGE_FACES)).unwrap();
+        assert_eq!(vertex_count(&off), 6);
+        let on = load("s_bad_on", &format!("{}s 3\ns -1\n{}", HINGE, HINGE_FACES)).unwrap();
+        assert_eq!(vertex_count(&on), 4);
+    }
+
+    #[test]
+    fn given_normals_are_kept_unless_forced() {
+        let obj = format!("{}vn 0 0 1\nf 1//1 2//1 3//1\n", HINGE);
+        let kept = load("given_normals", &obj).unwrap();
+        assert_eq!(&kept.get_normals()[0..3], &[0.0, 0.0, 1.0]);
+        let mut options = ObjLoadOptions::new();
+        options._set_optimize(false);
+        options._set_force_normals(true);
+        let path = write_obj("forced_normals", &obj);
+        let forced = OBJFileLoader::load_obj_with_options(&path, &options).unwrap();
+        assert_eq!(&forced.get_normals()[0..3], &[0.0, 1.0, 0.0]);
+    }
+}
@@ -0,0 +1,84 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalMode {
+    // Media de las caras vecinas ponderada por área, por smoothing group y ángulo de pliegue
+    Smooth,
+    // La normal de la cara en sus tres vértices
+    Flat,
+}
+
+// Opciones de OBJFileLoader::load_obj_with_options
+#[derive(Debug, Clone, Copy)]
+pub struct ObjLoadOptions {
+    normal_mode: NormalMode,
+    crease_angle: f32,     // grados, entre caras más plegadas que esto no se suaviza
+    force_normals: bool,   // generar normales aunque el OBJ traiga vn
+    generate_tangents: bool,
+    optimize: bool,          // reordenar triángulos y vértices para la caché de vértices
+    optimize_overdraw: bool, // además ordenar los triángulos para reducir el overdraw
+}
+
+impl ObjLoadOptions {
+    // Las normales solo se generan (suaves, pliegue de 60º) en las caras que no tienen vn
+    pub fn new() -> ObjLoadOptions {
+        ObjLoadOptions {
+            normal_mode: NormalMode::Smooth,
+            crease_angle: 60.0,
+            force_normals: false,
+            generate_tangents: false,
+            optimize: true,
+            optimize_overdraw: false,
+        }
+    }
+
+    pub fn get_normal_mode(&self) -> NormalMode {
+        self.normal_mode
+    }
+
+    pub fn _set_normal_mode(&mut self, normal_mode: NormalMode) {
+        self.normal_mode = normal_mode;
+    }
+
+    pub fn get_crease_angle(&self) -> f32 {
+        self.crease_angle
+    }
+
+    pub fn _set_crease_angle(&mut self, crease_angle: f32) {
+        self.crease_angle = crease_angle;
+    }
+
+    pub fn is_force_normals(&self) -> bool {
+        self.force_normals
+    }
+
+    pub fn _set_force_normals(&mut self, force_normals: bool) {
+        self.force_normals = force_normals;
+    }
+
+    // Tangentes MikkTSpace para normal mapping, en el atributo TANGENT_ATTRIBUTE
+    pub fn is_generate_tangents(&self) -> bool {
+        self.generate_tangents
+    }
+
+    pub fn _set_generate_tangents(&mut self, generate_tangents: bool) {
+        self.generate_tangents = generate_tangents;
+    }
+
+    // Ver toolbox::mesh_optimizer. El ACMR antes y después queda en
+    // ModelData::get_optimization_report
+    pub fn is_optimize(&self) -> bool {
+        self.optimize
+    }
+
+    pub fn _set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    // Solo tiene efecto con optimize
+    pub fn is_optimize_overdraw(&self) -> bool {
+        self.optimize_overdraw
+    }
+
+    pub fn _set_optimize_overdraw(&mut self, optimize_overdraw: bool) {
+        self.optimize_overdraw = optimize_overdraw;
+    }
+}
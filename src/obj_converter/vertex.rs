@@ -1,69 +1,74 @@
-/*
+use cgmath::InnerSpace;
 
-package objConverter;
+type V3CG = cgmath::Vector3<f32>;
 
-import org.lwjgl.util.vector.Vector3f;
+const NO_INDEX: i32 = -1;
 
-public class Vertex {
-
-	private static final int NO_INDEX = -1;
-
-	private Vector3f position;
-	private int textureIndex = NO_INDEX;
-	private int normalIndex = NO_INDEX;
-	private Vertex duplicateVertex = null;
-	private int index;
-	private float length;
-
-	public Vertex(int index,Vector3f position){
-		this.index = index;
-		this.position = position;
-		this.length = position.length();
-	}
-
-	public int getIndex(){
-		return index;
-	}
-
-	public float getLength(){
-		return length;
-	}
-
-	public boolean isSet(){
-		return textureIndex!=NO_INDEX && normalIndex!=NO_INDEX;
-	}
-
-	public boolean hasSameTextureAndNormal(int textureIndexOther,int normalIndexOther){
-		return textureIndexOther==textureIndex && normalIndexOther==normalIndex;
-	}
-
-	public void setTextureIndex(int textureIndex){
-		this.textureIndex = textureIndex;
-	}
-
-	public void setNormalIndex(int normalIndex){
-		this.normalIndex = normalIndex;
-	}
-
-	public Vector3f getPosition() {
-		return position;
-	}
-
-	public int getTextureIndex() {
-		return textureIndex;
-	}
-
-	public int getNormalIndex() {
-		return normalIndex;
-	}
-
-	public Vertex getDuplicateVertex() {
-		return duplicateVertex;
-	}
-
-	public void setDuplicateVertex(Vertex duplicateVertex) {
-		this.duplicateVertex = duplicateVertex;
-	}
+// Vértice del OBJ con sus índices de textura y normal. Si una posición aparece con otra textura
+// u otra normal se crea un duplicado y se encadena con duplicate_vertex
+pub struct Vertex {
+    position: V3CG,
+    texture_index: i32,
+    normal_index: i32,
+    duplicate_vertex: Option<usize>, // índice del duplicado en la lista de vértices
+    index: usize,
+    length: f32,
+}
 
+impl Vertex {
+    pub fn new(index: usize, position: V3CG) -> Vertex {
+        Vertex {
+            position,
+            texture_index: NO_INDEX,
+            normal_index: NO_INDEX,
+            duplicate_vertex: None,
+            index,
+            length: position.magnitude(),
+        }
+    }
+
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_length(&self) -> f32 {
+        self.length
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.texture_index != NO_INDEX && self.normal_index != NO_INDEX
+    }
+
+    pub fn has_same_texture_and_normal(&self, texture_index_other: i32, normal_index_other: i32)
+                                       -> bool {
+        texture_index_other == self.texture_index && normal_index_other == self.normal_index
+    }
+
+    pub fn set_texture_index(&mut self, texture_index: i32) {
+        self.texture_index = texture_index;
+    }
+
+    pub fn set_normal_index(&mut self, normal_index: i32) {
+        self.normal_index = normal_index;
+    }
+
+    pub fn get_position(&self) -> V3CG {
+        self.position
+    }
+
+    pub fn get_texture_index(&self) -> i32 {
+        self.texture_index
+    }
+
+    pub fn get_normal_index(&self) -> i32 {
+        self.normal_index
+    }
+
+    pub fn get_duplicate_vertex(&self) -> Option<usize> {
+        self.duplicate_vertex
+    }
+
+    pub fn set_duplicate_vertex(&mut self, duplicate_vertex: usize) {
+        self.duplicate_vertex = Some(duplicate_vertex);
+    }
 }
-*/
\ No newline at end of file
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::models::lod_chain::{LodChain, LodMetric};
+use crate::models::raw_model::RawModel;
+use crate::models::textured_model::TexturedModel;
+use crate::obj_converter::material::Material;
+use crate::render_engine::gl_resources::{Texture, Vao};
+use crate::render_engine::loader::Loader;
+use crate::render_engine::objloader::OBJLoader;
+use crate::textures::texture_options::TextureOptions;
+use crate::textures::model_texture::ModelTexture;
+use crate::toolbox::mesh_simplifier;
+
+// Malla subida a la GPU, se borra cuando se suelta el último Rc<Mesh>
+pub struct Mesh {
+    _vao: Vao,
+    raw_model: RawModel,
+    _lod_vaos: Vec<Vao>,
+    lod_chain: Option<LodChain>,
+}
+
+impl Mesh {
+    pub fn new(vao: Vao, raw_model: RawModel) -> Mesh {
+        Mesh {
+            _vao: vao,
+            raw_model,
+            _lod_vaos: vec![],
+            lod_chain: None,
+        }
+    }
+
+    // lod_vaos: los VAOs de los niveles 1.. de la cadena
+    pub fn with_lods(vao: Vao, lod_vaos: Vec<Vao>, lod_chain: LodChain) -> Mesh {
+        Mesh {
+            _vao: vao,
+            raw_model: lod_chain.get_raw_model(0),
+            _lod_vaos: lod_vaos,
+            lod_chain: Some(lod_chain),
+        }
+    }
+
+    pub fn get_raw_model(&self) -> RawModel {
+        self.raw_model
+    }
+
+    pub fn _get_lod_chain(&self) -> Option<LodChain> {
+        self.lod_chain
+    }
+
+    // TexturedModel con la cadena de niveles de detalle, si la malla la tiene
+    pub fn create_textured_model(&self, texture: ModelTexture) -> TexturedModel {
+        let mut model = TexturedModel::new(self.raw_model, texture);
+        if let Some(lod_chain) = self.lod_chain {
+            model.set_lod_chain(lod_chain);
+        }
+        model
+    }
+}
+
+// Parte de un modelo con un solo material. Guarda la malla y la textura para que sigan vivas
+// mientras se use el TexturedModel
+pub struct ModelPart {
+    _mesh: Mesh,
+    _texture: Rc<Texture>,
+    textured_model: TexturedModel,
+}
+
+impl ModelPart {
+    pub fn get_textured_model(&self) -> TexturedModel {
+        self.textured_model
+    }
+}
+
+// OBJ con sus materiales MTL, una parte por material
+pub struct Model {
+    parts: Vec<ModelPart>,
+}
+
+impl Model {
+    pub fn get_textured_models(&self) -> Vec<TexturedModel> {
+        self.parts.iter().map(|part| part.get_textured_model()).collect()
+    }
+}
+
+// Caché de recursos por ruta sobre Loader y OBJLoader. Devuelve handles compartidos (Rc) y
+// solo guarda referencias débiles, así el recurso se libera cuando lo suelta su último usuario
+pub struct AssetManager {
+    textures: HashMap<String, Weak<Texture>>,
+    cube_maps: HashMap<String, Weak<Texture>>,
+    meshes: HashMap<String, Weak<Mesh>>,
+    models: HashMap<String, Weak<Model>>,
+}
+
+impl AssetManager {
+    pub fn new() -> AssetManager {
+        AssetManager {
+            textures: HashMap::new(),
+            cube_maps: HashMap::new(),
+            meshes: HashMap::new(),
+            models: HashMap::new(),
+        }
+    }
+
+    pub fn load_texture(&mut self, loader: &mut Loader, path: &str) -> Result<Rc<Texture>, String> {
+        self.load_texture_with_options(loader, path, &TextureOptions::new())
+    }
+
+    // La clave sigue siendo la ruta: si la textura ya está cargada se devuelve con las opciones
+    // de la primera carga
+    pub fn load_texture_with_options(&mut self, loader: &mut Loader, path: &str,
+                                     options: &TextureOptions) -> Result<Rc<Texture>, String> {
+        if let Some(texture) = self.textures.get(path).and_then(|weak| weak.upgrade()) {
+            return Ok(texture);
+        }
+        let texture = Rc::new(loader.load_texture_handle_with_options(path, options)?);
+        self.textures.insert(path.to_string(), Rc::downgrade(&texture));
+        Ok(texture)
+    }
+
+    // La clave de un cubemap son las seis rutas en orden. El skybox todavía carga los suyos
+    // directamente con el Loader
+    pub fn _load_cube_map(&mut self, loader: &mut Loader, texture_files: Vec<&str>) -> Rc<Texture> {
+        let key = texture_files.join("|");
+        if let Some(texture) = self.cube_maps.get(&key).and_then(|weak| weak.upgrade()) {
+            return texture;
+        }
+        let texture = Rc::new(loader.load_cube_map_handle(texture_files));
+        self.cube_maps.insert(key, Rc::downgrade(&texture));
+        texture
+    }
+
+    pub fn load_mesh(&mut self, loader: &mut Loader, path: &str) -> Result<Rc<Mesh>, String> {
+        if let Some(mesh) = self.meshes.get(path).and_then(|weak| weak.upgrade()) {
+            return Ok(mesh);
+        }
+        let (vao, raw_model) = OBJLoader::new().load_cached_handle(path, loader)?;
+        let mesh = Rc::new(Mesh::new(vao, raw_model));
+        self.meshes.insert(path.to_string(), Rc::downgrade(&mesh));
+        Ok(mesh)
+    }
+
+    // Como load_mesh, más un nivel de detalle simplificado por cada (ratio de triángulos respecto
+    // al original, umbral de la métrica). La clave de la caché incluye los niveles
+    pub fn load_lod_mesh(&mut self, loader: &mut Loader, path: &str, levels: &[(f32, f32)],
+                         metric: LodMetric) -> Result<Rc<Mesh>, String> {
+        let key = format!("{}|{:?}|{:?}", path, metric, levels);
+        if let Some(mesh) = self.meshes.get(&key).and_then(|weak| weak.upgrade()) {
+            return Ok(mesh);
+        }
+        let data = OBJLoader::new().load_cached_data(path)?;
+        let (vao, raw_model) = loader.load_model_data_handle(&data);
+        let mut lod_chain = LodChain::new(raw_model, metric);
+        let ratios: Vec<f32> = levels.iter().map(|&(ratio, _)| ratio).collect();
+        let mut lod_vaos = vec![];
+        for (lod, &(_, threshold)) in
+            mesh_simplifier::build_lod_chain(&data, &ratios).iter().zip(levels) {
+            let (lod_vao, lod_model) = loader.load_model_data_handle(lod);
+            lod_vaos.push(lod_vao);
+            lod_chain.add_level(lod_model, threshold)
+                .map_err(|e| format!("Could not build LODs for {}: {}", path, e))?;
+        }
+        let mesh = Rc::new(Mesh::with_lods(vao, lod_vaos, lod_chain));
+        self.meshes.insert(key, Rc::downgrade(&mesh));
+        Ok(mesh)
+    }
+
+    // Carga el OBJ con sus materiales. Cada material usa su map_Kd teñido con su color difuso o,
+    // si no tiene, una textura de ese color. Las caras sin usemtl quedan con un material blanco.
+    // Los avisos del OBJ (mtllib que falta, usemtl desconocido) se imprimen por stderr
+    pub fn load_model(&mut self, loader: &mut Loader, path: &str) -> Result<Rc<Model>, String> {
+        if let Some(model) = self.models.get(path).and_then(|weak| weak.upgrade()) {
+            return Ok(model);
+        }
+        let (obj_parts, warnings) = OBJLoader::new().load_obj_parts(path)
+            .map_err(|e| format!("Could not load model {}: {}", path, e))?;
+        for warning in warnings.iter() {
+            eprintln!("{}: {}", path, warning);
+        }
+        let default_material = Material::new("default");
+        let mut parts = vec![];
+        for obj_part in obj_parts.iter() {
+            let material = obj_part.get_material().unwrap_or(&default_material);
+            let data = obj_part.get_data();
+            let (vao, raw_model) = loader.load_model_data_handle(data);
+            let texture = match material.get_diffuse_map() {
+                Some(map) => self.load_texture(loader, map)?,
+                None => self.load_solid_colour(loader, material),
+            };
+            let mut model_texture = ModelTexture::new(texture.get_id());
+            // La textura de color sólido ya lleva el Kd, no se vuelve a teñir
+            if material.get_diffuse_map().is_some() {
+                model_texture.set_colour(material.get_diffuse_colour());
+            }
+            model_texture.set_shine_damper(material.get_specular_exponent());
+            let specular = material.get_specular_colour();
+            model_texture.set_reflectivity(specular.x.max(specular.y).max(specular.z));
+            model_texture.set_has_transparency(material.is_transparent());
+            parts.push(ModelPart {
+                _mesh: Mesh::new(vao, raw_model),
+                _texture: texture,
+                textured_model: TexturedModel::new(raw_model, model_texture),
+            });
+        }
+        let model = Rc::new(Model { parts });
+        self.models.insert(path.to_string(), Rc::downgrade(&model));
+        Ok(model)
+    }
+
+    // Se comparten entre materiales con el mismo color, la clave es "#rrggbbaa"
+    fn load_solid_colour(&mut self, loader: &mut Loader, material: &Material) -> Rc<Texture> {
+        let colour = material.get_diffuse_colour();
+        let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let rgba = [to_byte(colour.x), to_byte(colour.y), to_byte(colour.z),
+            to_byte(material.get_dissolve())];
+        let key = format!("#{:02x}{:02x}{:02x}{:02x}", rgba[0], rgba[1], rgba[2], rgba[3]);
+        if let Some(texture) = self.textures.get(&key).and_then(|weak| weak.upgrade()) {
+            return texture;
+        }
+        let texture = Rc::new(loader.load_solid_colour_texture_handle(rgba));
+        self.textures.insert(key, Rc::downgrade(&texture));
+        texture
+    }
+
+    // Quita del índice las rutas cuyos recursos ya se liberaron
+    pub fn purge(&mut self) {
+        self.textures.retain(|_, weak| weak.strong_count() > 0);
+        self.cube_maps.retain(|_, weak| weak.strong_count() > 0);
+        self.meshes.retain(|_, weak| weak.strong_count() > 0);
+        self.models.retain(|_, weak| weak.strong_count() > 0);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::obj_converter::model_data::ModelData;
+use crate::render_engine::asset_manager::Mesh;
+use crate::render_engine::gl_resources::Texture;
+use crate::render_engine::loader::Loader;
+use crate::render_engine::objloader::OBJLoader;
+use crate::terrains::heightmap::Heightmap;
+use crate::textures::compressed_texture::CompressedTexture;
+use crate::textures::texture_data::TextureData;
+use crate::textures::texture_options::TextureOptions;
+
+// Identifica una carga pedida al AsyncLoader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LoadTicket {
+    id: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadState {
+    Pending,
+    Ready,
+    Failed(String),
+}
+
+// Trabajo de los hilos: leer y decodificar el fichero
+enum Job {
+    Texture(String, TextureOptions),
+    Mesh(String),
+    Heightmap(String),
+}
+
+// Resultado en CPU que vuelve al hilo de OpenGL
+enum Decoded {
+    Texture(TextureData, TextureOptions),
+    Container(CompressedTexture, TextureOptions),
+    Mesh(ModelData),
+    Heightmap(Heightmap),
+}
+
+enum Loaded {
+    Texture(Texture),
+    Mesh(Box<Mesh>), // con la cadena de LODs ocupa mucho más que el resto
+    Heightmap(Heightmap),
+}
+
+enum Slot {
+    Pending,
+    Ready(Loaded),
+    Failed(String),
+}
+
+// Carga de recursos en segundo plano. Los hilos leen los ficheros, decodifican imágenes,
+// heightmaps y OBJ, y dejan el resultado en una cola. El hilo de OpenGL sube unos pocos por
+// frame con process_uploads() y recoge cada recurso con su ticket
+pub struct AsyncLoader {
+    job_sender: Option<Sender<(usize, Job)>>,
+    result_receiver: Receiver<(usize, Result<Decoded, String>)>,
+    workers: Vec<JoinHandle<()>>,
+    uploads: VecDeque<(usize, Decoded)>, // decodificados esperando subida a la GPU
+    slots: HashMap<usize, Slot>,
+    next_id: usize,
+}
+
+impl AsyncLoader {
+    pub fn new(worker_count: usize) -> AsyncLoader {
+        let (job_sender, job_receiver) = mpsc::channel::<(usize, Job)>();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..worker_count.max(1)).map(|_| {
+            let jobs = Arc::clone(&job_receiver);
+            let results = result_sender.clone();
+            thread::spawn(move || AsyncLoader::worker(jobs, results))
+        }).collect();
+
+        AsyncLoader {
+            job_sender: Some(job_sender),
+            result_receiver,
+            workers,
+            uploads: VecDeque::new(),
+            slots: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn request_texture(&mut self, path: &str, options: &TextureOptions) -> LoadTicket {
+        self.request(Job::Texture(path.to_string(), *options))
+    }
+
+    pub fn request_mesh(&mut self, path: &str) -> LoadTicket {
+        self.request(Job::Mesh(path.to_string()))
+    }
+
+    // El heightmap no necesita OpenGL, queda listo en cuanto el hilo lo decodifica
+    pub fn request_heightmap(&mut self, path: &str) -> LoadTicket {
+        self.request(Job::Heightmap(path.to_string()))
+    }
+
+    fn request(&mut self, job: Job) -> LoadTicket {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.slots.insert(id, Slot::Pending);
+        let sent = self.job_sender.as_ref()
+            .is_some_and(|sender| sender.send((id, job)).is_ok());
+        if !sent {
+            self.slots.insert(id, Slot::Failed("No quedan hilos de carga".to_string()));
+        }
+        LoadTicket { id }
+    }
+
+    // Llamar una vez por frame desde el hilo de OpenGL. Sube como mucho max_uploads recursos y
+    // devuelve cuántos ha subido
+    pub fn process_uploads(&mut self, loader: &mut Loader, max_uploads: usize) -> usize {
+        self.receive_results();
+        let mut count = 0;
+        while count < max_uploads {
+            match self.uploads.pop_front() {
+                Some((id, decoded)) => {
+                    self.upload(loader, id, decoded);
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        count
+    }
+
+    // Bloquea hasta que la carga del ticket termina, subiendo lo que haga falta por el camino
+    pub fn wait(&mut self, ticket: LoadTicket, loader: &mut Loader) -> LoadState {
+        loop {
+            self.receive_results();
+            if self.poll(ticket) != LoadState::Pending {
+                return self.poll(ticket);
+            }
+            if let Some((id, decoded)) = self.uploads.pop_front() {
+                self.upload(loader, id, decoded);
+                continue;
+            }
+            match self.result_receiver.recv() {
+                Ok((id, result)) => self.store_result(id, result),
+                Err(_) => {
+                    let error = "Los hilos de carga terminaron".to_string();
+                    self.slots.insert(ticket.id, Slot::Failed(error));
+                }
+            }
+        }
+    }
+
+    pub fn poll(&self, ticket: LoadTicket) -> LoadState {
+        match self.slots.get(&ticket.id) {
+            Some(Slot::Pending) => LoadState::Pending,
+            Some(Slot::Ready(_)) => LoadState::Ready,
+            Some(Slot::Failed(error)) => LoadState::Failed(error.clone()),
+            None => LoadState::Failed("Ticket desconocido o ya recogido".to_string()),
+        }
+    }
+
+    // Cargas sin terminar, para la pantalla de carga
+    pub fn get_pending_count(&self) -> usize {
+        self.slots.values().filter(|slot| matches!(slot, Slot::Pending)).count()
+    }
+
+    // Los take_* entregan el recurso una sola vez; después el ticket deja de existir
+    pub fn take_texture(&mut self, ticket: LoadTicket) -> Option<Texture> {
+        match self.slots.remove(&ticket.id) {
+            Some(Slot::Ready(Loaded::Texture(texture))) => Some(texture),
+            Some(slot) => {
+                self.slots.insert(ticket.id, slot);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn take_mesh(&mut self, ticket: LoadTicket) -> Option<Mesh> {
+        match self.slots.remove(&ticket.id) {
+            Some(Slot::Ready(Loaded::Mesh(mesh))) => Some(*mesh),
+            Some(slot) => {
+                self.slots.insert(ticket.id, slot);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn take_heightmap(&mut self, ticket: LoadTicket) -> Option<Heightmap> {
+        match self.slots.remove(&ticket.id) {
+            Some(Slot::Ready(Loaded::Heightmap(heightmap))) => Some(heightmap),
+            Some(slot) => {
+                self.slots.insert(ticket.id, slot);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn receive_results(&mut self) {
+        while let Ok((id, result)) = self.result_receiver.try_recv() {
+            self.store_result(id, result);
+        }
+    }
+
+    fn store_result(&mut self, id: usize, result: Result<Decoded, String>) {
+        match result {
+            Ok(Decoded::Heightmap(heightmap)) => {
+                self.slots.insert(id, Slot::Ready(Loaded::Heightmap(heightmap)));
+            }
+            Ok(decoded) => self.uploads.push_back((id, decoded)),
+            Err(error) => {
+                self.slots.insert(id, Slot::Failed(error));
+            }
+        }
+    }
+
+    fn upload(&mut self, loader: &mut Loader, id: usize, decoded: Decoded) {
+        let loaded = match decoded {
+            Decoded::Texture(data, options) =>
+                Ok(Loaded::Texture(loader.load_texture_from_data(&data, &options))),
+            Decoded::Container(container, options) =>
+                loader.load_texture_from_container(&container, &options).map(Loaded::Texture),
+            Decoded::Mesh(data) => {
+                let (vao, raw_model) = loader.load_model_data_handle(&data);
+                Ok(Loaded::Mesh(Box::new(Mesh::new(vao, raw_model))))
+            }
+            Decoded::Heightmap(heightmap) => Ok(Loaded::Heightmap(heightmap)),
+        };
+        let slot = match loaded {
+            Ok(loaded) => Slot::Ready(loaded),
+            Err(error) => Slot::Failed(error),
+        };
+        self.slots.insert(id, slot);
+    }
+
+    fn worker(jobs: Arc<Mutex<Receiver<(usize, Job)>>>,
+              results: Sender<(usize, Result<Decoded, String>)>) {
+        loop {
+            // El lock se suelta al recibir el trabajo, antes de decodificar
+            let job = jobs.lock().map_err(|_| ())
+                .and_then(|receiver| receiver.recv().map_err(|_| ()));
+            let (id, job) = match job {
+                Ok(job) => job,
+                Err(_) => break, // se cerró el AsyncLoader
+            };
+            // Los decoders pueden hacer panic con ficheros rotos, se convierte en error
+            let result = panic::catch_unwind(AssertUnwindSafe(|| AsyncLoader::decode(job)))
+                .unwrap_or_else(|_| Err("Panic al decodificar el recurso".to_string()));
+            if results.send((id, result)).is_err() {
+                break;
+            }
+        }
+    }
+
+    fn decode(job: Job) -> Result<Decoded, String> {
+        match job {
+            Job::Texture(path, options) => {
+                if CompressedTexture::is_container(&path) {
+                    CompressedTexture::load(&path)
+                        .map(|container| Decoded::Container(container, options))
+                } else {
+                    Loader::decode_texture(&path, &options)
+                        .map(|data| Decoded::Texture(data, options))
+                }
+            }
+            Job::Mesh(path) => OBJLoader::new().load_obj_data(&path)
+                .map(Decoded::Mesh)
+                .map_err(|e| format!("Could not load model {}: {}", path, e)),
+            Job::Heightmap(path) => Heightmap::load(&path).map(Decoded::Heightmap),
+        }
+    }
+}
+
+impl Drop for AsyncLoader {
+    // Cierra la cola de trabajos y espera a que los hilos terminen el que tengan entre manos
+    fn drop(&mut self) {
+        self.job_sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
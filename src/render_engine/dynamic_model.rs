@@ -0,0 +1,320 @@
+use gl;
+use gl::types::*;
+
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use crate::models::bounds::{BoundingBox, BoundingSphere};
+use crate::models::raw_model::RawModel;
+use crate::render_engine::gl_resources::{Vao, Vbo};
+use crate::render_engine::loader::Loader;
+use crate::render_engine::vertex_layout::{as_bytes, VertexLayout};
+
+// Regiones del VBO con mapeo persistente: la CPU escribe en una mientras la GPU lee las otras
+const PERSISTENT_REGIONS: usize = 3;
+// Espera máxima a que la GPU suelte una región, en nanosegundos
+const FENCE_TIMEOUT: GLuint64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BufferUsage {
+    Static,  // casi nunca cambia
+    Dynamic, // cambia a menudo y se dibuja varias veces (terreno editable)
+    Stream,  // se rellena cada frame (partículas, líneas de depuración)
+}
+
+impl BufferUsage {
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            BufferUsage::Static => gl::STATIC_DRAW,
+            BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+            BufferUsage::Stream => gl::STREAM_DRAW,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateStrategy {
+    // glBufferData(NULL) y después los datos: el driver da memoria nueva y no espera a la GPU
+    Orphan,
+    // glBufferSubData sobre el mismo almacenamiento, puede esperar si la GPU lo está leyendo
+    SubData,
+    // glBufferStorage + glMapBufferRange persistente en varias regiones protegidas con fences.
+    // Necesita OpenGL 4.4, si no está se usa Orphan
+    PersistentMap,
+}
+
+// VAO cuyo contenido se puede cambiar después de crearlo. Los vértices van intercalados en un
+// solo VBO según el layout y los índices siempre son u32
+pub struct DynamicModel {
+    vao: Vao,
+    vertex_vbo: GLuint,
+    index_vbo: GLuint,
+    layout: VertexLayout,
+    raw_model: RawModel,
+    usage: BufferUsage,
+    strategy: UpdateStrategy,
+    vertex_capacity: usize, // bytes, por región si el mapeo es persistente
+    index_capacity: usize,  // bytes
+    mapped: *mut u8,        // solo con PersistentMap
+    region: usize,
+    fences: [GLsync; PERSISTENT_REGIONS],
+    // Copia de los vértices de la región actual para pasarlos a la siguiente en las
+    // actualizaciones parciales, el mapeo es solo de escritura
+    shadow: Vec<u8>,
+}
+
+impl DynamicModel {
+    // max_vertices y max_indices reservan memoria. Con Orphan y SubData crecen si hace falta,
+    // con PersistentMap son el máximo
+    pub fn new(layout: &VertexLayout, max_vertices: usize, max_indices: usize,
+               usage: BufferUsage, strategy: UpdateStrategy) -> Result<DynamicModel, String> {
+        if !layout.is_interleaved() {
+            return Err("DynamicModel necesita un layout intercalado".to_string());
+        }
+        let stride = layout.get_attributes().first()
+            .map_or(0, |attribute| layout.get_stride(attribute));
+        let strategy = if strategy == UpdateStrategy::PersistentMap &&
+            !(gl::BufferStorage::is_loaded() && gl::FenceSync::is_loaded()) {
+            UpdateStrategy::Orphan
+        } else {
+            strategy
+        };
+
+        let mut vao = Vao::new();
+        vao.bind();
+        let vertex_vbo = Vbo::new();
+        let index_vbo = Vbo::new();
+        let vertex_capacity = max_vertices.max(1) * stride;
+        let index_capacity = max_indices.max(1) * mem::size_of::<GLuint>();
+        let mut mapped: *mut u8 = ptr::null_mut();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, vertex_vbo.get_id());
+            if strategy == UpdateStrategy::PersistentMap {
+                let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+                let size = (vertex_capacity * PERSISTENT_REGIONS) as GLsizeiptr;
+                gl::BufferStorage(gl::ARRAY_BUFFER, size, ptr::null(), flags);
+                mapped = gl::MapBufferRange(gl::ARRAY_BUFFER, 0, size, flags) as *mut u8;
+                if mapped.is_null() {
+                    gl::BindVertexArray(0);
+                    return Err("No se pudo mapear el VBO dinámico".to_string());
+                }
+            } else {
+                gl::BufferData(gl::ARRAY_BUFFER, vertex_capacity as GLsizeiptr, ptr::null(),
+                               usage.to_gl());
+            }
+            for attribute in layout.get_attributes() {
+                Loader::set_attribute_pointer(attribute, stride, 0);
+            }
+
+            // El GL_ELEMENT_ARRAY_BUFFER queda guardado en el VAO
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, index_vbo.get_id());
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, index_capacity as GLsizeiptr, ptr::null(),
+                           usage.to_gl());
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+
+        let raw_model = RawModel::with_index_type(vao.get_id(), 0, gl::UNSIGNED_INT);
+        let (vertex_vbo_id, index_vbo_id) = (vertex_vbo.get_id(), index_vbo.get_id());
+        vao.attach_vbo(vertex_vbo);
+        vao.attach_vbo(index_vbo);
+        Ok(DynamicModel {
+            vao,
+            vertex_vbo: vertex_vbo_id,
+            index_vbo: index_vbo_id,
+            layout: layout.clone(),
+            raw_model,
+            usage,
+            strategy,
+            vertex_capacity,
+            index_capacity,
+            mapped,
+            region: 0,
+            fences: [ptr::null(); PERSISTENT_REGIONS],
+            shadow: vec![],
+        })
+    }
+
+    // El vertex count es el número de índices que se dibujan
+    pub fn get_raw_model(&self) -> RawModel {
+        self.raw_model
+    }
+
+    pub fn get_strategy(&self) -> UpdateStrategy {
+        self.strategy
+    }
+
+    // Dibujar solo los primeros vertex_count índices
+    pub fn set_vertex_count(&mut self, vertex_count: i32) {
+        self.raw_model._set_vertex_count(vertex_count);
+    }
+
+    // Los vértices cambian en cada actualización, así que los volúmenes los da quien los cambia
+    pub fn set_bounds(&mut self, bounding_box: BoundingBox, bounding_sphere: BoundingSphere) {
+        self.raw_model.set_bounds(bounding_box, bounding_sphere);
+    }
+
+    // Sustituye todos los vértices
+    pub fn update_vertices(&mut self, data: &[u8]) -> Result<(), String> {
+        match self.strategy {
+            UpdateStrategy::PersistentMap => {
+                if data.len() > self.vertex_capacity {
+                    return Err(format!("{} bytes no caben en el VBO persistente de {}",
+                                       data.len(), self.vertex_capacity));
+                }
+                self.next_region()?;
+                self.shadow.clear();
+                self.shadow.extend_from_slice(data);
+                self.write_mapped(0, data);
+                self.point_attributes_to_region();
+            }
+            UpdateStrategy::Orphan => unsafe {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_vbo);
+                self.vertex_capacity = self.vertex_capacity.max(data.len());
+                gl::BufferData(gl::ARRAY_BUFFER, self.vertex_capacity as GLsizeiptr, ptr::null(),
+                               self.usage.to_gl());
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, data.len() as GLsizeiptr,
+                                  data.as_ptr() as *const c_void);
+                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            },
+            UpdateStrategy::SubData => unsafe {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_vbo);
+                if data.len() > self.vertex_capacity {
+                    self.vertex_capacity = data.len();
+                    gl::BufferData(gl::ARRAY_BUFFER, data.len() as GLsizeiptr,
+                                   data.as_ptr() as *const c_void, self.usage.to_gl());
+                } else {
+                    gl::BufferSubData(gl::ARRAY_BUFFER, 0, data.len() as GLsizeiptr,
+                                      data.as_ptr() as *const c_void);
+                }
+                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            },
+        }
+        Ok(())
+    }
+
+    // Cambia parte de los vértices, offset en bytes. No puede crecer el buffer
+    pub fn update_vertices_range(&mut self, offset: usize, data: &[u8]) -> Result<(), String> {
+        if offset + data.len() > self.vertex_capacity {
+            return Err(format!("El rango {}..{} se sale del VBO de {} bytes",
+                               offset, offset + data.len(), self.vertex_capacity));
+        }
+        if self.strategy == UpdateStrategy::PersistentMap {
+            // La región actual puede estar en uso por la GPU, así que el cambio se escribe en la
+            // siguiente junto con el resto de vértices actuales
+            self.next_region()?;
+            if self.shadow.len() < offset + data.len() {
+                self.shadow.resize(offset + data.len(), 0);
+            }
+            self.shadow[offset..offset + data.len()].copy_from_slice(data);
+            self.write_mapped(0, &self.shadow);
+            self.point_attributes_to_region();
+        } else {
+            unsafe {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_vbo);
+                gl::BufferSubData(gl::ARRAY_BUFFER, offset as GLintptr, data.len() as GLsizeiptr,
+                                  data.as_ptr() as *const c_void);
+                gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            }
+        }
+        Ok(())
+    }
+
+    // Sustituye los índices y el número de índices a dibujar
+    pub fn update_indices(&mut self, indices: &[u32]) {
+        let data = as_bytes(indices);
+        unsafe {
+            // Se enlaza el VAO para no cambiar el GL_ELEMENT_ARRAY_BUFFER de otro
+            self.vao.bind();
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.index_vbo);
+            if self.strategy == UpdateStrategy::SubData && data.len() <= self.index_capacity {
+                gl::BufferSubData(gl::ELEMENT_ARRAY_BUFFER, 0, data.len() as GLsizeiptr,
+                                  data.as_ptr() as *const c_void);
+            } else {
+                self.index_capacity = self.index_capacity.max(data.len());
+                gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, self.index_capacity as GLsizeiptr,
+                               ptr::null(), self.usage.to_gl());
+                gl::BufferSubData(gl::ELEMENT_ARRAY_BUFFER, 0, data.len() as GLsizeiptr,
+                                  data.as_ptr() as *const c_void);
+            }
+            gl::BindVertexArray(0);
+        }
+        self.raw_model._set_vertex_count(indices.len() as i32);
+    }
+
+    fn write_mapped(&self, offset: usize, data: &[u8]) {
+        unsafe {
+            let destination = self.mapped.add(self.region * self.vertex_capacity + offset);
+            ptr::copy_nonoverlapping(data.as_ptr(), destination, data.len());
+        }
+    }
+
+    // Protege la región que acaban de leer los draws ya enviados y pasa a la siguiente, esperando
+    // si la GPU todavía la está usando. Si no la suelta a tiempo la región no cambia
+    fn next_region(&mut self) -> Result<(), String> {
+        self.fence_region();
+        let next = (self.region + 1) % PERSISTENT_REGIONS;
+        self.wait_region(next)?;
+        self.region = next;
+        Ok(())
+    }
+
+    fn fence_region(&mut self) {
+        unsafe {
+            if !self.fences[self.region].is_null() {
+                gl::DeleteSync(self.fences[self.region]);
+            }
+            self.fences[self.region] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+        }
+    }
+
+    fn wait_region(&mut self, region: usize) -> Result<(), String> {
+        let fence = self.fences[region];
+        if fence.is_null() {
+            return Ok(());
+        }
+        let result = unsafe {
+            gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, FENCE_TIMEOUT)
+        };
+        match result {
+            gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => {
+                unsafe {
+                    gl::DeleteSync(fence);
+                }
+                self.fences[region] = ptr::null();
+                Ok(())
+            }
+            gl::TIMEOUT_EXPIRED =>
+                Err(format!("La GPU no soltó la región {} del VBO dinámico", region)),
+            _ => Err("Falló glClientWaitSync en el VBO dinámico".to_string()),
+        }
+    }
+
+    // Los atributos leen de la región donde se acaba de escribir
+    fn point_attributes_to_region(&self) {
+        self.vao.bind();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_vbo);
+            for attribute in self.layout.get_attributes() {
+                Loader::set_attribute_pointer(attribute, self.layout.get_stride(attribute),
+                                              self.region * self.vertex_capacity);
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for DynamicModel {
+    // El VAO borra los VBOs (y con ellos el mapeo), aquí solo quedan los fences
+    fn drop(&mut self) {
+        for fence in self.fences.iter() {
+            if !fence.is_null() {
+                unsafe {
+                    gl::DeleteSync(*fence);
+                }
+            }
+        }
+    }
+}
@@ -1,56 +1,135 @@
 use cgmath;
+use gl::types::GLint;
 
+use std::collections::HashMap;
 use std::ptr;
 
 use crate::entities::entity::Entity;
+use crate::models::raw_model::RawModel;
 use crate::models::textured_model::TexturedModel;
+use crate::render_engine::gl_resources::Vbo;
+use crate::render_engine::loader::Loader;
 use crate::render_engine::master_renderer::MasterRenderer;
+use crate::shaders::instanced_shader::InstancedShader;
 use crate::shaders::static_shader::StaticShader;
-use crate::toolbox::maths::*;
 
 type M4CG = cgmath::Matrix4<f32>;
+type V3CG = cgmath::Vector3<f32>;
+
+// Instancias que caben en el VBO de instancias, los lotes mayores se dibujan en varias llamadas
+const MAX_INSTANCES: usize = 10000;
+// Floats por instancia: 16 de la matriz de transformación + 2 del offset del atlas
+const INSTANCE_DATA_LENGTH: usize = 18;
+// Primer atributo por instancia, la matriz ocupa 3, 4, 5 y 6 y el offset el 7
+const FIRST_INSTANCE_ATTRIBUTE: u32 = 3;
+const INSTANCE_ATTRIBUTES: u32 = 5;
 
 pub struct EntityRenderer {
     shader: StaticShader,
+    instanced_shader: InstancedShader,
+    instance_vbo: Option<Vbo>, // del renderer, se suelta en cleanup() antes de cerrar el contexto
+    buffer: Vec<f32>,
 }
 
 impl EntityRenderer {
-    pub fn new(
-        shader: StaticShader, projection_matrix: &M4CG) -> EntityRenderer {
+    pub fn new(shader: StaticShader, instanced_shader: InstancedShader,
+               projection_matrix: &M4CG) -> EntityRenderer {
         shader.start();
         shader.load_projection_matrix(projection_matrix);
         shader.stop();
+        instanced_shader.start();
+        instanced_shader.load_projection_matrix(projection_matrix);
+        instanced_shader.stop();
         EntityRenderer {
             //projection_matrix,
             shader,
+            instanced_shader,
+            instance_vbo: Some(Loader::create_empty_vbo_handle(
+                MAX_INSTANCES * INSTANCE_DATA_LENGTH)),
+            buffer: Vec::with_capacity(MAX_INSTANCES * INSTANCE_DATA_LENGTH),
         }
     }
 
+    // Borra el VBO de instancias
+    pub fn cleanup(&mut self) {
+        self.instance_vbo = None;
+    }
+
+    fn get_instance_vbo_id(&self) -> u32 {
+        self.instance_vbo.as_ref().map(|vbo| vbo.get_id()).unwrap_or(0)
+    }
+
 
-    pub fn render(&mut self, entities: &Vec<Vec<Entity>>) {
-        for i in 0..entities.len() {
-            let vv = entities[i].clone();
-            for entity in vv {
-                self.prepare_textured_model(&entity.model);
-                //println!("e = {:#?} ", e);
-                self.prepare_instance(entity);
-                //dbg!(e.get_model().get_raw_model().get_vertex_count());
-                unsafe {
-                    gl::DrawElements(
-                        gl::TRIANGLES,// modo
-                        entity // Entity
-                            .get_model()
-                            .get_raw_model()
-                            .get_vertex_count(),// número de índices a
-                        // renderizar
-                        gl::UNSIGNED_INT,
-                        ptr::null());
+    // Enlaza cada modelo (cada nivel de detalle) una sola vez y dibuja todas sus instancias
+    pub fn render(&mut self, entities: &HashMap<TexturedModel, Vec<Entity>>,
+                  camera_position: V3CG, projection_scale: f32) {
+        for (model, batch) in entities {
+            for (raw_model, level_batch) in
+                EntityRenderer::split_by_lod(model, batch, camera_position, projection_scale) {
+                self.prepare_textured_model(model, &raw_model);
+                for entity in level_batch {
+                    self.prepare_instance(entity);
+                    unsafe {
+                        gl::DrawElements(
+                            gl::TRIANGLES,// modo
+                            raw_model.get_vertex_count(),// número de índices a renderizar
+                            raw_model.get_index_type(),
+                            ptr::null());
+                    }
                 }
+                self.unbind_textured_model();
             }
         }
     }
 
-    pub fn _unbind_textured_model(&mut self) {
+    // Un solo glDrawElementsInstanced por lote y nivel de detalle (o por cada MAX_INSTANCES
+    // entities)
+    pub fn render_instanced(&mut self, entities: &HashMap<TexturedModel, Vec<Entity>>,
+                            camera_position: V3CG, projection_scale: f32) {
+        for (model, batch) in entities {
+            for (raw_model, level_batch) in
+                EntityRenderer::split_by_lod(model, batch, camera_position, projection_scale) {
+                self.prepare_instanced_model(model, &raw_model);
+                for chunk in level_batch.chunks(MAX_INSTANCES) {
+                    self.buffer.clear();
+                    for entity in chunk {
+                        self.store_instance_data(entity);
+                    }
+                    Loader::update_vbo(self.get_instance_vbo_id(), &self.buffer);
+                    unsafe {
+                        gl::DrawElementsInstanced(
+                            gl::TRIANGLES,
+                            raw_model.get_vertex_count(),
+                            raw_model.get_index_type(),
+                            ptr::null(),
+                            chunk.len() as i32);
+                    }
+                }
+                self.unbind_instanced_model();
+            }
+        }
+    }
+
+    // Reparte el lote por nivel de detalle con la cadena del modelo del lote, no con la de cada
+    // entity. Los niveles sin entities no se devuelven
+    fn split_by_lod<'a>(model: &TexturedModel, batch: &'a [Entity], camera_position: V3CG,
+                        projection_scale: f32) -> Vec<(RawModel, Vec<&'a Entity>)> {
+        let chain = match model.get_lod_chain() {
+            Some(chain) => chain,
+            None => return vec![(model.get_raw_model(), batch.iter().collect())],
+        };
+        let mut levels: Vec<Vec<&Entity>> = vec![vec![]; chain.get_level_count()];
+        for entity in batch {
+            levels[entity.select_lod_level(&chain, camera_position, projection_scale)]
+                .push(entity);
+        }
+        levels.into_iter().enumerate()
+            .filter(|(_, level_batch)| !level_batch.is_empty())
+            .map(|(level, level_batch)| (model.get_lod_model(level), level_batch))
+            .collect()
+    }
+
+    pub fn unbind_textured_model(&mut self) {
         unsafe {
             MasterRenderer::enable_culling();
             gl::DisableVertexAttribArray(0);
@@ -60,13 +139,9 @@ impl EntityRenderer {
         }
     }
 
-    pub fn prepare_instance(&mut self, entity: Entity) {
+    pub fn prepare_instance(&mut self, entity: &Entity) {
         //Crea matriz de transformación con los datos de la entity
-        let transformation_matrix = create_transformation_matrix(
-            entity.get_position(),
-            entity.get_rotation_x(), entity.get_rotation_y(), entity.get_rotation_z(),
-            entity.get_scale(),
-        );
+        let transformation_matrix = entity.get_transformation_matrix();
 
         //Envia la matriz de transformación de la entity al shader
         self.shader.load_transformation_matrix(&transformation_matrix);
@@ -74,8 +149,79 @@ impl EntityRenderer {
     }
 
 
-    pub fn prepare_textured_model(&mut self, model: &TexturedModel) {
-        let raw_model = model.get_raw_model();
+    // Matriz de transformación (por columnas) y offset del atlas de la entity al buffer
+    fn store_instance_data(&mut self, entity: &Entity) {
+        let matrix = entity.get_transformation_matrix();
+        for column in 0..4 {
+            for row in 0..4 {
+                self.buffer.push(matrix[column][row]);
+            }
+        }
+        self.buffer.push(entity.get_texture_x_offset());
+        self.buffer.push(entity.get_texture_y_offset());
+    }
+
+    // raw_model: la malla del nivel de detalle que se va a dibujar
+    pub fn prepare_instanced_model(&mut self, model: &TexturedModel, raw_model: &RawModel) {
+        let vao_id = raw_model.get_vao_id();
+        let instance_vbo = self.get_instance_vbo_id();
+        if !EntityRenderer::has_instance_attributes(vao_id, instance_vbo) {
+            // El VAO guarda el enlace con el VBO de instancias, basta con hacerlo una vez
+            for column in 0..4 {
+                Loader::add_instanced_attribute(vao_id, instance_vbo,
+                                                FIRST_INSTANCE_ATTRIBUTE + column, 4,
+                                                INSTANCE_DATA_LENGTH as i32, column as i32 * 4);
+            }
+            Loader::add_instanced_attribute(vao_id, instance_vbo,
+                                            FIRST_INSTANCE_ATTRIBUTE + 4, 2,
+                                            INSTANCE_DATA_LENGTH as i32, 16);
+        }
+        unsafe {
+            gl::BindVertexArray(vao_id);
+            for attribute in 0..(FIRST_INSTANCE_ATTRIBUTE + INSTANCE_ATTRIBUTES) {
+                gl::EnableVertexAttribArray(attribute);
+            }
+
+            let texture = model.get_texture();
+            self.instanced_shader.load_number_of_rows(texture.get_number_of_rows());
+            if texture.is_has_transparency() {
+                MasterRenderer::disable_culling();
+            }
+
+            self.instanced_shader.load_model_colour(texture.get_colour());
+            self.instanced_shader.load_fake_lighting_variable(texture.is_use_fake_lighting());
+            self.instanced_shader.load_shine_variables(texture.get_shine_damper(),
+                                                       texture.get_reflectivity());
+
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, texture.get_id());
+        }
+    }
+
+    // Se pregunta al propio VAO en vez de guardar sus ids: al descargar un modelo GL puede dar el
+    // mismo id a un VAO nuevo que todavía no tiene los atributos por instancia
+    fn has_instance_attributes(vao_id: u32, instance_vbo: u32) -> bool {
+        let mut buffer: GLint = 0;
+        unsafe {
+            gl::BindVertexArray(vao_id);
+            gl::GetVertexAttribiv(FIRST_INSTANCE_ATTRIBUTE, gl::VERTEX_ATTRIB_ARRAY_BUFFER_BINDING,
+                                  &mut buffer);
+            gl::BindVertexArray(0);
+        }
+        buffer as u32 == instance_vbo
+    }
+
+    pub fn unbind_instanced_model(&mut self) {
+        unsafe {
+            MasterRenderer::enable_culling();
+            for attribute in 0..(FIRST_INSTANCE_ATTRIBUTE + INSTANCE_ATTRIBUTES) {
+                gl::DisableVertexAttribArray(attribute);
+            }
+            gl::BindVertexArray(0);
+        }
+    }
+
+    pub fn prepare_textured_model(&mut self, model: &TexturedModel, raw_model: &RawModel) {
         unsafe {
             gl::BindVertexArray(raw_model.get_vao_id());
             //Activa VAO 0 (vértices).
@@ -92,6 +238,7 @@ impl EntityRenderer {
                 MasterRenderer::disable_culling();
             }
 
+            self.shader.load_model_colour(texture.get_colour());
             self.shader.load_fake_lighting_variable(texture.is_use_fake_lighting());
             self.shader.load_shine_variables(texture.get_shine_damper(), texture.get_reflectivity());
 
@@ -0,0 +1,108 @@
+use gl;
+use gl::types::*;
+
+// Envoltorios de los objetos OpenGL. Cada uno es dueño de su id y lo borra en la GPU al
+// destruirse, así un nivel se descarga soltando sus handles
+
+pub struct Vao {
+    id: GLuint,
+    vbos: Vec<Vbo>, // VBOs enlazados al VAO, se borran con él
+}
+
+impl Vao {
+    pub fn new() -> Vao {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut id);
+        }
+        Vao {
+            id,
+            vbos: vec![],
+        }
+    }
+
+    pub fn get_id(&self) -> GLuint {
+        self.id
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindVertexArray(self.id);
+        }
+    }
+
+    // El VAO pasa a ser dueño del VBO
+    pub fn attach_vbo(&mut self, vbo: Vbo) {
+        self.vbos.push(vbo);
+    }
+}
+
+impl Drop for Vao {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe {
+                gl::DeleteVertexArrays(1, &self.id);
+            }
+        }
+    }
+}
+
+pub struct Vbo {
+    id: GLuint,
+}
+
+impl Vbo {
+    pub fn new() -> Vbo {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+        }
+        Vbo {
+            id
+        }
+    }
+
+    pub fn get_id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for Vbo {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe {
+                gl::DeleteBuffers(1, &self.id);
+            }
+        }
+    }
+}
+
+pub struct Texture {
+    id: GLuint,
+}
+
+impl Texture {
+    pub fn new() -> Texture {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+        }
+        Texture {
+            id
+        }
+    }
+
+    pub fn get_id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe {
+                gl::DeleteTextures(1, &self.id);
+            }
+        }
+    }
+}
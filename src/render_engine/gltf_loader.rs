@@ -0,0 +1,706 @@
+use cgmath::{vec3, vec4, InnerSpace, Matrix4, SquareMatrix};
+use gltf;
+use gltf::animation::util::ReadOutputs;
+use gltf::animation::{Interpolation, Property};
+use gltf::image::Format;
+use gltf::material::AlphaMode;
+use gltf::mesh::Mode;
+
+use crate::models::raw_model::RawModel;
+use crate::models::textured_model::TexturedModel;
+use crate::render_engine::loader::{Loader, JOINTS_ATTRIBUTE, TANGENT_ATTRIBUTE, WEIGHTS_ATTRIBUTE};
+use crate::render_engine::vertex_layout::{as_bytes, ComponentType, VertexAttribute, VertexLayout};
+use crate::textures::model_texture::ModelTexture;
+use crate::textures::texture_data::TextureData;
+use crate::textures::texture_options::TextureOptions;
+
+type V3CG = cgmath::Vector3<f32>;
+type V4CG = cgmath::Vector4<f32>;
+type M4CG = cgmath::Matrix4<f32>;
+
+// Un primitive de una malla glTF, ya en triángulos y con índices de 32 bits
+#[derive(Debug, Clone)]
+pub struct GLTFPrimitive {
+    positions: Vec<f32>,
+    texture_coords: Vec<f32>,
+    normals: Vec<f32>,
+    tangents: Option<Vec<f32>>, // xyz + w con el signo de la bitangente
+    joints: Option<Vec<u16>>,   // 4 huesos por vértice (JOINTS_0)
+    weights: Option<Vec<f32>>,  // 4 pesos por vértice (WEIGHTS_0)
+    indices: Vec<u32>,
+    material: Option<usize>,
+}
+
+impl GLTFPrimitive {
+    pub fn get_positions(&self) -> &Vec<f32> {
+        &self.positions
+    }
+
+    pub fn get_texture_coords(&self) -> &Vec<f32> {
+        &self.texture_coords
+    }
+
+    pub fn get_normals(&self) -> &Vec<f32> {
+        &self.normals
+    }
+
+    pub fn get_tangents(&self) -> Option<&Vec<f32>> {
+        self.tangents.as_ref()
+    }
+
+    pub fn get_joints(&self) -> Option<&Vec<u16>> {
+        self.joints.as_ref()
+    }
+
+    pub fn get_weights(&self) -> Option<&Vec<f32>> {
+        self.weights.as_ref()
+    }
+
+    pub fn get_indices(&self) -> &Vec<u32> {
+        &self.indices
+    }
+
+    pub fn get_material(&self) -> Option<usize> {
+        self.material
+    }
+}
+
+// Material PBR (metallic-roughness) del fichero
+#[derive(Debug, Clone)]
+pub struct GLTFMaterial {
+    name: String,
+    base_colour: V4CG,
+    base_colour_texture: Option<usize>, // índice en GLTFData::images
+    metallic: f32,
+    roughness: f32,
+    transparent: bool, // alphaMode MASK o BLEND
+    double_sided: bool,
+}
+
+impl GLTFMaterial {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_base_colour(&self) -> V4CG {
+        self.base_colour
+    }
+
+    pub fn get_base_colour_texture(&self) -> Option<usize> {
+        self.base_colour_texture
+    }
+
+    pub fn get_metallic(&self) -> f32 {
+        self.metallic
+    }
+
+    pub fn get_roughness(&self) -> f32 {
+        self.roughness
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    pub fn is_double_sided(&self) -> bool {
+        self.double_sided
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GLTFNode {
+    name: String,
+    transform: M4CG, // local, respecto al padre
+    mesh: Option<usize>,
+    skin: Option<usize>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+impl GLTFNode {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_transform(&self) -> M4CG {
+        self.transform
+    }
+
+    pub fn get_mesh(&self) -> Option<usize> {
+        self.mesh
+    }
+
+    pub fn get_skin(&self) -> Option<usize> {
+        self.skin
+    }
+
+    pub fn get_parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn get_children(&self) -> &Vec<usize> {
+        &self.children
+    }
+}
+
+// Esqueleto: nodos que hacen de huesos y sus matrices inversas de la pose de reposo
+#[derive(Debug, Clone)]
+pub struct GLTFSkin {
+    name: String,
+    joints: Vec<usize>,
+    inverse_bind_matrices: Vec<M4CG>,
+    skeleton: Option<usize>,
+}
+
+impl GLTFSkin {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_joints(&self) -> &Vec<usize> {
+        &self.joints
+    }
+
+    pub fn get_inverse_bind_matrices(&self) -> &Vec<M4CG> {
+        &self.inverse_bind_matrices
+    }
+
+    pub fn get_skeleton(&self) -> Option<usize> {
+        self.skeleton
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationProperty {
+    Translation,
+    Rotation, // cuaternión x, y, z, w
+    Scale,
+    MorphWeights,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationInterpolation {
+    Linear,
+    Step,
+    CubicSpline, // cada clave lleva tangente de entrada, valor y tangente de salida
+}
+
+// Valores de una propiedad de un nodo en el tiempo
+#[derive(Debug, Clone)]
+pub struct GLTFChannel {
+    node: usize,
+    property: AnimationProperty,
+    interpolation: AnimationInterpolation,
+    times: Vec<f32>,  // segundos
+    values: Vec<f32>, // seguidos, values.len() / times.len() componentes por clave
+}
+
+impl GLTFChannel {
+    pub fn get_node(&self) -> usize {
+        self.node
+    }
+
+    pub fn get_property(&self) -> AnimationProperty {
+        self.property
+    }
+
+    pub fn get_interpolation(&self) -> AnimationInterpolation {
+        self.interpolation
+    }
+
+    pub fn get_times(&self) -> &Vec<f32> {
+        &self.times
+    }
+
+    pub fn get_values(&self) -> &Vec<f32> {
+        &self.values
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GLTFAnimation {
+    name: String,
+    channels: Vec<GLTFChannel>,
+    duration: f32,
+}
+
+impl GLTFAnimation {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_channels(&self) -> &Vec<GLTFChannel> {
+        &self.channels
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        self.duration
+    }
+}
+
+// Contenido de un .gltf (+ .bin) o .glb ya decodificado, sin tocar OpenGL, así se puede leer
+// desde un hilo de carga. Las texturas embebidas o externas vienen ya como píxeles
+pub struct GLTFData {
+    meshes: Vec<Vec<GLTFPrimitive>>,
+    materials: Vec<GLTFMaterial>,
+    nodes: Vec<GLTFNode>,
+    roots: Vec<usize>, // nodos raíz de la escena por defecto
+    skins: Vec<GLTFSkin>,
+    animations: Vec<GLTFAnimation>,
+    images: Vec<TextureData>,
+}
+
+impl GLTFData {
+    pub fn load(path: &str) -> Result<GLTFData, String> {
+        let (document, buffers, images) = gltf::import(path)
+            .map_err(|e| format!("Could not load glTF {}: {}", path, e))?;
+        let buffer = |buffer: gltf::Buffer| buffers.get(buffer.index()).map(|data| &*data.0);
+
+        let mut meshes = vec![];
+        for mesh in document.meshes() {
+            let mut primitives = vec![];
+            for primitive in mesh.primitives() {
+                let reader = primitive.reader(buffer);
+                let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                    Some(positions) => positions.collect(),
+                    None => continue,
+                };
+                let count = positions.len();
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..count as u32).collect(),
+                };
+                let indices = match GLTFData::triangulate(primitive.mode(), indices) {
+                    Some(indices) => indices,
+                    // Puntos y líneas no se dibujan
+                    None => continue,
+                };
+                if let Some(&index) = indices.iter().find(|&&index| index as usize >= count) {
+                    return Err(format!("Could not load glTF {}: índice {} fuera de rango \
+                                        (hay {} vértices)", path, index, count));
+                }
+                let texture_coords: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+                    Some(coords) => coords.into_f32().collect(),
+                    None => vec![[0.0, 0.0]; count],
+                };
+                let mut data = GLTFPrimitive {
+                    positions: positions.iter().flat_map(|p| p.to_vec()).collect(),
+                    texture_coords: texture_coords.iter().flat_map(|t| t.to_vec()).collect(),
+                    normals: vec![],
+                    tangents: reader.read_tangents()
+                        .map(|tangents| tangents.flat_map(|t| t.to_vec()).collect()),
+                    joints: reader.read_joints(0)
+                        .map(|joints| joints.into_u16().flat_map(|j| j.to_vec()).collect()),
+                    weights: reader.read_weights(0)
+                        .map(|weights| weights.into_f32().flat_map(|w| w.to_vec()).collect()),
+                    indices,
+                    material: primitive.material().index(),
+                };
+                match reader.read_normals() {
+                    Some(normals) => data.normals = normals.flat_map(|n| n.to_vec()).collect(),
+                    // Sin normales la especificación pide normales planas
+                    None => GLTFData::generate_flat_normals(&mut data),
+                }
+                primitives.push(data);
+            }
+            meshes.push(primitives);
+        }
+
+        let materials = document.materials().map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            let colour = pbr.base_color_factor();
+            GLTFMaterial {
+                name: material.name().unwrap_or("").to_string(),
+                base_colour: vec4(colour[0], colour[1], colour[2], colour[3]),
+                base_colour_texture: pbr.base_color_texture()
+                    .map(|info| info.texture().source().index()),
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+                transparent: material.alpha_mode() != AlphaMode::Opaque,
+                double_sided: material.double_sided(),
+            }
+        }).collect();
+
+        let mut nodes: Vec<GLTFNode> = document.nodes().map(|node| GLTFNode {
+            name: node.name().unwrap_or("").to_string(),
+            transform: Matrix4::from(node.transform().matrix()),
+            mesh: node.mesh().map(|mesh| mesh.index()),
+            skin: node.skin().map(|skin| skin.index()),
+            parent: None,
+            children: node.children().map(|child| child.index()).collect(),
+        }).collect();
+        for index in 0..nodes.len() {
+            for child in nodes[index].children.clone() {
+                nodes[child].parent = Some(index);
+            }
+        }
+        let roots = match document.default_scene().or(document.scenes().next()) {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => (0..nodes.len()).filter(|&index| nodes[index].parent.is_none()).collect(),
+        };
+
+        let skins = document.skins().map(|skin| {
+            let reader = skin.reader(buffer);
+            let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+                Some(matrices) => matrices.map(Matrix4::from).collect(),
+                None => vec![M4CG::identity(); skin.joints().count()],
+            };
+            GLTFSkin {
+                name: skin.name().unwrap_or("").to_string(),
+                joints: skin.joints().map(|joint| joint.index()).collect(),
+                inverse_bind_matrices,
+                skeleton: skin.skeleton().map(|node| node.index()),
+            }
+        }).collect();
+
+        let animations = document.animations().map(|animation| {
+            let mut channels = vec![];
+            for channel in animation.channels() {
+                let reader = channel.reader(buffer);
+                let times: Vec<f32> = match reader.read_inputs() {
+                    Some(inputs) => inputs.collect(),
+                    None => continue,
+                };
+                let values: Vec<f32> = match reader.read_outputs() {
+                    Some(ReadOutputs::Translations(values)) =>
+                        values.flat_map(|v| v.to_vec()).collect(),
+                    Some(ReadOutputs::Rotations(values)) =>
+                        values.into_f32().flat_map(|v| v.to_vec()).collect(),
+                    Some(ReadOutputs::Scales(values)) =>
+                        values.flat_map(|v| v.to_vec()).collect(),
+                    Some(ReadOutputs::MorphTargetWeights(values)) => values.into_f32().collect(),
+                    None => continue,
+                };
+                channels.push(GLTFChannel {
+                    node: channel.target().node().index(),
+                    property: match channel.target().property() {
+                        Property::Translation => AnimationProperty::Translation,
+                        Property::Rotation => AnimationProperty::Rotation,
+                        Property::Scale => AnimationProperty::Scale,
+                        Property::MorphTargetWeights => AnimationProperty::MorphWeights,
+                    },
+                    interpolation: match channel.sampler().interpolation() {
+                        Interpolation::Linear => AnimationInterpolation::Linear,
+                        Interpolation::Step => AnimationInterpolation::Step,
+                        Interpolation::CubicSpline => AnimationInterpolation::CubicSpline,
+                    },
+                    times,
+                    values,
+                });
+            }
+            let duration = channels.iter()
+                .filter_map(|channel| channel.times.last().cloned())
+                .fold(0.0, f32::max);
+            GLTFAnimation {
+                name: animation.name().unwrap_or("").to_string(),
+                channels,
+                duration,
+            }
+        }).collect();
+
+        Ok(GLTFData {
+            meshes,
+            materials,
+            nodes,
+            roots,
+            skins,
+            animations,
+            images: images.iter().map(GLTFData::to_texture_data).collect(),
+        })
+    }
+
+    pub fn get_meshes(&self) -> &Vec<Vec<GLTFPrimitive>> {
+        &self.meshes
+    }
+
+    pub fn get_materials(&self) -> &Vec<GLTFMaterial> {
+        &self.materials
+    }
+
+    pub fn get_nodes(&self) -> &Vec<GLTFNode> {
+        &self.nodes
+    }
+
+    pub fn get_roots(&self) -> &Vec<usize> {
+        &self.roots
+    }
+
+    pub fn get_skins(&self) -> &Vec<GLTFSkin> {
+        &self.skins
+    }
+
+    pub fn get_animations(&self) -> &Vec<GLTFAnimation> {
+        &self.animations
+    }
+
+    pub fn get_images(&self) -> &Vec<TextureData> {
+        &self.images
+    }
+
+    // Transformación del nodo respecto a la raíz de la escena. Un fichero mal formado puede
+    // tener ciclos en la jerarquía: se sube hasta volver a un nodo ya visitado
+    pub fn get_world_transform(&self, node: usize) -> M4CG {
+        let mut visited = vec![false; self.nodes.len()];
+        visited[node] = true;
+        let mut transform = self.nodes[node].transform;
+        let mut parent = self.nodes[node].parent;
+        while let Some(index) = parent {
+            if visited[index] {
+                break;
+            }
+            visited[index] = true;
+            transform = self.nodes[index].transform * transform;
+            parent = self.nodes[index].parent;
+        }
+        transform
+    }
+
+    // Pasa tiras y abanicos a lista de triángulos. None si no son triángulos
+    fn triangulate(mode: Mode, indices: Vec<u32>) -> Option<Vec<u32>> {
+        match mode {
+            Mode::Triangles => Some(indices),
+            Mode::TriangleStrip => Some((2..indices.len()).flat_map(|i| {
+                // Los impares van al revés para mantener el orden de los vértices
+                if i % 2 == 0 {
+                    vec![indices[i - 2], indices[i - 1], indices[i]]
+                } else {
+                    vec![indices[i - 1], indices[i - 2], indices[i]]
+                }
+            }).collect()),
+            Mode::TriangleFan => Some((2..indices.len())
+                .flat_map(|i| vec![indices[0], indices[i - 1], indices[i]])
+                .collect()),
+            _ => None,
+        }
+    }
+
+    // Cada triángulo con sus propios vértices y la normal de la cara
+    fn generate_flat_normals(primitive: &mut GLTFPrimitive) {
+        fn expand<T: Copy>(data: &[T], components: usize, indices: &[u32]) -> Vec<T> {
+            indices.iter()
+                .flat_map(|&index| {
+                    let start = index as usize * components;
+                    data[start..start + components].to_vec()
+                })
+                .collect()
+        }
+        let indices = primitive.indices.clone();
+        primitive.positions = expand(&primitive.positions, 3, &indices);
+        primitive.texture_coords = expand(&primitive.texture_coords, 2, &indices);
+        primitive.tangents = primitive.tangents.as_ref().map(|data| expand(data, 4, &indices));
+        primitive.joints = primitive.joints.as_ref().map(|data| expand(data, 4, &indices));
+        primitive.weights = primitive.weights.as_ref().map(|data| expand(data, 4, &indices));
+        primitive.indices = (0..indices.len() as u32).collect();
+
+        let p = &primitive.positions;
+        let position = |i: usize| vec3(p[i * 3], p[i * 3 + 1], p[i * 3 + 2]);
+        let mut normals = Vec::with_capacity(p.len());
+        for triangle in 0..indices.len() / 3 {
+            let v0: V3CG = position(triangle * 3);
+            let cross = (position(triangle * 3 + 1) - v0).cross(position(triangle * 3 + 2) - v0);
+            let normal = if cross == vec3(0.0, 0.0, 0.0) { vec3(0.0, 1.0, 0.0) } else {
+                cross.normalize()
+            };
+            for _ in 0..3 {
+                normals.extend_from_slice(&[normal.x, normal.y, normal.z]);
+            }
+        }
+        primitive.normals = normals;
+    }
+
+    // Los formatos de 16 bits se quedan con el byte alto, BGR(A) se reordena
+    fn to_texture_data(image: &gltf::image::Data) -> TextureData {
+        let pixels = &image.pixels;
+        let buffer = match image.format {
+            Format::R8 | Format::R8G8 | Format::R8G8B8 | Format::R8G8B8A8 => pixels.clone(),
+            Format::B8G8R8 => pixels.chunks(3).flat_map(|p| vec![p[2], p[1], p[0]]).collect(),
+            Format::B8G8R8A8 =>
+                pixels.chunks(4).flat_map(|p| vec![p[2], p[1], p[0], p[3]]).collect(),
+            Format::R16 | Format::R16G16 | Format::R16G16B16 | Format::R16G16B16A16 =>
+                pixels.chunks(2).map(|value| value[1]).collect(),
+        };
+        TextureData::new(buffer, image.width, image.height)
+    }
+}
+
+// Un primitive de un nodo ya subido a la GPU
+#[derive(Debug, Clone, Copy)]
+pub struct GLTFModelPart {
+    node: usize,
+    transform: M4CG, // del nodo respecto a la raíz, para componerla con la de la Entity
+    textured_model: TexturedModel,
+}
+
+impl GLTFModelPart {
+    pub fn get_node(&self) -> usize {
+        self.node
+    }
+
+    pub fn get_transform(&self) -> M4CG {
+        self.transform
+    }
+
+    pub fn get_textured_model(&self) -> TexturedModel {
+        self.textured_model
+    }
+}
+
+// Resultado de GLTFLoader: las partes listas para dibujar y los datos del fichero (nodos,
+// esqueletos, animaciones) para quien los necesite
+pub struct GLTFModel {
+    parts: Vec<GLTFModelPart>,
+    data: GLTFData,
+}
+
+impl GLTFModel {
+    pub fn get_parts(&self) -> &Vec<GLTFModelPart> {
+        &self.parts
+    }
+
+    pub fn get_data(&self) -> &GLTFData {
+        &self.data
+    }
+
+    pub fn get_skins(&self) -> &Vec<GLTFSkin> {
+        self.data.get_skins()
+    }
+
+    pub fn get_animations(&self) -> &Vec<GLTFAnimation> {
+        self.data.get_animations()
+    }
+}
+
+// Carga glTF 2.0 a la GPU a través del Loader, que es quien libera VAOs y texturas
+pub struct GLTFLoader {
+    options: TextureOptions,
+}
+
+impl GLTFLoader {
+    pub fn new() -> GLTFLoader {
+        GLTFLoader::with_texture_options(TextureOptions::new())
+    }
+
+    pub fn with_texture_options(options: TextureOptions) -> GLTFLoader {
+        GLTFLoader {
+            options,
+        }
+    }
+
+    pub fn load_gltf_model(&mut self, filename: &str, loader: &mut Loader)
+                           -> Result<GLTFModel, String> {
+        let data = GLTFData::load(filename)?;
+        self.load_gltf_data(data, loader)
+    }
+
+    // Sube datos ya leídos con GLTFData::load (por ejemplo desde otro hilo)
+    pub fn load_gltf_data(&mut self, data: GLTFData, loader: &mut Loader)
+                          -> Result<GLTFModel, String> {
+        // Cada imagen se sube una sola vez aunque la usen varios materiales
+        let mut images: Vec<Option<u32>> = vec![None; data.images.len()];
+        let mut textures: Vec<ModelTexture> = vec![];
+        for material in data.materials.iter() {
+            let texture_id = match material.base_colour_texture {
+                Some(image) => match images[image] {
+                    Some(texture_id) => texture_id,
+                    None => {
+                        let texture_id =
+                            loader.load_texture_data(&data.images[image], &self.options);
+                        images[image] = Some(texture_id);
+                        texture_id
+                    }
+                },
+                None =>
+                    loader.load_solid_colour_texture(GLTFLoader::to_rgba(material.base_colour)),
+            };
+            let mut texture = GLTFLoader::model_texture(texture_id, material);
+            // La textura de color sólido ya es el base colour, solo se tiñe la imagen
+            if material.base_colour_texture.is_some() {
+                texture.set_colour(material.base_colour.truncate());
+            }
+            textures.push(texture);
+        }
+        // Para los primitives sin material, blanco mate
+        let default_texture = ModelTexture::new(loader.load_solid_colour_texture([255; 4]));
+
+        let mut parts = vec![];
+        // Cada malla se sube una sola vez aunque la usen varios nodos
+        let mut meshes: Vec<Option<Vec<RawModel>>> = vec![None; data.meshes.len()];
+        // Cada nodo una sola vez aunque la jerarquía tenga ciclos o nodos compartidos
+        let mut visited = vec![false; data.nodes.len()];
+        let mut pending = data.roots.clone();
+        while let Some(node) = pending.pop() {
+            if visited[node] {
+                continue;
+            }
+            visited[node] = true;
+            pending.extend(data.nodes[node].children.iter().cloned());
+            let mesh = match data.nodes[node].mesh {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            let transform = data.get_world_transform(node);
+            if meshes[mesh].is_none() {
+                let raw_models = data.meshes[mesh].iter()
+                    .map(|primitive| GLTFLoader::load_primitive(primitive, loader))
+                    .collect::<Result<Vec<RawModel>, String>>()?;
+                meshes[mesh] = Some(raw_models);
+            }
+            let raw_models = meshes[mesh].as_ref().unwrap();
+            for (primitive, &raw_model) in data.meshes[mesh].iter().zip(raw_models) {
+                let texture = primitive.material.map_or(default_texture, |index| textures[index]);
+                parts.push(GLTFModelPart {
+                    node,
+                    transform,
+                    textured_model: TexturedModel::new(raw_model, texture),
+                });
+            }
+        }
+        Ok(GLTFModel {
+            parts,
+            data,
+        })
+    }
+
+    // Posición, uv y normal en 0, 1 y 2 como los OBJ, más tangentes, huesos y pesos si hay
+    fn load_primitive(primitive: &GLTFPrimitive, loader: &mut Loader)
+                      -> Result<RawModel, String> {
+        let mut layout = VertexLayout::position_texture_normal(false);
+        let mut buffers: Vec<&[u8]> = vec![
+            as_bytes(&primitive.positions),
+            as_bytes(&primitive.texture_coords),
+            as_bytes(&primitive.normals),
+        ];
+        if let Some(tangents) = primitive.tangents.as_ref() {
+            layout.add_attribute(VertexAttribute::new(TANGENT_ATTRIBUTE, 4, ComponentType::Float));
+            buffers.push(as_bytes(tangents));
+        }
+        if let (Some(joints), Some(weights)) = (&primitive.joints, &primitive.weights) {
+            let mut attribute =
+                VertexAttribute::new(JOINTS_ATTRIBUTE, 4, ComponentType::UnsignedShort);
+            attribute.set_integer(true);
+            layout.add_attribute(attribute);
+            layout.add_attribute(VertexAttribute::new(WEIGHTS_ATTRIBUTE, 4, ComponentType::Float));
+            buffers.push(as_bytes(joints));
+            buffers.push(as_bytes(weights));
+        }
+        loader.load_to_vao_with_layout(&layout, &buffers, &primitive.indices)
+    }
+
+    // El motor usa Phong: el brillo sale de la rugosidad (exponente de Blinn-Phong para
+    // alfa = rugosidad²) y los materiales de doble cara se tratan como transparentes para que
+    // no se descarten sus caras traseras
+    fn model_texture(texture_id: u32, material: &GLTFMaterial) -> ModelTexture {
+        let alpha = (material.roughness * material.roughness).max(0.001);
+        let mut texture = ModelTexture::new(texture_id);
+        texture.set_shine_damper((2.0 / (alpha * alpha) - 2.0).clamp(1.0, 1000.0));
+        texture.set_reflectivity(1.0 - material.roughness);
+        texture.set_has_transparency(material.transparent || material.double_sided);
+        texture
+    }
+
+    fn to_rgba(colour: V4CG) -> [u8; 4] {
+        let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [to_byte(colour.x), to_byte(colour.y), to_byte(colour.z), to_byte(colour.w)]
+    }
+}
+
@@ -3,20 +3,48 @@ use gl::types::*;
 use image::ColorType;
 use image::GenericImageView;
 
+use std::ffi::CStr;
 use std::mem;
-use std::os::raw::c_void;
+use std::os::raw::{c_char, c_void};
 use std::path::Path;
 
+use crate::models::bounds::{BoundingBox, BoundingSphere};
 use crate::models::raw_model::RawModel;
+use crate::obj_converter::binary_mesh::BinaryMesh;
+use crate::obj_converter::model_data::ModelData;
+use crate::render_engine::gl_resources::{Texture, Vao, Vbo};
+use crate::render_engine::vertex_layout::{as_bytes, ComponentType, VertexAttribute, VertexLayout};
+use crate::textures::block_decoder;
+use crate::textures::compressed_texture::{CompressedFormat, CompressedTexture};
 use crate::textures::texture_data::TextureData;
+use crate::textures::texture_options::{TextureFilter, TextureOptions};
 
 //use std::ptr;
 
+// GL_EXT_texture_filter_anisotropic (core desde OpenGL 4.6)
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+// Gris -> (R, R, R, 1) y gris+alfa -> (R, R, R, G)
+const GRAY_SWIZZLE: [GLint; 4] =
+    [gl::RED as GLint, gl::RED as GLint, gl::RED as GLint, gl::ONE as GLint];
+const GRAY_ALPHA_SWIZZLE: [GLint; 4] =
+    [gl::RED as GLint, gl::RED as GLint, gl::RED as GLint, gl::GREEN as GLint];
+
+// Locations de los atributos opcionales. 3 a 7 son de las instancias (EntityRenderer)
+pub const TANGENT_ATTRIBUTE: GLuint = 8;
+pub const JOINTS_ATTRIBUTE: GLuint = 9;
+pub const WEIGHTS_ATTRIBUTE: GLuint = 10;
+
+// Los load_* devuelven ids y el Loader guarda los handles hasta cleanup() o unload_*().
+// Los load_*_handle devuelven el handle y es el llamador quien decide cuándo se libera
 pub struct Loader {
     raw_model: Option<RawModel>,
-    vaos: Vec<GLuint>,
-    vbos: Vec<GLuint>,
-    textures: Vec<GLuint>,
+    vaos: Vec<Vao>,
+    vbos: Vec<Vbo>, // VBOs que no pertenecen a un VAO (instancias)
+    textures: Vec<Texture>,
+    max_anisotropy: Option<f32>,
+    compressed_formats: Option<Vec<GLint>>,
 }
 
 impl Loader {
@@ -27,6 +55,8 @@ impl Loader {
             vaos: vec![],
             vbos: vec![],
             textures: vec![],
+            max_anisotropy: None,
+            compressed_formats: None,
         }
     }
 
@@ -35,92 +65,451 @@ impl Loader {
                        texture_coords: &Vec<f32>,
                        normals: &Vec<f32>,
                        indices: &Vec<u32>) -> RawModel {
-        let vao_id = self.create_vao(); //Crea VAO y almacena ID en vaoID.
-        self.bind_indices_vbo(indices);
+        let (vao, raw_model) = self.load_to_vao_handle(positions, texture_coords, normals, indices);
+        self.vaos.push(vao);
+        self.raw_model = Some(raw_model);
+        raw_model
+    }
+
+    pub fn load_to_vao_handle(&mut self,
+                              positions: &[f32],
+                              texture_coords: &[f32],
+                              normals: &[f32],
+                              indices: &[u32]) -> (Vao, RawModel) {
+        // Un VBO por atributo: posición, uv y normal en las locations 0, 1 y 2
+        let layout = VertexLayout::position_texture_normal(false);
+        let buffers = [as_bytes(positions), as_bytes(texture_coords), as_bytes(normals)];
+        self.load_to_vao_handle_with_layout(&layout, &buffers, indices).unwrap()
+    }
+
+    pub fn load_to_vao_with_layout(&mut self, layout: &VertexLayout, buffers: &[&[u8]],
+                                   indices: &[u32]) -> Result<RawModel, String> {
+        let (vao, raw_model) = self.load_to_vao_handle_with_layout(layout, buffers, indices)?;
+        self.vaos.push(vao);
+        self.raw_model = Some(raw_model);
+        Ok(raw_model)
+    }
+
+    // buffers: un solo buffer con los vértices intercalados, o uno por atributo en el orden del
+    // layout si no es intercalado. Los índices se guardan en 16 bits si caben
+    pub fn load_to_vao_handle_with_layout(&mut self, layout: &VertexLayout, buffers: &[&[u8]],
+                                          indices: &[u32]) -> Result<(Vao, RawModel), String> {
+        let (vao, mut raw_model) = self.create_vao_with_layout(layout, buffers, indices)?;
+        // Los volúmenes salen del atributo 0 si son posiciones xyz en float
+        if let Some(positions) = Loader::read_positions(layout, buffers) {
+            raw_model.set_bounds(BoundingBox::from_positions(&positions),
+                                 BoundingSphere::from_positions(&positions));
+        }
+        Ok((vao, raw_model))
+    }
 
+    fn create_vao_with_layout(&mut self, layout: &VertexLayout, buffers: &[&[u8]],
+                              indices: &[u32]) -> Result<(Vao, RawModel), String> {
+        if buffers.len() != layout.get_buffer_count() {
+            return Err(format!("El layout necesita {} buffers y se han pasado {}",
+                               layout.get_buffer_count(), buffers.len()));
+        }
+        let mut vao = self.create_vao(); //Crea VAO y lo activa
+        let index_type = self.bind_indices_vbo(&mut vao, indices);
 
-        //almacena datos posicionales en lista de atributos
-        self.store_data_in_attribute_list(0, 3, positions);
-        //almacena datos u,v de textura en lista de atributos
-        self.store_data_in_attribute_list(1, 2, texture_coords);
-        //almacena datos de normals en lista de atributos
-        self.store_data_in_attribute_list(2, 3, normals);
+        if layout.is_interleaved() {
+            let vbo = Loader::store_bytes_in_vbo(buffers[0]);
+            for attribute in layout.get_attributes() {
+                Loader::set_attribute_pointer(attribute, layout.get_stride(attribute), 0);
+            }
+            vao.attach_vbo(vbo);
+        } else {
+            for (attribute, data) in layout.get_attributes().iter().zip(buffers) {
+                let vbo = Loader::store_bytes_in_vbo(data);
+                Loader::set_attribute_pointer(attribute, layout.get_stride(attribute), 0);
+                vao.attach_vbo(vbo);
+            }
+        }
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
 
         self.unbind_vao();
-        //dbg!(positions.len() * std::mem::size_of::<f32>());
-        self.raw_model = Some(RawModel::new(vao_id, indices.len() as i32));
-        self.raw_model.unwrap()
+        let raw_model = RawModel::with_index_type(vao.get_id(), indices.len() as i32, index_type);
+        Ok((vao, raw_model))
+    }
+
+    pub fn load_model_data(&mut self, data: &ModelData) -> RawModel {
+        let (vao, raw_model) = self.load_model_data_handle(data);
+        self.vaos.push(vao);
+        self.raw_model = Some(raw_model);
+        raw_model
     }
+
+    // Como load_to_vao_handle, más las tangentes en TANGENT_ATTRIBUTE si la malla las tiene
+    pub fn load_model_data_handle(&mut self, data: &ModelData) -> (Vao, RawModel) {
+        let mut layout = VertexLayout::position_texture_normal(false);
+        let mut buffers = vec![as_bytes(data.get_vertices()),
+            as_bytes(data.get_texture_coords()),
+            as_bytes(data.get_normals())];
+        if data.has_tangents() {
+            layout.add_attribute(VertexAttribute::new(TANGENT_ATTRIBUTE, 4, ComponentType::Float));
+            buffers.push(as_bytes(data.get_tangents()));
+        }
+        self.load_to_vao_handle_with_layout(&layout, &buffers, data.get_indices()).unwrap()
+    }
+
+    pub fn load_binary_mesh(&mut self, mesh: &BinaryMesh) -> RawModel {
+        let (vao, raw_model) = self.load_binary_mesh_handle(mesh);
+        self.vaos.push(vao);
+        self.raw_model = Some(raw_model);
+        raw_model
+    }
+
+    // Los arrays van del fichero mapeado a los VBOs sin pasar por Vec
+    pub fn load_binary_mesh_handle(&mut self, mesh: &BinaryMesh) -> (Vao, RawModel) {
+        let mut layout = VertexLayout::position_texture_normal(false);
+        let mut buffers = vec![as_bytes(mesh.get_vertices()),
+            as_bytes(mesh.get_texture_coords()),
+            as_bytes(mesh.get_normals())];
+        if mesh.has_tangents() {
+            layout.add_attribute(VertexAttribute::new(TANGENT_ATTRIBUTE, 4, ComponentType::Float));
+            buffers.push(as_bytes(mesh.get_tangents()));
+        }
+        // Los volúmenes vienen en la cabecera, no hace falta recorrer los vértices
+        let (vao, mut raw_model) =
+            self.create_vao_with_layout(&layout, &buffers, mesh.get_indices()).unwrap();
+        raw_model.set_bounds(mesh.get_bounding_box(), mesh.get_bounding_sphere());
+        (vao, raw_model)
+    }
+
+    // Copia las posiciones (location 0, 3 floats) de los buffers, estén o no intercalados
+    fn read_positions(layout: &VertexLayout, buffers: &[&[u8]]) -> Option<Vec<f32>> {
+        let (index, attribute) = layout.get_attributes().iter().enumerate()
+            .find(|(_, attribute)| attribute.get_location() == 0)?;
+        if attribute.get_components() != 3 || attribute.get_component_type() != ComponentType::Float {
+            return None;
+        }
+        let data = if layout.is_interleaved() { buffers[0] } else { buffers[index] };
+        let stride = layout.get_stride(attribute);
+        let mut positions = Vec::with_capacity(data.len() / stride * 3);
+        let mut start = attribute.get_offset();
+        while start + 12 <= data.len() {
+            for component in data[start..start + 12].chunks(4) {
+                positions.push(f32::from_ne_bytes([component[0], component[1], component[2],
+                    component[3]]));
+            }
+            start += stride;
+        }
+        Some(positions)
+    }
+
     pub fn load_to_vao2(&mut self, positions: &Vec<f32>, dimensions: i32) -> RawModel {
-        let vao_id = self.create_vao(); //Crea VAO y almacena ID en vaoID.
-        self.store_data_in_attribute_list(0, dimensions, positions);
+        let mut vao = self.create_vao(); //Crea VAO y lo activa
+        self.store_data_in_attribute_list(&mut vao, 0, dimensions, positions);
         self.unbind_vao();
-        self.raw_model = Some(RawModel::new(vao_id, positions.len() as i32 / dimensions));
+        self.raw_model = Some(RawModel::new(vao.get_id(), positions.len() as i32 / dimensions));
+        self.vaos.push(vao);
         self.raw_model.unwrap()
     }
 
-    pub fn load_texture(&mut self, path: &str) -> Result<u32, String> {// read image data
-        let img = image::open(&Path::new(path)).map_err(|e| format!("Could not load texture {}", e))?;
+    pub fn load_texture(&mut self, path: &str) -> Result<u32, String> {
+        self.load_texture_with_options(path, &TextureOptions::new())
+    }
+
+    pub fn load_texture_with_options(&mut self, path: &str, options: &TextureOptions)
+                                     -> Result<u32, String> {
+        let texture = self.load_texture_handle_with_options(path, options)?;
+        let texture_id = texture.get_id();
+        self.textures.push(texture);
+        Ok(texture_id)
+    }
+
+    pub fn load_texture_handle(&mut self, path: &str) -> Result<Texture, String> {
+        self.load_texture_handle_with_options(path, &TextureOptions::new())
+    }
+
+    pub fn load_texture_handle_with_options(&mut self, path: &str, options: &TextureOptions)
+                                            -> Result<Texture, String> {
+        if CompressedTexture::is_container(path) {
+            return self.load_compressed_texture_handle(path, options);
+        }
+        let data = Loader::decode_texture(path, options)?;
+        Ok(self.load_texture_from_data(&data, options))
+    }
 
-        let format = match img.color() {
-            ColorType::RGB(_) => gl::RGB,
-            ColorType::RGBA(_) => gl::RGBA,
-            ColorType::Gray(_) => gl::DEPTH_COMPONENT,
-            ColorType::GrayA(_) => gl::DEPTH_STENCIL,
-            ColorType::BGR(_) => gl::BGR,
-            ColorType::BGRA(_) => gl::BGRA,
-            ColorType::Palette(_) => gl::DEPTH_COMPONENT,
+    pub fn load_texture_data(&mut self, data: &TextureData, options: &TextureOptions) -> u32 {
+        let texture = self.load_texture_from_data(data, options);
+        let texture_id = texture.get_id();
+        self.textures.push(texture);
+        texture_id
+    }
+
+    pub fn load_solid_colour_texture(&mut self, rgba: [u8; 4]) -> u32 {
+        let texture = self.load_solid_colour_texture_handle(rgba);
+        let texture_id = texture.get_id();
+        self.textures.push(texture);
+        texture_id
+    }
+
+    // Textura de 1x1 de un solo color, para materiales sin mapa difuso
+    pub fn load_solid_colour_texture_handle(&mut self, rgba: [u8; 4]) -> Texture {
+        let mut options = TextureOptions::new();
+        options.set_filter(TextureFilter::Nearest);
+        let data = TextureData::new(rgba.to_vec(), 1, 1);
+        self.load_texture_from_data(&data, &options)
+    }
+
+    // Lee y decodifica la imagen sin tocar OpenGL, se puede llamar desde cualquier hilo.
+    // Gris y gris+alfa se quedan en 1 y 2 canales, las imágenes con paleta se expanden a RGBA
+    // y BGR(A) se reordena al convertir
+    pub fn decode_texture(path: &str, options: &TextureOptions) -> Result<TextureData, String> {
+        let img = image::open(Path::new(path)).map_err(|e| format!("Could not load texture {}", e))?;
+        let (width, height) = img.dimensions();
+
+        let mut pixels = match img.color() {
+            ColorType::Gray(_) => img.to_luma().into_raw(),
+            ColorType::GrayA(_) => img.to_luma_alpha().into_raw(),
+            ColorType::RGB(_) | ColorType::BGR(_) => img.to_rgb().into_raw(),
+            ColorType::RGBA(_) | ColorType::BGRA(_) | ColorType::Palette(_) => img.to_rgba().into_raw(),
         };
 
-        unsafe {
-            // Mipmapping
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER,
-                              gl::LINEAR_MIPMAP_LINEAR as GLint);
-            gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_LOD_BIAS, -0.4);
-            // initialize texture
+        if options.is_premultiply_alpha() {
+            match img.color() {
+                ColorType::GrayA(_) => Loader::premultiply_alpha(&mut pixels, 2),
+                ColorType::RGBA(_) | ColorType::BGRA(_) | ColorType::Palette(_) =>
+                    Loader::premultiply_alpha(&mut pixels, 4),
+                _ => {}
+            }
+        }
+        Ok(TextureData::new(pixels, width, height))
+    }
 
+    // Sube una imagen ya decodificada con decode_texture.
+    // Gris y gris+alfa se suben como R y RG y el swizzle los devuelve como RGB(A) al shader
+    pub fn load_texture_from_data(&mut self, data: &TextureData, options: &TextureOptions) -> Texture {
+        let rgb_format = if options.is_srgb() { gl::SRGB8 } else { gl::RGB8 };
+        let rgba_format = if options.is_srgb() { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+        let (internal_format, format, swizzle) = match data.get_channels() {
+            1 => (gl::R8, gl::RED, Some(GRAY_SWIZZLE)),
+            2 => (gl::RG8, gl::RG, Some(GRAY_ALPHA_SWIZZLE)),
+            3 => (rgb_format, gl::RGB, None),
+            _ => (rgba_format, gl::RGBA, None),
+        };
+        let (width, height) = (data.get_width(), data.get_height());
+        let pixels = data.get_pixels();
 
-            let mut texture = 0;
-            gl::GenTextures(1, &mut texture);
+        let texture = Texture::new();
+        unsafe {
+            // Primero se enlaza la textura, los parámetros se aplican a la textura enlazada
+            gl::BindTexture(gl::TEXTURE_2D, texture.get_id());
 
-            // set the texture wrapping parameters
-            // set texture wrapping to GL_REPEAT (default wrapping method)
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
-            // set texture filtering parameters
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            self.set_texture_parameters(options);
+
+            if let Some(swizzle) = swizzle {
+                gl::TexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+            }
 
+            // Las filas de R, RG y RGB no tienen por qué estar alineadas a 4 bytes
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             // transfer image data
-            gl::BindTexture(gl::TEXTURE_2D, texture);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                format as GLint,
-                img.width() as GLint,
-                img.height() as GLint,
+                internal_format as GLint,
+                width as GLint,
+                height as GLint,
                 0,
                 format,
                 gl::UNSIGNED_BYTE,
-                &img.raw_pixels()[0] as *const u8 as *const c_void,
+                pixels.as_ptr() as *const c_void,
             );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
 
-            // generate all mip map images for us
-            self.textures.push(texture);
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-            Ok(texture)
+            if options.get_filter() == TextureFilter::Trilinear {
+                // generate all mip map images for us
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, 0);
         }
+        texture
     }
 
-    pub fn load_cube_map(&mut self, texture_files: Vec<&str>) -> u32 {
-        let mut tex_id = 0;
+    // KTX2 y DDS: se suben los mipmaps del fichero tal cual. Si el driver no tiene el formato
+    // comprimido, o hay que premultiplicar el alfa, se descomprime cada nivel en CPU y se sube
+    // como RGBA8
+    fn load_compressed_texture_handle(&mut self, path: &str, options: &TextureOptions)
+                                      -> Result<Texture, String> {
+        let container = CompressedTexture::load(path)?;
+        self.load_texture_from_container(&container, options)
+    }
+
+    // Sube un KTX2/DDS ya leído con CompressedTexture::load
+    pub fn load_texture_from_container(&mut self, container: &CompressedTexture,
+                                       options: &TextureOptions) -> Result<Texture, String> {
+        let format = container.get_format();
+        let srgb = options.is_srgb() || container.is_srgb();
+        let mut internal_format = format.to_gl(srgb);
+        // Los bloques comprimidos no se pueden premultiplicar sin descomprimirlos
+        let decompress = format.is_compressed()
+            && (options.is_premultiply_alpha() || !self.is_compressed_format_supported(internal_format));
+        if decompress {
+            internal_format = CompressedFormat::Rgba8.to_gl(srgb);
+        }
+
+        // Los niveles se descomprimen antes de crear la textura para no dejarla a medias si falla
+        let mut levels: Vec<Vec<u8>> = vec![];
+        for (level, data) in container.get_levels().iter().enumerate() {
+            let (width, height) = container.get_level_dimensions(level);
+            let mut pixels = if decompress {
+                block_decoder::decompress(format, data, width, height)?
+            } else {
+                data.clone()
+            };
+            if options.is_premultiply_alpha() {
+                Loader::premultiply_alpha(&mut pixels, 4);
+            }
+            levels.push(pixels);
+        }
+
+        let texture = Texture::new();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, texture.get_id());
+            self.set_texture_parameters(options);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            for (level, pixels) in levels.iter().enumerate() {
+                let (width, height) = container.get_level_dimensions(level);
+                if format.is_compressed() && !decompress {
+                    gl::CompressedTexImage2D(
+                        gl::TEXTURE_2D,
+                        level as GLint,
+                        internal_format,
+                        width as GLint,
+                        height as GLint,
+                        0,
+                        pixels.len() as GLsizei,
+                        pixels.as_ptr() as *const c_void,
+                    );
+                } else {
+                    gl::TexImage2D(
+                        gl::TEXTURE_2D,
+                        level as GLint,
+                        internal_format as GLint,
+                        width as GLint,
+                        height as GLint,
+                        0,
+                        gl::RGBA,
+                        gl::UNSIGNED_BYTE,
+                        pixels.as_ptr() as *const c_void,
+                    );
+                }
+            }
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+            // Cadena de mipmaps incompleta: se limita al último nivel que trae el fichero.
+            // Si solo trae el nivel 0 y no está comprimido se generan como en load_texture
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, (levels.len() - 1) as GLint);
+            if levels.len() == 1 && options.get_filter() == TextureFilter::Trilinear {
+                if format.is_compressed() && !decompress {
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+                } else {
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 1000);
+                    gl::GenerateMipmap(gl::TEXTURE_2D);
+                }
+            }
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        Ok(texture)
+    }
 
+    // Wrap, filtros, bias y anisotropía de la textura 2D enlazada
+    fn set_texture_parameters(&mut self, options: &TextureOptions) {
         unsafe {
-            gl::GenTextures(1, &mut tex_id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, options.get_wrap().to_gl());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, options.get_wrap().to_gl());
+
+            let (min_filter, mag_filter) = match options.get_filter() {
+                TextureFilter::Nearest => (gl::NEAREST, gl::NEAREST),
+                TextureFilter::Bilinear => (gl::LINEAR, gl::LINEAR),
+                TextureFilter::Trilinear => (gl::LINEAR_MIPMAP_LINEAR, gl::LINEAR),
+            };
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+
+            if options.get_filter() == TextureFilter::Trilinear {
+                gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_LOD_BIAS, options.get_lod_bias());
+
+                let max_anisotropy = self.get_max_anisotropy();
+                if max_anisotropy > 1.0 && options.get_anisotropy() > 1.0 {
+                    gl::TexParameterf(gl::TEXTURE_2D, TEXTURE_MAX_ANISOTROPY,
+                                      options.get_anisotropy().min(max_anisotropy));
+                }
+            }
+        }
+    }
+
+    // Formatos comprimidos que acepta el driver. Se consultan la primera vez y se guardan
+    fn is_compressed_format_supported(&mut self, internal_format: GLenum) -> bool {
+        if self.compressed_formats.is_none() {
+            let mut count: GLint = 0;
+            let mut formats: Vec<GLint> = vec![];
+            unsafe {
+                gl::GetIntegerv(gl::NUM_COMPRESSED_TEXTURE_FORMATS, &mut count);
+                if count > 0 {
+                    formats = vec![0; count as usize];
+                    gl::GetIntegerv(gl::COMPRESSED_TEXTURE_FORMATS, formats.as_mut_ptr());
+                }
+            }
+            self.compressed_formats = Some(formats);
+        }
+        self.compressed_formats.as_ref().unwrap().contains(&(internal_format as GLint))
+    }
 
+    // Multiplica el color por el alfa, el alfa es el último de los channels de cada pixel
+    fn premultiply_alpha(pixels: &mut [u8], channels: usize) {
+        for pixel in pixels.chunks_mut(channels) {
+            let (colour, alpha) = pixel.split_at_mut(channels - 1);
+            for c in colour {
+                *c = ((*c as u32 * alpha[0] as u32 + 127) / 255) as u8;
+            }
+        }
+    }
+
+    // Máximo filtrado anisótropo del driver, 0.0 si no tiene la extensión.
+    // Se consulta la primera vez y se guarda
+    fn get_max_anisotropy(&mut self) -> f32 {
+        if let Some(max_anisotropy) = self.max_anisotropy {
+            return max_anisotropy;
+        }
+        let mut max_anisotropy: f32 = 0.0;
+        unsafe {
+            let mut count: GLint = 0;
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+            for i in 0..count {
+                let name = gl::GetStringi(gl::EXTENSIONS, i as GLuint);
+                if name.is_null() {
+                    continue;
+                }
+                let extension = CStr::from_ptr(name as *const c_char).to_string_lossy();
+                if extension == "GL_EXT_texture_filter_anisotropic" ||
+                    extension == "GL_ARB_texture_filter_anisotropic" {
+                    gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+                    break;
+                }
+            }
+        }
+        self.max_anisotropy = Some(max_anisotropy);
+        max_anisotropy
+    }
+
+    pub fn load_cube_map(&mut self, texture_files: Vec<&str>) -> u32 {
+        let texture = self.load_cube_map_handle(texture_files);
+        let tex_id = texture.get_id();
+        self.textures.push(texture);
+        tex_id
+    }
+
+    pub fn load_cube_map_handle(&mut self, texture_files: Vec<&str>) -> Texture {
+        let texture = Texture::new();
+
+        unsafe {
             gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_CUBE_MAP, tex_id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, texture.get_id());
 
             for i in 0..texture_files.len() {
                 let data: TextureData =
@@ -140,14 +529,13 @@ impl Loader {
 
             gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
             gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
-            self.textures.push(tex_id);
 
             // En los comentarios del video dice que hay tarjetas de video que necesita estas 2 líneas
             gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
             gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
         }
 
-        tex_id
+        texture
     }
     // Devuelve un Vec<u8> del fichero gráfico envuelto en TextureData
     fn decode_texture_file(&self, path: &str) -> Result<TextureData, String> {
@@ -161,36 +549,42 @@ impl Loader {
         Ok(TextureData::new(data, width, height))
     }
 
-    fn create_vao(&mut self) -> u32 {
-        let mut vao_id: u32 = 0;
-        unsafe {
-            gl::GenVertexArrays(1, &mut vao_id);
-            //Añade vaoID al list VAO
-            self.vaos.push(vao_id);
+    fn create_vao(&mut self) -> Vao {
+        let vao = Vao::new();
+        //Activa el VAO
+        vao.bind();
+        //dbg!(vao.get_id());
+        vao
+    }
 
-            //Activa el VAO
-            gl::BindVertexArray(vao_id);
-        }
-        //dbg!(vao_id);
-        vao_id
+    // Borra en la GPU todo lo que ha creado el Loader: VAOs con sus VBOs, VBOs sueltos y texturas
+    // (cubemaps del skybox y quad de las GUIs incluidos)
+    pub fn cleanup(&mut self) {
+        self.vaos.clear();
+        self.vbos.clear();
+        self.textures.clear();
+        self.raw_model = None;
+    }
+
+    // Libera el VAO del modelo y sus VBOs, para descargar un nivel sin esperar a cleanup()
+    pub fn unload_model(&mut self, raw_model: &RawModel) {
+        self.vaos.retain(|vao| vao.get_id() != raw_model.get_vao_id());
+    }
+
+    pub fn unload_texture(&mut self, texture_id: u32) {
+        self.textures.retain(|texture| texture.get_id() != texture_id);
     }
-    pub fn cleanup(&mut self) {}
 
     pub fn store_data_in_attribute_list(&mut self,
+                                        vao: &mut Vao,
                                         attribute_number: GLuint,
                                         coordenate_size: i32,
                                         data: &Vec<GLfloat>) {
+        //Crea un VBO vacio
+        let vbo = Vbo::new();
         unsafe {
-            //Crea un VBO vacio
-            let mut vbo_id = 0;
-            gl::GenBuffers(1, &mut vbo_id);
-            //dbg!(vbo_id);
-
-            //Añade vboID al vbo list.
-            self.vbos.push(vbo_id);
-
             //Activa VBO. Ahora se puede almacenar datos en el.
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo_id); // enlaza buffer de vertices
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo.get_id()); // enlaza buffer de vertices
 
             // Almacena datos en el VBO.
             // Tamaño de los datos en bytes tomando el tamaño de un f32 con std::mem::size_of::<f32>(),
@@ -229,33 +623,136 @@ impl Loader {
             //Desenlaza el VBO
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
+        //El VAO es dueño del VBO, se borran juntos
+        vao.attach_vbo(vbo);
     }
 
-    pub fn unbind_vao(&mut self) {
+    // Crea un VBO con los datos y lo deja enlazado a GL_ARRAY_BUFFER
+    fn store_bytes_in_vbo(data: &[u8]) -> Vbo {
+        let vbo = Vbo::new();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo.get_id());
+            gl::BufferData(gl::ARRAY_BUFFER,
+                           data.len() as GLsizeiptr,
+                           data.as_ptr() as *const c_void,
+                           gl::STATIC_DRAW);
+        }
+        vbo
+    }
+
+    // Pone en el VAO activo el atributo leído del VBO enlazado a GL_ARRAY_BUFFER.
+    // base_offset: bytes donde empiezan los vértices dentro del VBO
+    pub fn set_attribute_pointer(attribute: &VertexAttribute, stride: usize, base_offset: usize) {
+        unsafe {
+            let offset = (base_offset + attribute.get_offset()) as *const c_void;
+            if attribute.is_integer() {
+                gl::VertexAttribIPointer(
+                    attribute.get_location(),
+                    attribute.get_components(),
+                    attribute.get_component_type().to_gl(),
+                    stride as GLsizei,
+                    offset,
+                );
+            } else {
+                gl::VertexAttribPointer(
+                    attribute.get_location(),
+                    attribute.get_components(),
+                    attribute.get_component_type().to_gl(),
+                    if attribute.is_normalized() { gl::TRUE } else { gl::FALSE },
+                    stride as GLsizei,
+                    offset,
+                );
+            }
+            if attribute.get_divisor() != 0 {
+                gl::VertexAttribDivisor(attribute.get_location(), attribute.get_divisor());
+            }
+        }
+    }
+
+    // Crea un VBO vacío de float_count floats para datos que cambian cada frame (instancias)
+    pub fn create_empty_vbo(&mut self, float_count: usize) -> u32 {
+        let vbo = Loader::create_empty_vbo_handle(float_count);
+        let vbo_id = vbo.get_id();
+        self.vbos.push(vbo);
+        vbo_id
+    }
+
+    // Como create_empty_vbo, pero el VBO es del llamador
+    pub fn create_empty_vbo_handle(float_count: usize) -> Vbo {
+        let vbo = Vbo::new();
         unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo.get_id());
+            gl::BufferData(gl::ARRAY_BUFFER,
+                           (float_count * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                           std::ptr::null(),
+                           gl::STREAM_DRAW);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        vbo
+    }
+
+    // Añade al VAO un atributo leído del VBO de instancias, avanza una vez por instancia
+    // data_size: floats del atributo, instanced_data_length: floats por instancia,
+    // offset: posición en floats del atributo dentro de los datos de una instancia
+    pub fn add_instanced_attribute(vao: u32, vbo: u32, attribute: GLuint, data_size: i32,
+                                   instanced_data_length: i32, offset: i32) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BindVertexArray(vao);
+            gl::VertexAttribPointer(
+                attribute,
+                data_size,
+                gl::FLOAT,
+                gl::FALSE,
+                instanced_data_length * mem::size_of::<GLfloat>() as GLsizei,
+                (offset as usize * mem::size_of::<GLfloat>()) as *const c_void,
+            );
+            gl::VertexAttribDivisor(attribute, 1);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindVertexArray(0);
         }
     }
 
-    //Carga el buffer de indices = lo enlaza con el VAO que vamos a renderizar.
-    fn bind_indices_vbo(&mut self, indices: &Vec<GLuint>) {
+    // Sustituye el contenido del VBO. Se deja huérfano el almacenamiento anterior (orphaning)
+    // para que el driver no espere a que la GPU termine de leerlo
+    pub fn update_vbo(vbo: u32, data: &[GLfloat]) {
         unsafe {
-            let indices_ref = &indices[0] as *const u32 as *const c_void;
+            let size = mem::size_of_val(data) as GLsizeiptr;
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, size, std::ptr::null(), gl::STREAM_DRAW);
+            gl::BufferSubData(gl::ARRAY_BUFFER, 0, size, data.as_ptr() as *const c_void);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
 
-            let indices_size = (indices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr;
-            //dbg!(indices.len());
-            //Creamos VBO vacío
-            let mut vbo_id = 0;
+    pub fn unbind_vao(&mut self) {
+        unsafe {
+            gl::BindVertexArray(0);
+        }
+    }
 
-            gl::GenBuffers(1, &mut vbo_id);
-            //Añadimos a la lista de VBO
-            self.vbos.push(vbo_id); // enlaza buffer de indices
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, vbo_id);
+    //Carga el buffer de indices = lo enlaza con el VAO que vamos a renderizar.
+    // Si todos los índices caben en 16 bits se guardan como u16. Devuelve el tipo para
+    // glDrawElements
+    fn bind_indices_vbo(&mut self, vao: &mut Vao, indices: &[GLuint]) -> GLenum {
+        let vbo = Vbo::new();
+        let short_indices: Vec<u16>;
+        let (index_type, indices_data) = if indices.iter().all(|&index| index <= u16::MAX as u32) {
+            short_indices = indices.iter().map(|&index| index as u16).collect();
+            (gl::UNSIGNED_SHORT, as_bytes(&short_indices))
+        } else {
+            (gl::UNSIGNED_INT, as_bytes(indices))
+        };
+        unsafe {
+            // enlaza buffer de indices
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, vbo.get_id());
 
             gl::BufferData(gl::ELEMENT_ARRAY_BUFFER,
-                           indices_size,
-                           indices_ref,// puntero a datos,
+                           indices_data.len() as GLsizeiptr,
+                           indices_data.as_ptr() as *const c_void,// puntero a datos,
                            gl::STATIC_DRAW);
         }
+        vao.attach_vbo(vbo);
+        index_type
     }
-}
\ No newline at end of file
+}
@@ -1,12 +1,16 @@
-use cgmath::vec3;
+use cgmath::{vec3, EuclideanSpace};
+
+use std::collections::HashMap;
 
 use crate::entities::camera::Camera;
 use crate::entities::entity::Entity;
 use crate::entities::light::Light;
+use crate::models::textured_model::TexturedModel;
 use crate::render_engine::display_manager::DisplayManager;
 use crate::render_engine::entity_renderer::EntityRenderer;
 use crate::render_engine::loader::Loader;
 use crate::render_engine::terrain_renderer::TerrainRenderer;
+use crate::shaders::instanced_shader::InstancedShader;
 use crate::shaders::static_shader::*;
 use crate::shaders::terrain_shader::TerrainShader;
 use crate::skybox::skybox_renderer::SkyboxRenderer;
@@ -24,11 +28,15 @@ const BLUE: f32 = 1.0;
 pub struct MasterRenderer {
     projection_matrix: M4CG,
     shader: StaticShader,
+    instanced_shader: InstancedShader,
+    // true: un glDrawElementsInstanced por modelo, false: un glDrawElements por entity
+    instancing: bool,
     terrain_shader: TerrainShader,
     renderer: EntityRenderer,
     terrain_renderer: TerrainRenderer,
     skybox_renderer: SkyboxRenderer,
-    entities: Vec<Vec<Entity>>,
+    // Lotes de entities agrupadas por modelo (VAO + textura)
+    entities: HashMap<TexturedModel, Vec<Entity>>,
     terrains: Vec<Terrain>,
 }
 
@@ -38,39 +46,32 @@ impl MasterRenderer {
         let projection_matrix =
             create_projection_matrix_perspective(dm.width as f32, dm.height as f32);
         let shader = StaticShader::new();
+        let instanced_shader = InstancedShader::new();
         let terrain_shader = TerrainShader::new();
 
         MasterRenderer {
             projection_matrix,
             shader,
+            instanced_shader,
+            instancing: true,
             terrain_shader,
-            renderer: EntityRenderer::new(shader, &projection_matrix),
+            renderer: EntityRenderer::new(shader, instanced_shader, &projection_matrix),
             terrain_renderer: TerrainRenderer::new(terrain_shader, projection_matrix),
             skybox_renderer: SkyboxRenderer::new(loader, projection_matrix),
-            entities: MasterRenderer::inicializa_entities(),
+            entities: HashMap::new(),
             terrains: vec![],
             //nombre_vector,
         }
     }
 
-    // Una variable por cada tipo de objeto, se debe modificar si modificamos el numero
-    fn inicializa_entities() -> Vec<Vec<Entity>> {
-        let aa: Vec<Entity> = vec![];
-        let bb: Vec<Entity> = vec![];
-        let cc: Vec<Entity> = vec![];
-        let dd: Vec<Entity> = vec![];
-        let ee: Vec<Entity> = vec![];
-        let ff: Vec<Entity> = vec![];
-        let gg: Vec<Entity> = vec![];
-        let hh: Vec<Entity> = vec![];
-        let ii: Vec<Entity> = vec![];
-        vec![aa, bb, cc, dd, ee, ff, gg, hh, ii]
-    }
-
     pub fn get_projection_matrix(&self) -> M4CG {
         self.projection_matrix
     }
 
+    pub fn set_instancing(&mut self, instancing: bool) {
+        self.instancing = instancing;
+    }
+
     pub fn enable_culling() {
         unsafe {
             gl::Enable(gl::CULL_FACE); //Caras posteriores no se ven
@@ -86,13 +87,27 @@ impl MasterRenderer {
     // renderiza antes de presentarlo en pantalla
     pub fn render(&mut self, lights: &Vec<Light>, camera: &mut Camera, dm: &DisplayManager) {
         self.prepare();
-
-        self.shader.start();
-        self.shader.load_sky_colour(RED, GREEN, BLUE);
-        self.shader.load_lights(lights);
-        self.shader.load_view_matrix(camera);
-        self.renderer.render(&self.entities); //Renderizamos entities
-        self.shader.stop();
+        // Para elegir el nivel de detalle de cada entity
+        let camera_position = camera.get_position().to_vec();
+        let projection_scale = self.projection_matrix[1][1];
+
+        if self.instancing {
+            self.instanced_shader.start();
+            self.instanced_shader.load_sky_colour(RED, GREEN, BLUE);
+            self.instanced_shader.load_lights(lights);
+            self.instanced_shader.load_view_matrix(camera);
+            //Renderizamos entities
+            self.renderer.render_instanced(&self.entities, camera_position, projection_scale);
+            self.instanced_shader.stop();
+        } else {
+            self.shader.start();
+            self.shader.load_sky_colour(RED, GREEN, BLUE);
+            self.shader.load_lights(lights);
+            self.shader.load_view_matrix(camera);
+            //Renderizamos entities
+            self.renderer.render(&self.entities, camera_position, projection_scale);
+            self.shader.stop();
+        }
 
 
         self.terrain_shader.start();
@@ -105,9 +120,7 @@ impl MasterRenderer {
         self.skybox_renderer.render(camera, RED, GREEN, BLUE, dm);
 
         self.terrains.clear();
-
-
-        self.entities = MasterRenderer::inicializa_entities(); // En vez de entities.clear()
+        self.entities.clear();
     }
 
     pub fn copia_entity(entity: &Entity) -> Entity {
@@ -122,31 +135,16 @@ impl MasterRenderer {
     }
 
 
-    // Un numero por cada tipo de objeto, se debe modificar si modificamos el numero de objetos
-    // máximo hay 9 objetos
+    // Añade la entity al lote de su modelo, si el modelo es nuevo se crea el lote
     pub fn process_entity(&mut self, entity: &Entity) {
-        let id = entity.id;
-        //dbg!(id,tam);
-        match id {
-            0 => self.entities[0].push(entity.clone()),
-
-            1 => self.entities[1].push(entity.clone()),
-            2 => self.entities[2].push(entity.clone()),
-            3 => self.entities[3].push(entity.clone()),
-            4 => self.entities[4].push(entity.clone()),
-            5 => self.entities[5].push(entity.clone()),
-
-            // Lamparas
-            6 => self.entities[6].push(entity.clone()),
-            7 => self.entities[7].push(entity.clone()),
-            8 => self.entities[8].push(entity.clone()),
-
-            _ => println!("Falta id entity en master_renderer"),
-        }
+        let batch = self.entities.entry(entity.get_model()).or_default();
+        batch.push(*entity);
     }
 
     pub fn cleanup(&mut self) {
+        self.renderer.cleanup();
         self.shader.cleanup();
+        self.instanced_shader.cleanup();
         self.terrain_shader.cleanup();
     }
 
@@ -2,5 +2,13 @@ pub mod display_manager;
 pub mod loader;
 pub mod entity_renderer;
 pub mod objloader;
+#[allow(dead_code)] // de lo que lee, el juego solo usa las mallas, materiales y nodos
+pub mod gltf_loader;
 pub mod master_renderer;
-pub mod terrain_renderer;
\ No newline at end of file
+pub mod terrain_renderer;
+pub mod gl_resources;
+pub mod asset_manager;
+pub mod async_loader;
+pub mod vertex_layout;
+#[allow(dead_code)] // todavía no hay nada en el juego que cambie su geometría
+pub mod dynamic_model;
@@ -34,7 +34,7 @@ impl TerrainRenderer {
                         .get_model()
                         .get_vertex_count(),// número de índices a
                     // renderizar
-                    gl::UNSIGNED_INT,
+                    terrain.get_model().get_index_type(),
                     ptr::null());
             }
             self.unbind_textured_model();
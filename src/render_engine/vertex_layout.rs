@@ -0,0 +1,180 @@
+use gl;
+use gl::types::*;
+
+use std::mem;
+use std::slice;
+
+#[allow(dead_code)] // los tipos de GL que todavía no usa ningún formato de vértices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentType {
+    Float,
+    HalfFloat,
+    Byte,
+    UnsignedByte,
+    Short,
+    UnsignedShort,
+    Int,
+    UnsignedInt,
+}
+
+impl ComponentType {
+    // Bytes de una componente
+    pub fn get_size(&self) -> usize {
+        match self {
+            ComponentType::Byte | ComponentType::UnsignedByte => 1,
+            ComponentType::HalfFloat | ComponentType::Short | ComponentType::UnsignedShort => 2,
+            ComponentType::Float | ComponentType::Int | ComponentType::UnsignedInt => 4,
+        }
+    }
+
+    pub fn to_gl(self) -> GLenum {
+        match self {
+            ComponentType::Float => gl::FLOAT,
+            ComponentType::HalfFloat => gl::HALF_FLOAT,
+            ComponentType::Byte => gl::BYTE,
+            ComponentType::UnsignedByte => gl::UNSIGNED_BYTE,
+            ComponentType::Short => gl::SHORT,
+            ComponentType::UnsignedShort => gl::UNSIGNED_SHORT,
+            ComponentType::Int => gl::INT,
+            ComponentType::UnsignedInt => gl::UNSIGNED_INT,
+        }
+    }
+}
+
+// Un atributo del vertex shader ("layout (location = N)")
+#[derive(Debug, Clone, Copy)]
+pub struct VertexAttribute {
+    location: GLuint,
+    components: i32,
+    component_type: ComponentType,
+    normalized: bool, // enteros a float en [0, 1] o [-1, 1] (colores, pesos)
+    integer: bool,    // llega al shader como ivec/uvec (índices de huesos)
+    divisor: GLuint,  // 0 = por vértice, 1 = por instancia
+    offset: usize,    // bytes desde el principio del vértice, lo calcula VertexLayout
+}
+
+impl VertexAttribute {
+    pub fn new(location: GLuint, components: i32, component_type: ComponentType) -> VertexAttribute {
+        VertexAttribute {
+            location,
+            components,
+            component_type,
+            normalized: false,
+            integer: false,
+            divisor: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn get_location(&self) -> GLuint {
+        self.location
+    }
+
+    pub fn get_components(&self) -> i32 {
+        self.components
+    }
+
+    pub fn get_component_type(&self) -> ComponentType {
+        self.component_type
+    }
+
+    pub fn is_normalized(&self) -> bool {
+        self.normalized
+    }
+
+    pub fn _set_normalized(&mut self, normalized: bool) {
+        self.normalized = normalized;
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.integer
+    }
+
+    pub fn set_integer(&mut self, integer: bool) {
+        self.integer = integer;
+    }
+
+    pub fn get_divisor(&self) -> GLuint {
+        self.divisor
+    }
+
+    pub fn _set_divisor(&mut self, divisor: GLuint) {
+        self.divisor = divisor;
+    }
+
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    // Bytes del atributo en un vértice
+    pub fn get_size(&self) -> usize {
+        self.components as usize * self.component_type.get_size()
+    }
+}
+
+// Cómo están los vértices en los VBOs. Intercalado: un solo VBO con todos los atributos de cada
+// vértice seguidos. Separado: un VBO por atributo, con los datos juntos
+#[derive(Debug, Clone)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+    interleaved: bool,
+    stride: usize,
+}
+
+impl VertexLayout {
+    pub fn new(interleaved: bool) -> VertexLayout {
+        VertexLayout {
+            attributes: vec![],
+            interleaved,
+            stride: 0,
+        }
+    }
+
+    // Posición, uv y normal en las locations 0, 1 y 2, lo que usaba load_to_vao
+    pub fn position_texture_normal(interleaved: bool) -> VertexLayout {
+        let mut layout = VertexLayout::new(interleaved);
+        layout.add_attribute(VertexAttribute::new(0, 3, ComponentType::Float));
+        layout.add_attribute(VertexAttribute::new(1, 2, ComponentType::Float));
+        layout.add_attribute(VertexAttribute::new(2, 3, ComponentType::Float));
+        layout
+    }
+
+    // En un layout intercalado el atributo va detrás del anterior
+    pub fn add_attribute(&mut self, mut attribute: VertexAttribute) {
+        if self.interleaved {
+            attribute.offset = self.stride;
+            self.stride += attribute.get_size();
+        }
+        self.attributes.push(attribute);
+    }
+
+    pub fn get_attributes(&self) -> &Vec<VertexAttribute> {
+        &self.attributes
+    }
+
+    pub fn is_interleaved(&self) -> bool {
+        self.interleaved
+    }
+
+    // Bytes entre vértices consecutivos del VBO del atributo
+    pub fn get_stride(&self, attribute: &VertexAttribute) -> usize {
+        if self.interleaved { self.stride } else { attribute.get_size() }
+    }
+
+    // Para relleno al final de cada vértice (alinear a 4 bytes, por ejemplo)
+    pub fn _set_stride(&mut self, stride: usize) {
+        self.stride = stride;
+    }
+
+    // Número de VBOs que espera Loader::load_to_vao_with_layout
+    pub fn get_buffer_count(&self) -> usize {
+        if self.interleaved { 1 } else { self.attributes.len() }
+    }
+}
+
+// Vista en bytes de un slice de f32, u8, u16... para pasarlo como datos de vértices
+pub fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe {
+        slice::from_raw_parts(data.as_ptr() as *const u8, mem::size_of_val(data))
+    }
+}
@@ -0,0 +1,127 @@
+use cgmath::vec3;
+
+use crate::entities::camera::Camera;
+use crate::entities::light::Light;
+use crate::shaders::shader_program::ShaderProgram;
+use crate::shaders::static_shader::MAX_LIGHTS;
+use crate::toolbox::maths;
+
+type V3CG = cgmath::Vector3<f32>;
+type M4CG = cgmath::Matrix4<f32>;
+
+// La matriz de transformación y el offset del atlas llegan como atributos por instancia
+// (locations 3-6 y 7), no como uniforms
+const VERTEX_FILE: &str = "res/shaders/instancedShader.vert";
+const FRAGMENT_FILE: &str = "res/shaders/instancedShader.frag";
+
+#[derive(Debug, Clone, Copy)]
+pub struct InstancedShader {
+    program_id: u32,
+    vertex_shader_id: u32,
+    fragment_shader_id: u32,
+    location_projection_matrix: i32,
+    location_view_matrix: i32,
+    location_light_position: [i32; MAX_LIGHTS],
+    location_light_color: [i32; MAX_LIGHTS],
+    location_light_attenuation: [i32; MAX_LIGHTS],
+    location_shine_damper: i32,
+    location_reflectivity: i32,
+    location_use_fake_lighting: i32,
+    location_sky_colour: i32,
+    location_number_of_rows: i32,
+    location_model_colour: i32,
+}
+
+impl InstancedShader {
+    pub fn new() -> InstancedShader {
+        let p = ShaderProgram::new(VERTEX_FILE, FRAGMENT_FILE);
+
+        InstancedShader {
+            program_id: p.program_id,
+            vertex_shader_id: p.vertex_shader_id,
+            fragment_shader_id: p.fragment_shader_id,
+            location_projection_matrix: p.location_projection_matrix,
+            location_view_matrix: p.location_view_matrix,
+            location_light_position: p.location_light_position,
+            location_light_color: p.location_light_color,
+            location_light_attenuation: p.location_light_attenuation,
+            location_shine_damper: p.location_shine_damper,
+            location_reflectivity: p.location_reflectivity,
+            location_use_fake_lighting: p.location_use_fake_lighting,
+            location_sky_colour: p.location_sky_colour,
+            location_number_of_rows: p.location_number_of_rows,
+            location_model_colour: p.location_model_colour,
+        }
+    }
+
+    // Para textura atlas
+    pub fn load_number_of_rows(&self, number_of_rows: i32) {
+        ShaderProgram::load_float(self.location_number_of_rows, number_of_rows as f32);
+    }
+
+    pub fn start(&self) {
+        unsafe {
+            gl::UseProgram(self.program_id); //Hace funcionar el programa shader
+        }
+    }
+
+    pub fn stop(&self) {
+        unsafe {
+            gl::UseProgram(0); //Para el programa shader
+        }
+    }
+
+    pub fn cleanup(&self) {
+        //Desconectamos y borramos shaders
+        self.stop();
+        unsafe {
+            gl::DetachShader(self.program_id, self.vertex_shader_id);
+            gl::DetachShader(self.program_id, self.fragment_shader_id);
+            gl::DeleteShader(self.vertex_shader_id);
+            gl::DeleteShader(self.fragment_shader_id);
+            gl::DeleteProgram(self.program_id);
+        }
+    }
+
+    pub fn load_sky_colour(&self, r: f32, g: f32, b: f32) {
+        ShaderProgram::load_vector(self.location_sky_colour, vec3(r, g, b));
+    }
+
+    // Color que multiplica a la textura (Kd del MTL, base colour del glTF)
+    pub fn load_model_colour(&self, colour: V3CG) {
+        ShaderProgram::load_vector(self.location_model_colour, colour);
+    }
+
+    pub fn load_fake_lighting_variable(&self, use_fake: bool) {
+        ShaderProgram::load_boolean(self.location_use_fake_lighting, use_fake);
+    }
+
+    pub fn load_shine_variables(&self, damper: f32, reflectivity: f32) {
+        ShaderProgram::load_float(self.location_shine_damper, damper);
+        ShaderProgram::load_float(self.location_reflectivity, reflectivity);
+    }
+
+    pub fn load_lights(&self, lights: &[Light]) {
+        for i in 0..MAX_LIGHTS {
+            if i < lights.len() {
+                ShaderProgram::load_vector(self.location_light_position[i], lights[i].get_position());
+                ShaderProgram::load_vector(self.location_light_color[i], lights[i].get_color());
+                ShaderProgram::load_vector(
+                    self.location_light_attenuation[i], lights[i].get_attenuation());
+            } else {
+                ShaderProgram::load_vector(self.location_light_position[i], vec3(0.0, 0.0, 0.0));
+                ShaderProgram::load_vector(self.location_light_color[i], vec3(0.0, 0.0, 0.0));
+                ShaderProgram::load_vector(self.location_light_attenuation[i], vec3(1.0, 0.0, 0.0));
+            }
+        }
+    }
+
+    pub fn load_view_matrix(&self, camera: &mut Camera) {
+        let view_matrix = maths::create_view_matrix(camera);
+        ShaderProgram::load_matrix(self.location_view_matrix, &view_matrix);
+    }
+
+    pub fn load_projection_matrix(&self, projection: &M4CG) {
+        ShaderProgram::load_matrix(self.location_projection_matrix, projection);
+    }
+}
@@ -1,4 +1,5 @@
 #[macro_use]
 pub mod shader_program;
 pub mod static_shader;
-pub mod terrain_shader;
\ No newline at end of file
+pub mod terrain_shader;
+pub mod instanced_shader;
\ No newline at end of file
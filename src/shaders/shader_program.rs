@@ -44,6 +44,7 @@ pub struct ShaderProgram {
     pub location_blend_map: i32,
     pub location_number_of_rows: i32,
     pub location_offset: i32,
+    pub location_model_colour: i32,
 
     pub location_light_position: [i32; MAX_LIGHTS],
     pub location_light_color: [i32; MAX_LIGHTS],
@@ -103,6 +104,7 @@ impl ShaderProgram {
                 location_blend_map: 0,
                 location_number_of_rows: 0,
                 location_offset: 0,
+                location_model_colour: 0,
 
                 location_light_position: [0; MAX_LIGHTS],
                 location_light_color: [0; MAX_LIGHTS],
@@ -208,6 +210,9 @@ impl ShaderProgram {
             self.location_offset =
                 self.get_uniform_location(c_str!("offset"));
 
+            self.location_model_colour =
+                self.get_uniform_location(c_str!("modelColour"));
+
             // ------------- carga a lo bestia ----------------------
             self.location_light_position[0] =
                 self.get_uniform_location(c_str!("lightPosition[0]"));
@@ -7,6 +7,7 @@ use crate::entities::light::Light;
 use crate::shaders::shader_program::ShaderProgram;
 use crate::toolbox::maths;
 
+type V3CG = cgmath::Vector3<f32>;
 type M4CG = cgmath::Matrix4<f32>;
 
 pub const MAX_LIGHTS: usize = 4;
@@ -36,6 +37,7 @@ pub struct StaticShader {
     location_sky_colour: i32,
     location_number_of_rows: i32,
     location_offset: i32,
+    location_model_colour: i32,
 }
 
 impl StaticShader {
@@ -66,6 +68,7 @@ impl StaticShader {
             location_sky_colour: p.location_sky_colour,
             location_number_of_rows: p.location_number_of_rows,
             location_offset: p.location_offset,
+            location_model_colour: p.location_model_colour,
 
             location_light_position,
             location_light_color,
@@ -125,6 +128,11 @@ impl StaticShader {
         ShaderProgram::load_vector(self.location_sky_colour, vec3(r, g, b));
     }
 
+    // Color que multiplica a la textura (Kd del MTL, base colour del glTF)
+    pub fn load_model_colour(&self, colour: V3CG) {
+        ShaderProgram::load_vector(self.location_model_colour, colour);
+    }
+
     pub fn load_fake_lighting_variable(&self, use_fake: bool) {
         ShaderProgram::load_boolean(self.location_use_fake_lighting, use_fake);
     }
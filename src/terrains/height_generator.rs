@@ -0,0 +1,229 @@
+use crate::terrains::terrain_descriptor::{TerrainDescriptor, DEFAULT_VERTEX_COUNT};
+
+// Alturas procedurales: ruido de Perlin en varias octavas (fBm). Las coordenadas son del mundo,
+// así dos tiles vecinos calculan la misma altura en su borde común y el terreno no tiene
+// costuras, sea cual sea la resolución de cada uno
+#[derive(Debug, Clone, Copy)]
+pub struct HeightGenerator {
+    seed: u32,
+    octaves: u32,
+    roughness: f32, // cuánto pesa cada octava respecto a la anterior
+    amplitude: f32, // altura máxima aproximada, el resultado va de -amplitude a amplitude
+    scale: f32,     // unidades del mundo que ocupa un periodo de la primera octava
+    x_offset: f32,  // desplazamiento, para sacar otra zona con la misma semilla
+    z_offset: f32,
+}
+
+impl HeightGenerator {
+    pub fn new(seed: u32) -> HeightGenerator {
+        HeightGenerator {
+            seed,
+            octaves: 4,
+            roughness: 0.4,
+            amplitude: 40.0,
+            scale: 800.0,
+            x_offset: 0.0,
+            z_offset: 0.0,
+        }
+    }
+
+    pub fn _get_seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn _set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+    }
+
+    pub fn _get_octaves(&self) -> u32 {
+        self.octaves
+    }
+
+    pub fn _set_octaves(&mut self, octaves: u32) {
+        self.octaves = octaves;
+    }
+
+    pub fn _get_roughness(&self) -> f32 {
+        self.roughness
+    }
+
+    pub fn _set_roughness(&mut self, roughness: f32) {
+        self.roughness = roughness;
+    }
+
+    pub fn _get_amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    pub fn _set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude;
+    }
+
+    pub fn _get_scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn _set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    pub fn _get_offset(&self) -> (f32, f32) {
+        (self.x_offset, self.z_offset)
+    }
+
+    pub fn _set_offset(&mut self, x_offset: f32, z_offset: f32) {
+        self.x_offset = x_offset;
+        self.z_offset = z_offset;
+    }
+
+    // Altura en el punto (x, z) del mundo. Cada octava dobla la frecuencia y
+    // multiplica la amplitud por roughness; el total se normaliza para no pasar de amplitude
+    pub fn generate_height(&self, x: f32, z: f32) -> f32 {
+        let x = (x + self.x_offset) / self.scale;
+        let z = (z + self.z_offset) / self.scale;
+        let mut total = 0.0;
+        let mut weights = 0.0;
+        let mut frequency = 1.0;
+        let mut weight = 1.0;
+        for octave in 0..self.octaves {
+            let seed = self.seed.wrapping_add(octave.wrapping_mul(0x9e37_79b9));
+            total += perlin(x * frequency, z * frequency, seed) * weight;
+            weights += weight;
+            frequency *= 2.0;
+            weight *= self.roughness;
+        }
+        if weights > 0.0 { total / weights * self.amplitude } else { 0.0 }
+    }
+
+    // Alturas de un tile con las dimensiones del descriptor, indexadas [x][z] como
+    // Terrain::heights. Sin resolución en el descriptor usa DEFAULT_VERTEX_COUNT por lado. Solo
+    // se aplica el desplazamiento de altura del descriptor, el rango lo da amplitude
+    pub fn generate_tile(&self, grid_x: i32, grid_z: i32, descriptor: &TerrainDescriptor)
+                         -> Vec<Vec<f32>> {
+        let (vertex_count_x, vertex_count_z) = descriptor.get_resolution()
+            .unwrap_or((DEFAULT_VERTEX_COUNT, DEFAULT_VERTEX_COUNT));
+        // (grid + i / (n - 1)) da exactamente grid + 1 en el último vértice, igual que el
+        // primero del tile siguiente
+        let world = |grid: i32, i: usize, count: usize, size: f32| {
+            (grid as f32 + i as f32 / (count - 1) as f32) * size
+        };
+        (0..vertex_count_x).map(|x| {
+            let world_x = world(grid_x, x, vertex_count_x, descriptor.get_width());
+            (0..vertex_count_z).map(|z| {
+                let world_z = world(grid_z, z, vertex_count_z, descriptor.get_depth());
+                self.generate_height(world_x, world_z) + descriptor.get_height_offset()
+            }).collect()
+        }).collect()
+    }
+}
+
+// Ruido de Perlin 2D entre -1 y 1 aproximadamente. El gradiente de cada esquina sale de un hash
+// de sus coordenadas, sin tabla de permutaciones, así no se repite a ninguna distancia
+fn perlin(x: f32, z: f32, seed: u32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (fx, fz) = (x - x0, z - z0);
+    let (ix, iz) = (x0 as i32, z0 as i32);
+    let corner = |dx: i32, dz: i32| gradient(hash(ix + dx, iz + dz, seed), fx - dx as f32,
+                                              fz - dz as f32);
+    let (u, v) = (fade(fx), fade(fz));
+    let bottom = lerp(corner(0, 0), corner(1, 0), u);
+    let top = lerp(corner(0, 1), corner(1, 1), u);
+    // El máximo teórico del Perlin 2D es sqrt(1/2)
+    lerp(bottom, top, v) * std::f32::consts::SQRT_2
+}
+
+// Producto escalar con uno de 8 gradientes unitarios
+fn gradient(hash: u32, x: f32, z: f32) -> f32 {
+    const DIAGONAL: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    match hash & 7 {
+        0 => x,
+        1 => -x,
+        2 => z,
+        3 => -z,
+        4 => (x + z) * DIAGONAL,
+        5 => (-x + z) * DIAGONAL,
+        6 => (x - z) * DIAGONAL,
+        _ => (-x - z) * DIAGONAL,
+    }
+}
+
+fn hash(x: i32, z: i32, seed: u32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (z as u32).wrapping_mul(0xd816_3841)
+        ^ seed.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^ (h >> 15)
+}
+
+// 6t⁵ - 15t⁴ + 10t³, derivadas primera y segunda nulas en 0 y 1
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptor() -> TerrainDescriptor {
+        let mut descriptor = TerrainDescriptor::new();
+        descriptor.set_resolution(97, 130);
+        descriptor._set_size(800.0, 500.0);
+        descriptor
+    }
+
+    #[test]
+    fn neighbouring_tiles_share_their_edges() {
+        let generator = HeightGenerator::new(2019);
+        let descriptor = descriptor();
+        for &(grid_x, grid_z) in &[(0, 0), (-1, 3), (-7, -2)] {
+            let tile = generator.generate_tile(grid_x, grid_z, &descriptor);
+            let right = generator.generate_tile(grid_x + 1, grid_z, &descriptor);
+            let front = generator.generate_tile(grid_x, grid_z + 1, &descriptor);
+            assert_eq!((tile.len(), tile[0].len()), (97, 130));
+            assert_eq!(tile[96], right[0]);
+            for x in 0..97 {
+                assert_eq!(tile[x][129], front[x][0]);
+            }
+        }
+    }
+
+    #[test]
+    fn seams_do_not_depend_on_the_resolution() {
+        let generator = HeightGenerator::new(2019);
+        let mut coarse = descriptor();
+        coarse.set_resolution(5, 3);
+        let mut fine = descriptor();
+        fine.set_resolution(97, 129);
+        let tile = generator.generate_tile(0, 0, &fine);
+        let right = generator.generate_tile(1, 0, &coarse);
+        // Los vértices del tile pequeño caen sobre vértices del grande (129 = 2 * 64 + 1)
+        assert_eq!(right[0][0], tile[96][0]);
+        assert_eq!(right[0][1], tile[96][64]);
+        assert_eq!(right[0][2], tile[96][128]);
+    }
+
+    #[test]
+    fn same_seed_same_terrain() {
+        let descriptor = descriptor();
+        let tile = HeightGenerator::new(2019).generate_tile(2, -1, &descriptor);
+        assert_eq!(tile, HeightGenerator::new(2019).generate_tile(2, -1, &descriptor));
+        assert_ne!(tile, HeightGenerator::new(2020).generate_tile(2, -1, &descriptor));
+        let amplitude = HeightGenerator::new(2019)._get_amplitude();
+        assert!(tile.iter().flatten().all(|height| height.abs() <= amplitude));
+    }
+
+    #[test]
+    fn height_offset_is_added() {
+        let generator = HeightGenerator::new(7);
+        let mut raised = descriptor();
+        raised._set_height_offset(10.0);
+        let tile = generator.generate_tile(0, 0, &descriptor());
+        let raised_tile = generator.generate_tile(0, 0, &raised);
+        assert_eq!(raised_tile[3][4], tile[3][4] + 10.0);
+    }
+}
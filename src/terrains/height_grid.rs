@@ -0,0 +1,128 @@
+use cgmath::{InnerSpace, vec3, Vector3};
+
+use crate::terrains::terrain_descriptor::NormalFilter;
+
+const UP: Vector3<f32> = Vector3 { x: 0.0, y: 1.0, z: 0.0 };
+
+// Normal en el vértice (x, z) de una rejilla de alturas [x][z] cuyos vértices están separados
+// square_size en x y en z. Las alturas que caen fuera de la rejilla se piden a outside con su
+// índice (puede ser negativo o pasarse del último); si devuelve None se prolonga la pendiente
+// del borde
+pub fn calculate_normal(heights: &[Vec<f32>], x: usize, z: usize, square_size: (f32, f32),
+                        filter: NormalFilter, outside: &dyn Fn(isize, isize) -> Option<f32>)
+                        -> Vector3<f32> {
+    let count_z = heights.first().map_or(0, |column| column.len());
+    if x >= heights.len() || z >= count_z {
+        return UP;
+    }
+    let (square_x, square_z) = square_size;
+    let (last_x, last_z) = (heights.len() as isize - 1, count_z as isize - 1);
+    let inside = |i: isize, k: isize| {
+        heights[i.clamp(0, last_x) as usize][k.clamp(0, last_z) as usize]
+    };
+    // Altura del vértice (x + dx, z + dz)
+    let height = |dx: isize, dz: isize| {
+        let (i, k) = (x as isize + dx, z as isize + dz);
+        let (edge_i, edge_k) = (i.clamp(0, last_x), k.clamp(0, last_z));
+        if i == edge_i && k == edge_k {
+            return inside(i, k);
+        }
+        outside(i, k).unwrap_or_else(|| {
+            2.0 * inside(edge_i, edge_k) - inside(2 * edge_i - i, 2 * edge_k - k)
+        })
+    };
+
+    // Derivadas de la altura en x y en z
+    let (slope_x, slope_z) = match filter {
+        NormalFilter::CentralDifference => {
+            ((height(1, 0) - height(-1, 0)) / (2.0 * square_x),
+             (height(0, 1) - height(0, -1)) / (2.0 * square_z))
+        }
+        NormalFilter::Sobel => {
+            let gx = height(1, -1) + 2.0 * height(1, 0) + height(1, 1) -
+                height(-1, -1) - 2.0 * height(-1, 0) - height(-1, 1);
+            let gz = height(-1, 1) + 2.0 * height(0, 1) + height(1, 1) -
+                height(-1, -1) - 2.0 * height(0, -1) - height(1, -1);
+            (gx / (8.0 * square_x), gz / (8.0 * square_z))
+        }
+    };
+    vec3(-slope_x, 1.0, -slope_z).normalize()
+}
+
+// Pendiente en grados entre la normal y la vertical: 0 en llano, 90 en una pared
+pub fn slope_degrees(normal: Vector3<f32>) -> f32 {
+    normal.y.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: (f32, f32) = (2.0, 3.0);
+
+    // Plano inclinado y = a * x + b * z, con x y z en unidades del mundo
+    fn plane(a: f32, b: f32, count_x: usize, count_z: usize) -> Vec<Vec<f32>> {
+        (0..count_x).map(|i| {
+            (0..count_z).map(|k| a * i as f32 * SQUARE.0 + b * k as f32 * SQUARE.1).collect()
+        }).collect()
+    }
+
+    fn assert_normal(normal: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((normal - expected).magnitude() < 1e-5, "{:?} != {:?}", normal, expected);
+    }
+
+    #[test]
+    fn inclined_plane_gives_its_normal_everywhere() {
+        let (a, b) = (0.5, -0.25);
+        let heights = plane(a, b, 5, 4);
+        let expected = vec3(-a, 1.0, -b).normalize();
+        // Sin vecinos se prolonga la pendiente, que en un plano es exacta también en las esquinas
+        let none = |_: isize, _: isize| None;
+        for &filter in [NormalFilter::CentralDifference, NormalFilter::Sobel].iter() {
+            for &(x, z) in [(2, 1), (1, 2), (0, 0), (4, 3), (0, 2), (3, 0), (4, 1)].iter() {
+                assert_normal(calculate_normal(&heights, x, z, SQUARE, filter, &none), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn border_uses_the_neighbour_heights() {
+        let heights = plane(0.5, -0.25, 5, 4);
+        // El vecino sigue otro plano: en el borde la normal mezcla los dos
+        let neighbour = |i: isize, k: isize| {
+            Some(i as f32 * SQUARE.0 - 0.25 * k as f32 * SQUARE.1)
+        };
+        let normal = calculate_normal(&heights, 4, 1, SQUARE, NormalFilter::CentralDifference,
+                                      &neighbour);
+        let slope_x = (5.0 * SQUARE.0 - 0.5 * 3.0 * SQUARE.0) / (2.0 * SQUARE.0);
+        assert_normal(normal, vec3(-slope_x, 1.0, 0.25).normalize());
+        // En el interior no se pregunta al vecino
+        let interior = calculate_normal(&heights, 2, 1, SQUARE, NormalFilter::Sobel, &neighbour);
+        assert_normal(interior, vec3(-0.5, 1.0, 0.25).normalize());
+    }
+
+    #[test]
+    fn outside_the_grid_is_up() {
+        let heights = plane(0.5, 0.5, 3, 3);
+        let none = |_: isize, _: isize| None;
+        assert_eq!(calculate_normal(&heights, 3, 0, SQUARE, NormalFilter::Sobel, &none), UP);
+        assert_eq!(calculate_normal(&[], 0, 0, SQUARE, NormalFilter::Sobel, &none), UP);
+    }
+
+    #[test]
+    fn slope_is_measured_from_the_vertical() {
+        let none = |_: isize, _: isize| None;
+        let flat = calculate_normal(&plane(0.0, 0.0, 3, 3), 1, 1, SQUARE,
+                                    NormalFilter::CentralDifference, &none);
+        assert!(slope_degrees(flat).abs() < 1e-3);
+        // Sube 1 por cada 1 en z: 45 grados
+        let ramp = calculate_normal(&plane(0.0, 1.0, 3, 3), 1, 1, SQUARE, NormalFilter::Sobel,
+                                    &none);
+        assert!((slope_degrees(ramp) - 45.0).abs() < 1e-3);
+        let steep = (60.0f32).to_radians().tan();
+        let cliff = calculate_normal(&plane(steep, 0.0, 3, 3), 0, 2, SQUARE,
+                                     NormalFilter::CentralDifference, &none);
+        assert!((slope_degrees(cliff) - 60.0).abs() < 1e-3);
+        assert!((slope_degrees(vec3(1.0, 0.0, 0.0)) - 90.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,501 @@
+use image::GenericImageView;
+use png::{BitDepth, ColorType, HasParameters, Transformations};
+
+use std::fs;
+use std::io::Cursor;
+
+// Valores distintos de un píxel RGB del heightmap (r, g y b forman un entero de 24 bits)
+const MAX_PIXEL_COLOUR: f32 = 256.0 * 256.0 * 256.0;
+const MAX_GRAY_8: f32 = 255.0;
+const MAX_GRAY_16: f32 = 65535.0;
+
+const PNG_MAGIC: &[u8] = &[0x89, b'P', b'N', b'G'];
+const EXR_MAGIC: &[u8] = &[0x76, 0x2F, 0x31, 0x01];
+const TIFF_LITTLE_ENDIAN: &[u8] = b"II*\0";
+const TIFF_BIG_ENDIAN: &[u8] = b"MM\0*";
+
+// Tags de TIFF que hacen falta para una imagen de un canal sin comprimir
+const TIFF_IMAGE_WIDTH: u16 = 256;
+const TIFF_IMAGE_LENGTH: u16 = 257;
+const TIFF_BITS_PER_SAMPLE: u16 = 258;
+const TIFF_COMPRESSION: u16 = 259;
+const TIFF_STRIP_OFFSETS: u16 = 273;
+const TIFF_SAMPLES_PER_PIXEL: u16 = 277;
+const TIFF_STRIP_BYTE_COUNTS: u16 = 279;
+const TIFF_TILE_OFFSETS: u16 = 324;
+const TIFF_SAMPLE_FORMAT: u16 = 339;
+const TIFF_SAMPLE_FORMAT_UINT: u32 = 1;
+const TIFF_SAMPLE_FORMAT_FLOAT: u32 = 3;
+
+// Codificación de las alturas en el fichero
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeightmapFormat {
+    Rgb24,   // PNG RGB o RGBA de 8 bits, r, g y b forman un entero de 24 bits (el alfa se ignora)
+    Gray8,   // PNG o TIFF en escala de grises de 8 bits
+    Gray16,  // PNG o TIFF de 16 bits, y RAW/R16
+    Float32, // EXR, TIFF float o R32
+}
+
+// Heightmap decodificado: un valor por píxel, fila a fila. Los formatos enteros se normalizan
+// entre 0 y 1; los float se dejan tal cual. Si vienen normalizados se escalan con el rango de
+// alturas del TerrainDescriptor como los enteros; si vienen en metros hay que activar
+// TerrainDescriptor::_set_float_heights_in_metres
+#[derive(Debug, Clone)]
+pub struct Heightmap {
+    width: usize,
+    height: usize,
+    format: HeightmapFormat,
+    values: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn new(width: usize, height: usize, format: HeightmapFormat, values: Vec<f32>)
+               -> Result<Heightmap, String> {
+        if values.len() != width * height {
+            return Err(format!("heightmap de {}x{} con {} valores", width, height, values.len()));
+        }
+        Ok(Heightmap { width, height, format, values })
+    }
+
+    // Detecta el formato por la cabecera del fichero o, para los RAW sin cabecera, por la
+    // extensión
+    pub fn load(path: &str) -> Result<Heightmap, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Could not load heightmap {}: {}", path, e))?;
+        let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+        let heightmap = if bytes.starts_with(PNG_MAGIC) {
+            Heightmap::parse_png(&bytes)
+        } else if bytes.starts_with(EXR_MAGIC) {
+            Heightmap::parse_exr(path)
+        } else if bytes.starts_with(TIFF_LITTLE_ENDIAN) || bytes.starts_with(TIFF_BIG_ENDIAN) {
+            Heightmap::parse_tiff(&bytes)
+        } else if extension == "r16" || extension == "raw" {
+            Heightmap::parse_raw(&bytes, HeightmapFormat::Gray16, None)
+        } else if extension == "r32" {
+            Heightmap::parse_raw(&bytes, HeightmapFormat::Float32, None)
+        } else {
+            Err("formato no soportado (se admiten PNG, RAW/R16, R32, EXR y TIFF)".to_string())
+        };
+        heightmap.map_err(|e| format!("Could not load heightmap {}: {}", path, e))
+    }
+
+    // RAW sin cabecera de width x height muestras, para los que no son cuadrados (load supone
+    // que lo son). Muestras de 16 bits o, con extensión .r32, floats de 32
+    pub fn _load_raw(path: &str, width: usize, height: usize) -> Result<Heightmap, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Could not load heightmap {}: {}", path, e))?;
+        let format = if path.to_lowercase().ends_with(".r32") {
+            HeightmapFormat::Float32
+        } else {
+            HeightmapFormat::Gray16
+        };
+        Heightmap::parse_raw(&bytes, format, Some((width, height)))
+            .map_err(|e| format!("Could not load heightmap {}: {}", path, e))
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_format(&self) -> HeightmapFormat {
+        self.format
+    }
+
+    pub fn get_values(&self) -> &Vec<f32> {
+        &self.values
+    }
+
+    // Valor del píxel (x, z), 0 fuera de la imagen
+    pub fn get_value(&self, x: usize, z: usize) -> f32 {
+        if x >= self.width || z >= self.height {
+            return 0.0;
+        }
+        self.values[z * self.width + x]
+    }
+
+    // Valor en una posición en píxeles no entera, interpolando entre los cuatro más cercanos
+    pub fn get_interpolated_value(&self, x: f32, z: f32) -> f32 {
+        if self.width == 0 || self.height == 0 {
+            return 0.0;
+        }
+        let x = x.max(0.0).min((self.width - 1) as f32);
+        let z = z.max(0.0).min((self.height - 1) as f32);
+        let (x0, z0) = (x.floor() as usize, z.floor() as usize);
+        let (x1, z1) = ((x0 + 1).min(self.width - 1), (z0 + 1).min(self.height - 1));
+        let (tx, tz) = (x - x0 as f32, z - z0 as f32);
+
+        let top = self.get_value(x0, z0) * (1.0 - tx) + self.get_value(x1, z0) * tx;
+        let bottom = self.get_value(x0, z1) * (1.0 - tx) + self.get_value(x1, z1) * tx;
+        top * (1.0 - tz) + bottom * tz
+    }
+
+    // Con el crate png directamente y sin transformaciones: el decoder de image 0.21 recorta los
+    // PNG de 16 bits a 8
+    fn parse_png(bytes: &[u8]) -> Result<Heightmap, String> {
+        let mut decoder = png::Decoder::new(Cursor::new(bytes));
+        decoder.set(Transformations::IDENTITY);
+        let (info, mut reader) = decoder.read_info().map_err(|e| e.to_string())?;
+        // Paletas y grises de menos de 8 bits los expande image, no pierden precisión
+        let indexed = info.color_type == ColorType::Indexed;
+        if indexed || (info.bit_depth as u8) < 8 {
+            return Heightmap::parse_expanded_png(bytes, indexed);
+        }
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).map_err(|e| e.to_string())?;
+        let (width, height) = (info.width as usize, info.height as usize);
+
+        // Bytes por píxel y formato; de los grises con alfa solo se usa el gris
+        let (stride, format) = match (info.color_type, info.bit_depth) {
+            (ColorType::Grayscale, BitDepth::Eight) => (1, HeightmapFormat::Gray8),
+            (ColorType::GrayscaleAlpha, BitDepth::Eight) => (2, HeightmapFormat::Gray8),
+            (ColorType::Grayscale, BitDepth::Sixteen) => (2, HeightmapFormat::Gray16),
+            (ColorType::GrayscaleAlpha, BitDepth::Sixteen) => (4, HeightmapFormat::Gray16),
+            (ColorType::RGB, BitDepth::Eight) => (3, HeightmapFormat::Rgb24),
+            (ColorType::RGBA, BitDepth::Eight) => (4, HeightmapFormat::Rgb24),
+            (color_type, bit_depth) => return Err(format!(
+                "PNG {:?} de {} bits no soportado", color_type, bit_depth as u8)),
+        };
+        if pixels.len() < width * height * stride {
+            return Err("faltan datos de la imagen PNG".to_string());
+        }
+
+        // En PNG las muestras de 16 bits van en big endian
+        let values = pixels.chunks(stride).take(width * height).map(|pixel| match format {
+            HeightmapFormat::Gray8 => pixel[0] as f32 / MAX_GRAY_8,
+            HeightmapFormat::Gray16 => u16::from_be_bytes([pixel[0], pixel[1]]) as f32 / MAX_GRAY_16,
+            _ => ((pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32) as f32
+                / MAX_PIXEL_COLOUR,
+        }).collect();
+        Heightmap::new(width, height, format, values)
+    }
+
+    // Las paletas se leen como RGB de 24 bits, igual que antes de admitir otros formatos
+    fn parse_expanded_png(bytes: &[u8], indexed: bool) -> Result<Heightmap, String> {
+        let image = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        if indexed {
+            let values = image.to_rgb().pixels().map(|pixel| {
+                ((pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32) as f32
+                    / MAX_PIXEL_COLOUR
+            }).collect();
+            return Heightmap::new(width, height, HeightmapFormat::Rgb24, values);
+        }
+        let values = image.to_luma().pixels().map(|pixel| pixel[0] as f32 / MAX_GRAY_8).collect();
+        Heightmap::new(width, height, HeightmapFormat::Gray8, values)
+    }
+
+    // RAW/R16 (enteros de 16 bits) o R32 (float), little endian y sin cabecera. Se asume un
+    // heightmap cuadrado, como los que exportan World Machine o Unity
+    // size: ancho y alto; None para deducirlos suponiendo que es cuadrado
+    fn parse_raw(bytes: &[u8], format: HeightmapFormat, size: Option<(usize, usize)>)
+                 -> Result<Heightmap, String> {
+        let sample_size = if format == HeightmapFormat::Float32 { 4 } else { 2 };
+        let (width, height) = match size {
+            Some(size) => size,
+            None => {
+                let side = ((bytes.len() / sample_size) as f64).sqrt().round() as usize;
+                (side, side)
+            }
+        };
+        let expected = width.checked_mul(height).and_then(|count| count.checked_mul(sample_size));
+        if width == 0 || height == 0 || expected != Some(bytes.len()) {
+            return Err(match size {
+                Some(_) => format!("{} bytes no forman un heightmap de {}x{} con {} bytes por \
+                                    muestra", bytes.len(), width, height, sample_size),
+                None => format!("{} bytes no forman un heightmap cuadrado de {} bytes por \
+                                 muestra", bytes.len(), sample_size),
+            });
+        }
+        let values = bytes.chunks(sample_size).map(|sample| match format {
+            HeightmapFormat::Float32 => f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]),
+            _ => u16::from_le_bytes([sample[0], sample[1]]) as f32 / MAX_GRAY_16,
+        }).collect();
+        Heightmap::new(width, height, format, values)
+    }
+
+    // Primera capa del EXR; el canal de altura es Y, R o, si no hay ninguno, el primero
+    fn parse_exr(path: &str) -> Result<Heightmap, String> {
+        let image = exr::prelude::read_first_flat_layer_from_file(path).map_err(|e| e.to_string())?;
+        let layer = image.layer_data;
+        let channels = &layer.channel_data.list;
+        let channel = channels.iter().find(|channel| channel.name == *"Y")
+            .or_else(|| channels.iter().find(|channel| channel.name == *"R"))
+            .or_else(|| channels.first())
+            .ok_or_else(|| "EXR sin canales".to_string())?;
+        let values = channel.sample_data.values_as_f32().collect();
+        Heightmap::new(layer.size.width(), layer.size.height(), HeightmapFormat::Float32, values)
+    }
+
+    // TIFF de un solo canal sin comprimir y por strips: enteros de 8 o 16 bits o float de 32
+    fn parse_tiff(bytes: &[u8]) -> Result<Heightmap, String> {
+        let reader = TiffReader { bytes, big_endian: bytes.starts_with(TIFF_BIG_ENDIAN) };
+        let ifd = reader.read_u32(4)? as usize;
+        let entry_count = reader.read_u16(ifd)? as usize;
+
+        let (mut width, mut height) = (0, 0);
+        let (mut bits_per_sample, mut sample_format) = (8, TIFF_SAMPLE_FORMAT_UINT);
+        let (mut compression, mut samples_per_pixel) = (1, 1);
+        let (mut strip_offsets, mut strip_byte_counts) = (vec![], vec![]);
+        for i in 0..entry_count {
+            let entry = ifd + 2 + i * 12;
+            let tag = reader.read_u16(entry)?;
+            match tag {
+                TIFF_IMAGE_WIDTH => width = reader.read_entry(entry)?[0] as usize,
+                TIFF_IMAGE_LENGTH => height = reader.read_entry(entry)?[0] as usize,
+                TIFF_BITS_PER_SAMPLE => bits_per_sample = reader.read_entry(entry)?[0],
+                TIFF_COMPRESSION => compression = reader.read_entry(entry)?[0],
+                TIFF_SAMPLES_PER_PIXEL => samples_per_pixel = reader.read_entry(entry)?[0],
+                TIFF_SAMPLE_FORMAT => sample_format = reader.read_entry(entry)?[0],
+                TIFF_STRIP_OFFSETS => strip_offsets = reader.read_entry(entry)?,
+                TIFF_STRIP_BYTE_COUNTS => strip_byte_counts = reader.read_entry(entry)?,
+                TIFF_TILE_OFFSETS => return Err("TIFF por tiles no soportado".to_string()),
+                _ => {}
+            }
+        }
+        if compression != 1 {
+            return Err(format!("compresión TIFF {} no soportada", compression));
+        }
+        if samples_per_pixel != 1 {
+            return Err(format!("TIFF de {} canales no soportado, se necesita uno",
+                               samples_per_pixel));
+        }
+        let format = match (bits_per_sample, sample_format) {
+            (8, TIFF_SAMPLE_FORMAT_UINT) => HeightmapFormat::Gray8,
+            (16, TIFF_SAMPLE_FORMAT_UINT) => HeightmapFormat::Gray16,
+            (32, TIFF_SAMPLE_FORMAT_FLOAT) => HeightmapFormat::Float32,
+            _ => return Err(format!("TIFF de {} bits con SampleFormat {} no soportado",
+                                    bits_per_sample, sample_format)),
+        };
+        if strip_offsets.is_empty() || strip_offsets.len() != strip_byte_counts.len() {
+            return Err("TIFF sin strips".to_string());
+        }
+
+        // Los strips van en orden de filas, se juntan en un solo buffer
+        let mut data: Vec<u8> = vec![];
+        for (&offset, &count) in strip_offsets.iter().zip(strip_byte_counts.iter()) {
+            let (offset, count) = (offset as usize, count as usize);
+            if bytes.len() < offset + count {
+                return Err("faltan datos de los strips TIFF".to_string());
+            }
+            data.extend_from_slice(&bytes[offset..offset + count]);
+        }
+        let sample_size = bits_per_sample as usize / 8;
+        if data.len() < width * height * sample_size {
+            return Err("faltan datos de la imagen TIFF".to_string());
+        }
+        let values = data.chunks(sample_size).take(width * height).map(|sample| match format {
+            HeightmapFormat::Gray8 => sample[0] as f32 / MAX_GRAY_8,
+            HeightmapFormat::Gray16 => reader.to_u32(sample) as f32 / MAX_GRAY_16,
+            _ => f32::from_bits(reader.to_u32(sample)),
+        }).collect();
+        Heightmap::new(width, height, format, values)
+    }
+}
+
+// Lectura de enteros de un TIFF en el orden de bytes de su cabecera
+struct TiffReader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> TiffReader<'a> {
+    // Entero de 1, 2 o 4 bytes
+    fn to_u32(&self, bytes: &[u8]) -> u32 {
+        let fold = |value: u32, byte: &u8| value << 8 | *byte as u32;
+        if self.big_endian {
+            bytes.iter().fold(0, fold)
+        } else {
+            bytes.iter().rev().fold(0, fold)
+        }
+    }
+
+    fn read(&self, offset: usize, size: usize) -> Result<u32, String> {
+        if self.bytes.len() < offset + size {
+            return Err("cabecera TIFF incompleta".to_string());
+        }
+        Ok(self.to_u32(&self.bytes[offset..offset + size]))
+    }
+
+    fn read_u16(&self, offset: usize) -> Result<u16, String> {
+        self.read(offset, 2).map(|value| value as u16)
+    }
+
+    fn read_u32(&self, offset: usize) -> Result<u32, String> {
+        self.read(offset, 4)
+    }
+
+    // Valores de una entrada del IFD (BYTE, SHORT o LONG). Si caben en 4 bytes van en la
+    // propia entrada, si no la entrada guarda dónde están
+    fn read_entry(&self, entry: usize) -> Result<Vec<u32>, String> {
+        let size = match self.read_u16(entry + 2)? {
+            1 => 1,
+            3 => 2,
+            4 => 4,
+            field_type => return Err(format!("tipo de campo TIFF {} no soportado", field_type)),
+        };
+        let count = self.read_u32(entry + 4)? as usize;
+        if count == 0 {
+            return Err("entrada TIFF sin valores".to_string());
+        }
+        let offset = if size * count <= 4 { entry + 8 } else { self.read_u32(entry + 8)? as usize };
+        (0..count).map(|i| self.read(offset + i * size, size)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, bytes: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("heightmap_test_{}_{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn encode_png(width: u32, height: u32, color_type: ColorType, bit_depth: BitDepth,
+                  data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, width, height);
+            encoder.set(color_type).set(bit_depth);
+            encoder.write_header().unwrap().write_image_data(data).unwrap();
+        }
+        bytes
+    }
+
+    // TIFF de un canal y un solo strip, en el orden de bytes pedido
+    fn encode_tiff(big_endian: bool, width: u32, height: u32, bits_per_sample: u32,
+                   sample_format: u32, compression: u32, data: &[u8]) -> Vec<u8> {
+        let put = |bytes: &mut Vec<u8>, value: u32, size: usize| {
+            let le = value.to_le_bytes();
+            if big_endian {
+                bytes.extend(le[..size].iter().rev());
+            } else {
+                bytes.extend_from_slice(&le[..size]);
+            }
+        };
+        // (tag, tipo, valor); SHORT = 3, LONG = 4
+        let entries = [
+            (TIFF_IMAGE_WIDTH, 4, width),
+            (TIFF_IMAGE_LENGTH, 4, height),
+            (TIFF_BITS_PER_SAMPLE, 3, bits_per_sample),
+            (TIFF_COMPRESSION, 3, compression),
+            (TIFF_STRIP_OFFSETS, 4, 8 + 2 + 8 * 12 + 4),
+            (TIFF_SAMPLES_PER_PIXEL, 3, 1),
+            (TIFF_STRIP_BYTE_COUNTS, 4, data.len() as u32),
+            (TIFF_SAMPLE_FORMAT, 3, sample_format),
+        ];
+        let mut bytes = if big_endian { TIFF_BIG_ENDIAN } else { TIFF_LITTLE_ENDIAN }.to_vec();
+        put(&mut bytes, 8, 4);
+        put(&mut bytes, entries.len() as u32, 2);
+        for &(tag, field_type, value) in &entries {
+            put(&mut bytes, tag as u32, 2);
+            put(&mut bytes, field_type, 2);
+            put(&mut bytes, 1, 4);
+            // Los valores que ocupan menos de 4 bytes van al principio del campo
+            let size = if field_type == 3 { 2 } else { 4 };
+            put(&mut bytes, value, size);
+            bytes.resize(bytes.len() + 4 - size, 0);
+        }
+        put(&mut bytes, 0, 4);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn png_16_bit_keeps_its_precision() {
+        let samples: [u16; 6] = [0, 1, 256, 32768, 65534, 65535];
+        let data: Vec<u8> = samples.iter().flat_map(|sample| sample.to_be_bytes().to_vec()).collect();
+        let bytes = encode_png(3, 2, ColorType::Grayscale, BitDepth::Sixteen, &data);
+        let heightmap = Heightmap::load(&write_temp("gray16.png", &bytes)).unwrap();
+        assert_eq!((heightmap.get_width(), heightmap.get_height()), (3, 2));
+        assert_eq!(heightmap.get_format(), HeightmapFormat::Gray16);
+        let expected: Vec<f32> = samples.iter().map(|&sample| sample as f32 / MAX_GRAY_16).collect();
+        assert_eq!(heightmap.get_values(), &expected);
+        assert_eq!(heightmap.get_value(1, 1), 65534.0 / MAX_GRAY_16);
+    }
+
+    #[test]
+    fn png_rgb_is_a_24_bit_height() {
+        let bytes = encode_png(1, 1, ColorType::RGB, BitDepth::Eight, &[1, 2, 3]);
+        let heightmap = Heightmap::load(&write_temp("rgb.png", &bytes)).unwrap();
+        assert_eq!(heightmap.get_format(), HeightmapFormat::Rgb24);
+        assert_eq!(heightmap.get_values(), &[0x01_0203 as f32 / MAX_PIXEL_COLOUR]);
+    }
+
+    #[test]
+    fn raw_heightmaps() {
+        let samples: Vec<u8> = (0..16u16).flat_map(|i| (i * 4096).to_le_bytes().to_vec()).collect();
+        let square = Heightmap::load(&write_temp("square.r16", &samples)).unwrap();
+        assert_eq!((square.get_width(), square.get_height()), (4, 4));
+        assert_eq!(square.get_value(1, 2), 9.0 * 4096.0 / MAX_GRAY_16);
+
+        let floats: Vec<u8> = (0..6).flat_map(|i| (i as f32 * 12.5).to_le_bytes().to_vec()).collect();
+        let path = write_temp("wide.r32", &floats);
+        let wide = Heightmap::_load_raw(&path, 3, 2).unwrap();
+        assert_eq!(wide.get_format(), HeightmapFormat::Float32);
+        assert_eq!(wide.get_value(0, 1), 37.5);
+        assert!(Heightmap::_load_raw(&path, 4, 2).is_err());
+        assert!(Heightmap::_load_raw(&path, 0, 6).is_err());
+        assert!(Heightmap::_load_raw(&path, usize::MAX, 2).is_err());
+        // 24 bytes no son un R32 cuadrado
+        assert!(Heightmap::load(&path).is_err());
+    }
+
+    #[test]
+    fn tiff_16_bit_and_float() {
+        for &big_endian in &[false, true] {
+            let samples: [u16; 4] = [0, 1000, 40000, 65535];
+            let data: Vec<u8> = samples.iter().flat_map(|sample| if big_endian {
+                sample.to_be_bytes().to_vec()
+            } else {
+                sample.to_le_bytes().to_vec()
+            }).collect();
+            let bytes = encode_tiff(big_endian, 2, 2, 16, TIFF_SAMPLE_FORMAT_UINT, 1, &data);
+            let heightmap = Heightmap::load(&write_temp("gray16.tif", &bytes)).unwrap();
+            assert_eq!(heightmap.get_format(), HeightmapFormat::Gray16);
+            assert_eq!(heightmap.get_value(0, 1), 40000.0 / MAX_GRAY_16);
+        }
+        let data: Vec<u8> = [-3.5f32, 0.0, 120.25].iter()
+            .flat_map(|value| value.to_le_bytes().to_vec()).collect();
+        let bytes = encode_tiff(false, 3, 1, 32, TIFF_SAMPLE_FORMAT_FLOAT, 1, &data);
+        let heightmap = Heightmap::load(&write_temp("float.tif", &bytes)).unwrap();
+        assert_eq!(heightmap.get_format(), HeightmapFormat::Float32);
+        assert_eq!(heightmap.get_values(), &[-3.5, 0.0, 120.25]);
+    }
+
+    #[test]
+    fn rejects_malformed_heightmaps() {
+        let data = [0u8; 8];
+        // Compresión LZW, float de 16 bits y strip más corto que la imagen
+        let lzw = encode_tiff(false, 2, 2, 16, TIFF_SAMPLE_FORMAT_UINT, 5, &data);
+        let half = encode_tiff(false, 2, 2, 16, TIFF_SAMPLE_FORMAT_FLOAT, 1, &data);
+        let short = encode_tiff(false, 4, 4, 16, TIFF_SAMPLE_FORMAT_UINT, 1, &data);
+        let mut truncated = encode_tiff(false, 2, 2, 16, TIFF_SAMPLE_FORMAT_UINT, 1, &data);
+        truncated.truncate(20);
+        for (name, bytes) in &[("lzw.tif", lzw), ("half.tif", half), ("short.tif", short),
+                               ("truncated.tif", truncated)] {
+            assert!(Heightmap::load(&write_temp(name, bytes)).is_err(), "{}", name);
+        }
+
+        let rgb16 = encode_png(1, 1, ColorType::RGB, BitDepth::Sixteen, &[0; 6]);
+        assert!(Heightmap::load(&write_temp("rgb16.png", &rgb16)).is_err());
+        assert!(Heightmap::load(&write_temp("truncated.png", &rgb16[..12])).is_err());
+        assert!(Heightmap::load(&write_temp("unknown.bmp", &data)).is_err());
+        assert!(Heightmap::load("no/existe.png").is_err());
+        assert!(Heightmap::new(2, 2, HeightmapFormat::Gray8, vec![0.0; 3]).is_err());
+    }
+
+    #[test]
+    fn interpolated_values() {
+        let heightmap = Heightmap::new(2, 2, HeightmapFormat::Float32, vec![0.0, 1.0, 2.0, 3.0])
+            .unwrap();
+        assert_eq!(heightmap.get_interpolated_value(0.5, 0.5), 1.5);
+        assert_eq!(heightmap.get_interpolated_value(1.0, 0.25), 1.5);
+        // Fuera de la imagen se usa el borde
+        assert_eq!(heightmap.get_interpolated_value(-4.0, 9.0), 2.0);
+        assert_eq!(heightmap.get_value(2, 0), 0.0);
+    }
+}
@@ -1 +1,6 @@
-pub mod terrain;
\ No newline at end of file
+pub mod height_generator;
+pub mod height_grid;
+pub mod heightmap;
+pub mod terrain;
+pub mod terrain_descriptor;
+pub mod terrain_world;
\ No newline at end of file
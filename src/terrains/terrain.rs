@@ -1,17 +1,19 @@
 use cgmath::{InnerSpace, vec2, vec3, Vector3};
 
 use crate::models::raw_model::RawModel;
+use crate::obj_converter::model_data::ModelData;
 use crate::render_engine::loader::Loader;
+use crate::terrains::height_generator::HeightGenerator;
+use crate::terrains::height_grid;
+use crate::terrains::heightmap::{Heightmap, HeightmapFormat};
+use crate::terrains::terrain_descriptor::TerrainDescriptor;
 use crate::textures::terrain_texture::TerrainTexture;
 use crate::textures::terrain_texture_pack::TerrainTexturePack;
 use crate::toolbox::maths::*;
-use crate::toolbox::png_loader::PngLoader;
+use crate::toolbox::mesh_optimizer::OptimizationReport;
+use crate::toolbox::{mesh_optimizer, tangent_generator};
 
-const SIZE: f32 = 800.0;
-const MAX_HEIGHT: f32 = 40.0;
-const MAX_PIXEL_COLOUR: f32 = 256.0 * 256.0 * 256.0;
-//Esto arregla los bordes de altura de terrains
-const GRAIN: f32 = 0.99;
+const UP: Vector3<f32> = Vector3 { x: 0.0, y: 1.0, z: 0.0 };
 
 pub struct Terrain {
     x: f32,
@@ -19,9 +21,13 @@ pub struct Terrain {
     model: RawModel,
     texture_pack: TerrainTexturePack,
     blend_map: TerrainTexture,
-    vertex_count: usize,
-    png_loader: PngLoader,
+    descriptor: TerrainDescriptor,
+    vertex_count_x: usize,
+    vertex_count_z: usize,
+    heightmap: Option<Heightmap>, // None en los terrenos procedurales
     heights: Vec<Vec<f32>>,
+    normals: Vec<Vec<Vector3<f32>>>, // normal de cada vértice, [x][z] como heights
+    optimization_report: Option<OptimizationReport>, // de la última vez que se subió la malla
     //alturas: Vec<u8>,
 }
 
@@ -33,9 +39,13 @@ impl Clone for Terrain {
             model: self.model,
             texture_pack: self.texture_pack,
             blend_map: self.blend_map,
-            vertex_count: self.vertex_count,
-            png_loader: self.png_loader.clone(),
+            descriptor: self.descriptor,
+            vertex_count_x: self.vertex_count_x,
+            vertex_count_z: self.vertex_count_z,
+            heightmap: self.heightmap.clone(),
             heights: self.heights.clone(),
+            normals: self.normals.clone(),
+            optimization_report: self.optimization_report,
             //alturas: self.alturas,
         }
     }
@@ -46,20 +56,65 @@ impl Terrain {
                loader: &mut Loader,
                texture_pack: TerrainTexturePack,
                blend_map: TerrainTexture,
-               heightmap: &str) -> Terrain {
+               heightmap: &str,
+               descriptor: &TerrainDescriptor) -> Result<Terrain, String> {
+        let heightmap = Heightmap::load(heightmap)?;
+        Terrain::with_heightmap(grid_x, grid_z, loader, texture_pack, blend_map, heightmap,
+                                descriptor)
+    }
+
+    // Con el heightmap ya decodificado (por ejemplo en un hilo de AsyncLoader, o con
+    // Heightmap::_load_raw para un RAW que no es cuadrado). Falla si está vacío
+    pub fn with_heightmap(grid_x: i32, grid_z: i32,
+                          loader: &mut Loader,
+                          texture_pack: TerrainTexturePack,
+                          blend_map: TerrainTexture,
+                          heightmap: Heightmap,
+                          descriptor: &TerrainDescriptor) -> Result<Terrain, String> {
         let mut t = Terrain {
-            x: grid_x as f32 * SIZE,
-            z: grid_z as f32 * SIZE,
+            x: grid_x as f32 * descriptor.get_width(),
+            z: grid_z as f32 * descriptor.get_depth(),
             model: RawModel::new(0, 0),
             texture_pack,
             blend_map,
-            vertex_count: 0,
-            png_loader: PngLoader::new(),
+            descriptor: *descriptor,
+            vertex_count_x: 0,
+            vertex_count_z: 0,
+            heightmap: Some(heightmap),
             heights: vec![vec![]],
-
+            normals: vec![vec![]],
+            optimization_report: None,
         };
 
-        t.model = t.generate_terrain(loader, heightmap).unwrap();
+        t.model = t.generate_terrain(loader)?;
+        Ok(t)
+    }
+
+    // Alturas procedurales en vez de un heightmap. Los tiles con el mismo generador y
+    // descriptor encajan sin costuras con sus vecinos
+    pub fn with_generator(grid_x: i32, grid_z: i32,
+                          loader: &mut Loader,
+                          texture_pack: TerrainTexturePack,
+                          blend_map: TerrainTexture,
+                          generator: &HeightGenerator,
+                          descriptor: &TerrainDescriptor) -> Terrain {
+        let heights = generator.generate_tile(grid_x, grid_z, descriptor);
+        let mut t = Terrain {
+            x: grid_x as f32 * descriptor.get_width(),
+            z: grid_z as f32 * descriptor.get_depth(),
+            model: RawModel::new(0, 0),
+            texture_pack,
+            blend_map,
+            descriptor: *descriptor,
+            vertex_count_x: heights.len(),
+            vertex_count_z: heights[0].len(),
+            heightmap: None,
+            heights,
+            normals: vec![vec![]],
+            optimization_report: None,
+        };
+        t.normals = t.calculate_normals(&|_, _| None);
+        t.model = t.load_model(loader);
         t
     }
 
@@ -71,10 +126,37 @@ impl Terrain {
         self.z
     }
 
+    // Celda de la rejilla de TerrainWorld
+    pub fn get_grid_x(&self) -> i32 {
+        (self.x / self.descriptor.get_width()).round() as i32
+    }
+
+    pub fn get_grid_z(&self) -> i32 {
+        (self.z / self.descriptor.get_depth()).round() as i32
+    }
+
+    pub fn get_descriptor(&self) -> TerrainDescriptor {
+        self.descriptor
+    }
+
+    // Vértices en x y en z
+    pub fn get_resolution(&self) -> (usize, usize) {
+        (self.vertex_count_x, self.vertex_count_z)
+    }
+
     pub fn get_model(&self) -> RawModel {
         self.model
     }
 
+    // ACMR antes y después de optimizar la malla para la caché de vértices
+    pub fn get_optimization_report(&self) -> Option<OptimizationReport> {
+        self.optimization_report
+    }
+
+    pub fn get_heightmap(&self) -> Option<&Heightmap> {
+        self.heightmap.as_ref()
+    }
+
     pub fn get_texture_pack(&self) -> TerrainTexturePack {
         self.texture_pack
     }
@@ -85,162 +167,249 @@ impl Terrain {
 
     pub fn get_height_of_terrain(&self, world_x: f32, world_z: f32) -> f32 { // Devuelve altura del
         // player
-        // coordenadas x,z relativas en terrain (será 0,0 la esquina superior izquierda y SIZE,SIZE la esq inf der
+        let (grid_x, grid_z, x_coord, z_coord) = match self.locate(world_x, world_z) {
+            Some(cell) => cell,
+            None => return 0.0,
+        };
+
+        // Averiguamos en que triángulo de los dos posibles está el player y dentro del triángulo  la altura del player
+        if x_coord <= (1.0 - z_coord) { // Primer triángulo
+            barry_centric(vec3(0.0, self.heights[grid_x][grid_z], 0.0),
+                          vec3(1.0, self.heights[grid_x + 1][grid_z], 0.0),
+                          vec3(0.0, self.heights[grid_x][grid_z + 1], 1.0),
+                          vec2(x_coord, z_coord))
+        } else { // Segundo triángulo
+            barry_centric(vec3(1.0, self.heights[grid_x + 1][grid_z], 0.0),
+                          vec3(1.0, self.heights[grid_x + 1][grid_z + 1], 1.0),
+                          vec3(0.0, self.heights[grid_x][grid_z + 1], 1.0),
+                          vec2(x_coord, z_coord))
+        }
+    }
+
+    // Normal del terreno en un punto del mundo, interpolada entre las de los cuatro vértices del
+    // cuadrado (las mismas que usa la malla). Hacia arriba fuera del tile
+    pub fn get_normal_of_terrain(&self, world_x: f32, world_z: f32) -> Vector3<f32> {
+        let (grid_x, grid_z, x_coord, z_coord) = match self.locate(world_x, world_z) {
+            Some(cell) => cell,
+            None => return UP,
+        };
+        let top = self.normals[grid_x][grid_z] * (1.0 - x_coord) +
+            self.normals[grid_x + 1][grid_z] * x_coord;
+        let bottom = self.normals[grid_x][grid_z + 1] * (1.0 - x_coord) +
+            self.normals[grid_x + 1][grid_z + 1] * x_coord;
+        (top * (1.0 - z_coord) + bottom * z_coord).normalize()
+    }
+
+    // Pendiente en grados entre la normal y la vertical: 0 en llano, 90 en una pared
+    pub fn get_slope_of_terrain(&self, world_x: f32, world_z: f32) -> f32 {
+        height_grid::slope_degrees(self.get_normal_of_terrain(world_x, world_z))
+    }
+
+    // Cuadrado de la malla que contiene el punto del mundo y posición dentro de él (de 0 a 1),
+    // None fuera del tile
+    fn locate(&self, world_x: f32, world_z: f32) -> Option<(usize, usize, f32, f32)> {
+        // coordenadas x,z relativas en terrain (será 0,0 la esquina superior izquierda y
+        // ancho,fondo la esq inf der
         let terrain_x = world_x - self.x;
         let terrain_z = world_z - self.z;
+        // Fuera por delante del tile (floor de un negativo no cabe en usize)
+        if terrain_x < 0.0 || terrain_z < 0.0 {
+            return None;
+        }
+        if self.vertex_count_x < 2 || self.vertex_count_z < 2 {
+            return None;
+        }
         // Tamaño de cuadrado de la malla (-1 porque cuadrados es vertices por lado - 1)
-        let grid_square_size = SIZE / (self.heights.len() - 1) as f32;
+        let (square_x, square_z) = self.get_square_size();
         // gridX y gridZ son las coordenadas de cuadrados en la malla
-        let grid_x = (terrain_x / grid_square_size).floor() as usize;
-        let grid_z = (terrain_z / grid_square_size).floor() as usize;
+        let grid_x = (terrain_x / square_x).floor() as usize;
+        let grid_z = (terrain_z / square_z).floor() as usize;
 
         // Comprueba que no estamos fuera de los límites
-        if grid_x >= self.heights.len() - 1 || grid_z >= self.heights.len() - 1 {
-            return 0.0;
-        }
-        let x_coord = (terrain_x % grid_square_size) / grid_square_size;
-        let z_coord = (terrain_z % grid_square_size) / grid_square_size;
-
-        // Averiguamos en que triángulo de los dos posibles está el player y dentro del triángulo  la altura del player
-        let answer: f32;
-        if x_coord <= (1.0 - z_coord) { // Primer triángulo
-            answer = barry_centric(vec3(0.0, self.heights[grid_x][grid_z], 0.0),
-                                   vec3(1.0, self.heights[grid_x + 1][grid_z], 0.0),
-                                   vec3(0.0, self.heights[grid_x][grid_z + 1], 1.0),
-                                   vec2(x_coord, z_coord));
-        } else { // Segundo triángulo
-            answer = barry_centric(vec3(1.0, self.heights[grid_x + 1][grid_z], 0.0),
-                                   vec3(1.0, self.heights[grid_x + 1][grid_z + 1], 1.0),
-                                   vec3(0.0, self.heights[grid_x][grid_z + 1], 1.0),
-                                   vec2(x_coord, z_coord));
+        if grid_x >= self.vertex_count_x - 1 || grid_z >= self.vertex_count_z - 1 {
+            return None;
         }
+        let x_coord = (terrain_x % square_x) / square_x;
+        let z_coord = (terrain_z % square_z) / square_z;
+        Some((grid_x, grid_z, x_coord, z_coord))
+    }
 
-        return answer;
+    // Distancia entre vértices en x y en z
+    fn get_square_size(&self) -> (f32, f32) {
+        (self.descriptor.get_width() / (self.vertex_count_x.max(2) - 1) as f32,
+         self.descriptor.get_depth() / (self.vertex_count_z.max(2) - 1) as f32)
     }
 
-    pub fn generate_terrain(&mut self, loader: &mut Loader, height_map: &str)
-                            -> Result<RawModel, String> {
-        let alturas = self.png_loader.load_image(height_map)?;
+    pub fn generate_terrain(&mut self, loader: &mut Loader) -> Result<RawModel, String> {
+        let (width, height) = match &self.heightmap {
+            Some(heightmap) if !heightmap.get_values().is_empty() =>
+                (heightmap.get_width(), heightmap.get_height()),
+            _ => return Err("Heightmap sin cargar".to_string()),
+        };
 
-        self.vertex_count = self.png_loader.get_height();
-        self.heights = vec![vec![0.0; self.vertex_count]; self.vertex_count];
+        // Sin resolución en el descriptor, un vértice por píxel
+        let (vertex_count_x, vertex_count_z) = self.descriptor.get_resolution()
+            .unwrap_or((width.max(2), height.max(2)));
+        self.vertex_count_x = vertex_count_x;
+        self.vertex_count_z = vertex_count_z;
+        self.heights = vec![vec![0.0; self.vertex_count_z]; self.vertex_count_x];
+
+        for i in 0..self.vertex_count_z {
+            for j in 0..self.vertex_count_x {
+                //altura de terrain Y ahora usamos el heightmap
+                self.heights[j][i] = self.get_height(j, i);
+            }
+        }
+        self.normals = self.calculate_normals(&|_, _| None);
+        Ok(self.load_model(loader))
+    }
 
-        //let alturas = img.raw_pixels(); //convierte a Vec<u8>
+    // Sube a la GPU la malla de las alturas actuales y guarda el informe de la optimización
+    fn load_model(&mut self, loader: &mut Loader) -> RawModel {
+        // Tangentes para el normal mapping del terreno; la rejilla no tiene costuras de uv, así
+        // que no se duplica ningún vértice
+        let data = tangent_generator::generate_tangents(&self.to_model_data());
+        // Las filas de quads no aprovechan la caché de vértices. Visto desde arriba un
+        // heightmap apenas se solapa consigo mismo, no hace falta ordenar para el overdraw
+        let (data, report) = mesh_optimizer::optimize(&data, false);
+        self.optimization_report = Some(report);
+        loader.load_model_data(&data)
+    }
 
-        let count = self.vertex_count * self.vertex_count;
+    // Malla del terreno en CPU a partir de las alturas actuales, en coordenadas locales (de 0 al
+    // ancho en x y de 0 al fondo en z, el terreno se dibuja desplazado get_x, get_z). Sirve para
+    // exportarla con toolbox::mesh_exporter
+    pub fn to_model_data(&self) -> ModelData {
+        let (count_x, count_z) = (self.vertex_count_x, self.vertex_count_z);
+        let count = count_x * count_z;
+        if count_x < 2 || count_z < 2 {
+            return ModelData::new(vec![], vec![], vec![], vec![], 0.0);
+        }
 
         //vertices[] almacena coordenadas x,y,z
-        let mut vertices: Vec<f32> = vec![0.0; count * 3];
+        let mut vertices: Vec<f32> = Vec::with_capacity(count * 3);
         //normals[] almacena normals x,y,z
-        let mut normals: Vec<f32> = vec![0.0; count * 3];
+        let mut normals: Vec<f32> = Vec::with_capacity(count * 3);
         //textureCoords[] almacena  textura x,y
-        let mut texture_coords: Vec<f32> = vec![0.0; count * 2];
-        let mut indices: Vec<u32> = vec![0; 6 * (self.vertex_count - 1) * (self.vertex_count - 1)];
-
-        let mut vertex_pointer = 0;
-        for i in 0..self.vertex_count {
-            for j in 0..self.vertex_count {
-                vertices[vertex_pointer * 3] = j as f32 / (self.vertex_count - 1) as f32 * SIZE; //X
-
-                let height = self.get_height(j, i, &alturas);
-                self.heights[j][i] = height;
-                //altura de terrain Y ahora usamos heightMap.png
-                vertices[vertex_pointer * 3 + 1] = height;
-
-
-                vertices[vertex_pointer * 3 + 2] = i as f32 / (self.vertex_count - 1) as f32 * SIZE; //Z
-
-                let normal = self.calculate_normal(j as f32, i as f32, &alturas);//para montañas
-                normals[vertex_pointer * 3] = normal.x; //X Normal
-                normals[vertex_pointer * 3 + 1] = normal.y; //Y Normal apunta arriba
-                normals[vertex_pointer * 3 + 2] = normal.z; //Z Normal
-
-                //X de textura
-                texture_coords[vertex_pointer * 2] = j as f32 / (self.vertex_count - 1) as f32;
-
-                //Y de textura
-                texture_coords[vertex_pointer * 2 + 1] = i as f32 / (self.vertex_count - 1) as f32;
-
-
-                vertex_pointer += 1;
+        let mut texture_coords: Vec<f32> = Vec::with_capacity(count * 2);
+        let mut indices: Vec<u32> = Vec::with_capacity(6 * (count_x - 1) * (count_z - 1));
+
+        let (last_x, last_z) = ((count_x - 1) as f32, (count_z - 1) as f32);
+        for i in 0..count_z {
+            for j in 0..count_x {
+                let (u, v) = (j as f32 / last_x, i as f32 / last_z);
+                vertices.extend_from_slice(&[u * self.descriptor.get_width(), self.heights[j][i],
+                    v * self.descriptor.get_depth()]);
+                let normal = self.normals[j][i]; //para montañas
+                normals.extend_from_slice(&[normal.x, normal.y, normal.z]);
+                texture_coords.extend_from_slice(&[u, v]);
             }
         }
-        let mut pointer = 0;
-        for gz in 0..(self.vertex_count - 1) {
-            for gx in 0..(self.vertex_count - 1) {
-                let top_left = (gz * self.vertex_count) + gx;
+        for gz in 0..(count_z - 1) {
+            for gx in 0..(count_x - 1) {
+                let top_left = ((gz * count_x) + gx) as u32;
                 let top_right = top_left + 1;
-                let bottom_left = ((gz + 1) * self.vertex_count) + gx;
+                let bottom_left = (((gz + 1) * count_x) + gx) as u32;
                 let bottom_right = bottom_left + 1;
-
-                indices[pointer] = top_left as u32;
-                pointer += 1;
-                indices[pointer] = bottom_left as u32;
-                pointer += 1;
-                indices[pointer] = top_right as u32;
-                pointer += 1;
-                indices[pointer] = top_right as u32;
-                pointer += 1;
-                indices[pointer] = bottom_left as u32;
-                pointer += 1;
-                indices[pointer] = bottom_right as u32;
-                pointer += 1;
+                indices.extend_from_slice(&[top_left, bottom_left, top_right,
+                    top_right, bottom_left, bottom_right]);
             }
         }
-        Ok(loader.load_to_vao(&vertices.to_vec(),
-                              &texture_coords.to_vec(),
-                              &normals.to_vec(),
-                              &indices.to_vec()))
+        ModelData::new(vertices, texture_coords, normals, indices, 0.0)
     }
 
-    //usado para montañas en terrain, retorna vector normal en una coordenada de terrain
-    pub fn calculate_normal(&self, x: f32, z: f32, alturas: &Vec<u8>)
-                            -> Vector3<f32> {
+    // Cambia las normales (por ejemplo las que calcula TerrainWorld::stitch_normals con los
+    // tiles vecinos) y vuelve a subir la malla
+    pub fn set_normals(&mut self, loader: &mut Loader, normals: Vec<Vec<Vector3<f32>>>) {
+        self.normals = normals;
+        self.reload_model(loader);
+    }
+
+    // Cambia la altura del vértice (x, z), recalcula las normales de los vértices que la usan y
+    // vuelve a subir la malla. En los bordes la pendiente se prolonga: si el tile está en un
+    // TerrainWorld hay que volver a llamar a stitch_normals
+    pub fn set_height(&mut self, loader: &mut Loader, x: usize, z: usize, height: f32)
+                      -> Result<(), String> {
+        if x >= self.vertex_count_x || z >= self.vertex_count_z {
+            return Err(format!("Vértice ({}, {}) fuera del terreno de {}x{}", x, z,
+                               self.vertex_count_x, self.vertex_count_z));
+        }
+        self.heights[x][z] = height;
+        // El filtro de Sobel usa los ocho vecinos, la diferencia central solo cuatro
+        for i in x.saturating_sub(1)..(x + 2).min(self.vertex_count_x) {
+            for k in z.saturating_sub(1)..(z + 2).min(self.vertex_count_z) {
+                self.normals[i][k] = self.calculate_normal(i, k, &|_, _| None);
+            }
+        }
+        self.reload_model(loader);
+        Ok(())
+    }
 
-        // Calcula las alturas de las coordenadas vecinas
-        let height_l = self.get_height((x - GRAIN) as usize, z as usize, &alturas);
-        let height_r = self.get_height((x + GRAIN) as usize, z as usize, &alturas);
-        let height_d = self.get_height(x as usize, (z - GRAIN) as usize, &alturas);
-        let height_u = self.get_height(x as usize, (z + GRAIN) as usize, &alturas);
+    // Cambia todas las alturas, [x][z] con la misma resolución que el terreno, y recalcula
+    // todas las normales. Como set_height, los bordes no tienen en cuenta a los vecinos
+    pub fn set_heights(&mut self, loader: &mut Loader, heights: Vec<Vec<f32>>)
+                       -> Result<(), String> {
+        if heights.len() != self.vertex_count_x ||
+            heights.iter().any(|column| column.len() != self.vertex_count_z) {
+            return Err(format!("Las alturas no son de {}x{}", self.vertex_count_x,
+                               self.vertex_count_z));
+        }
+        self.heights = heights;
+        self.normals = self.calculate_normals(&|_, _| None);
+        self.reload_model(loader);
+        Ok(())
+    }
 
-        // Usando las alturas de los vertices vecinos, calcula el vector normal
-        let normal = vec3(height_l - height_r, 2.0, height_d - height_u);
-        normal.normalize(); // normaliza vector normal
-        return normal;
+    // Sube la malla nueva y descarga la anterior
+    fn reload_model(&mut self, loader: &mut Loader) {
+        let old_model = self.model;
+        self.model = self.load_model(loader);
+        loader.unload_model(&old_model);
     }
 
+    // Normales de todos los vértices, [x][z]
+    pub fn calculate_normals(&self, neighbours: &dyn Fn(f32, f32) -> Option<f32>)
+                             -> Vec<Vec<Vector3<f32>>> {
+        (0..self.vertex_count_x).map(|x| {
+            (0..self.vertex_count_z).map(|z| self.calculate_normal(x, z, neighbours)).collect()
+        }).collect()
+    }
 
-    // retorna altura de terrain según el color de heightMap.png
-    pub fn get_height(&self, x: usize, z: usize, _alturas: &Vec<u8>) -> f32 {
+    //usado para montañas en terrain, retorna vector normal en el vértice (x, z). Los vecinos que
+    // caen fuera del tile se piden a neighbours con su posición en el mundo; si devuelve None se
+    // prolonga la pendiente del borde
+    pub fn calculate_normal(&self, x: usize, z: usize,
+                            neighbours: &dyn Fn(f32, f32) -> Option<f32>) -> Vector3<f32> {
+        let (square_x, square_z) = self.get_square_size();
+        height_grid::calculate_normal(&self.heights, x, z, (square_x, square_z),
+                                      self.descriptor.get_normal_filter(), &|i, k| {
+            neighbours(self.x + i as f32 * square_x, self.z + k as f32 * square_z)
+        })
+    }
 
-        // Ojo vertex_count es el ancho de la pantalla
+    // retorna altura de terrain en el vértice (x, z) según el valor del heightmap. Si la
+    // resolución no es la de la imagen, se interpola entre los cuatro píxeles más cercanos
+    pub fn get_height(&self, x: usize, z: usize) -> f32 {
+        let heightmap = match &self.heightmap {
+            Some(heightmap) => heightmap,
+            None => return 0.0,
+        };
         // Salir si fuera de márgenes
-        //if x < 0 || x >= self.vertex_count || z < 0 || z >= self.vertex_count {
-        if x >= self.vertex_count || z >= self.vertex_count {
+        let (width, height) = (heightmap.get_width(), heightmap.get_height());
+        if x >= self.vertex_count_x || z >= self.vertex_count_z || width == 0 || height == 0 {
             return 0.0;
         }
-        //let mut height = image.get_rgb(x, z);
-
-        //let mut height = alturas[x as usize * 4 + z as usize * self.vertex_count] as f32 *
-        // 65025.0;
-        //let mut g = self.alturas[(x * 3) + 1 + z * self.vertex_count];
-        //let mut b = self.alturas[(x * 3) + 2 + z * self.vertex_count];
-
-//        let r = alturas[4 * z as usize * self.vertex_count + 4 * x as usize + 0] as i32;
-//        let g = alturas[4 * z as usize * self.vertex_count + 4 * x as usize + 1] as i32;
-//        let b = alturas[4 * z as usize * self.vertex_count + 4 * x as usize + 2] as i32;
-
-
-//        let h = (r << 16) | (g << 8) | (b << 0);
-//        let mut height = h as f32;
-
-        let mut height = self.png_loader.get_rgb(x, z);
-        //dbg!(height);
-        height += MAX_PIXEL_COLOUR / 2.0;
-        height /= MAX_PIXEL_COLOUR / 2.0;
-        height *= MAX_HEIGHT;
-
-        //let height = 1.0;
-        height
+        // Posición del vértice en píxeles
+        let to_pixel = |i: usize, count: usize, size: usize| {
+            if count > 1 { i as f32 / (count - 1) as f32 * (size - 1) as f32 } else { 0.0 }
+        };
+        let value = heightmap.get_interpolated_value(to_pixel(x, self.vertex_count_x, width),
+                                                     to_pixel(z, self.vertex_count_z, height));
+        if heightmap.get_format() == HeightmapFormat::Float32 &&
+            self.descriptor.is_float_heights_in_metres() {
+            self.descriptor.map_absolute_height(value)
+        } else {
+            self.descriptor.map_height(value)
+        }
     }
 }
\ No newline at end of file
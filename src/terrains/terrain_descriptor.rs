@@ -0,0 +1,119 @@
+// Lado de un tile por defecto, en unidades del mundo
+pub const DEFAULT_SIZE: f32 = 800.0;
+pub const DEFAULT_MAX_HEIGHT: f32 = 40.0;
+// Vértices por lado si no se pide una resolución y no hay imagen de la que sacarla
+pub const DEFAULT_VERTEX_COUNT: usize = 128;
+
+// Cálculo de las normales a partir de las alturas de los vértices vecinos
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalFilter {
+    CentralDifference, // el vértice anterior y el siguiente en x y en z
+    Sobel,             // los 8 vecinos con pesos, suaviza los escalones de los heightmaps de 8 bits
+}
+
+// Dimensiones de un terreno. El heightmap se reescala a la resolución pedida, que no tiene por
+// qué coincidir con la de la imagen, y puede ser rectangular
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainDescriptor {
+    width: f32,                          // tamaño en x
+    depth: f32,                          // tamaño en z
+    min_height: f32,                     // altura del valor más bajo del heightmap
+    max_height: f32,                     // altura del valor más alto del heightmap
+    height_offset: f32,                  // se suma a todas las alturas (también las generadas)
+    resolution: Option<(usize, usize)>,  // vértices en x y z, None = la de la imagen
+    normal_filter: NormalFilter,
+    float_heights_in_metres: bool,       // los heightmaps float no se escalan con el rango
+}
+
+impl TerrainDescriptor {
+    // 800 x 800, alturas de -40 a 40 (también para los heightmaps float, que se tratan como
+    // normalizados), la resolución de la imagen y normales por diferencias centrales
+    pub fn new() -> TerrainDescriptor {
+        TerrainDescriptor {
+            width: DEFAULT_SIZE,
+            depth: DEFAULT_SIZE,
+            min_height: -DEFAULT_MAX_HEIGHT,
+            max_height: DEFAULT_MAX_HEIGHT,
+            height_offset: 0.0,
+            resolution: None,
+            normal_filter: NormalFilter::CentralDifference,
+            float_heights_in_metres: false,
+        }
+    }
+
+    pub fn get_width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+
+    pub fn _set_size(&mut self, width: f32, depth: f32) {
+        self.width = width;
+        self.depth = depth;
+    }
+
+    pub fn _get_min_height(&self) -> f32 {
+        self.min_height
+    }
+
+    pub fn _get_max_height(&self) -> f32 {
+        self.max_height
+    }
+
+    pub fn _set_height_range(&mut self, min_height: f32, max_height: f32) {
+        self.min_height = min_height;
+        self.max_height = max_height;
+    }
+
+    pub fn get_height_offset(&self) -> f32 {
+        self.height_offset
+    }
+
+    pub fn _set_height_offset(&mut self, height_offset: f32) {
+        self.height_offset = height_offset;
+    }
+
+    pub fn get_resolution(&self) -> Option<(usize, usize)> {
+        self.resolution
+    }
+
+    // Mínimo 2 x 2 vértices
+    pub fn set_resolution(&mut self, vertex_count_x: usize, vertex_count_z: usize) {
+        self.resolution = Some((vertex_count_x.max(2), vertex_count_z.max(2)));
+    }
+
+    // Vuelve a la resolución de la imagen
+    pub fn _clear_resolution(&mut self) {
+        self.resolution = None;
+    }
+
+    pub fn get_normal_filter(&self) -> NormalFilter {
+        self.normal_filter
+    }
+
+    pub fn set_normal_filter(&mut self, normal_filter: NormalFilter) {
+        self.normal_filter = normal_filter;
+    }
+
+    pub fn is_float_heights_in_metres(&self) -> bool {
+        self.float_heights_in_metres
+    }
+
+    // Para EXR, TIFF float y R32 exportados en metros: sus valores se usan como altura, solo
+    // con height_offset, en vez de ir de min_height a max_height
+    pub fn _set_float_heights_in_metres(&mut self, float_heights_in_metres: bool) {
+        self.float_heights_in_metres = float_heights_in_metres;
+    }
+
+    // Altura de un valor del heightmap normalizado entre 0 y 1
+    pub fn map_height(&self, value: f32) -> f32 {
+        self.min_height + value * (self.max_height - self.min_height) + self.height_offset
+    }
+
+    // Altura de un valor en metros, sin escalar
+    pub fn map_absolute_height(&self, value: f32) -> f32 {
+        value + self.height_offset
+    }
+}
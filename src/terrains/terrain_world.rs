@@ -0,0 +1,111 @@
+use cgmath::{vec3, Vector3};
+
+use std::collections::HashMap;
+
+use crate::render_engine::loader::Loader;
+use crate::terrains::terrain::Terrain;
+use crate::terrains::terrain_descriptor::TerrainDescriptor;
+
+// Rejilla de terrenos. Cada tile (grid_x, grid_z) cubre de grid_x * ancho a (grid_x + 1) * ancho
+// en x, igual en z con el fondo, y un punto del borde pertenece al tile de mayor coordenada
+pub struct TerrainWorld {
+    tile_width: f32,
+    tile_depth: f32,
+    tiles: HashMap<(i32, i32), Terrain>,
+}
+
+impl TerrainWorld {
+    // Todos los tiles deberían tener el ancho y el fondo del descriptor
+    pub fn new(descriptor: &TerrainDescriptor) -> TerrainWorld {
+        TerrainWorld {
+            tile_width: descriptor.get_width(),
+            tile_depth: descriptor.get_depth(),
+            tiles: HashMap::new(),
+        }
+    }
+
+    pub fn _get_tile_size(&self) -> (f32, f32) {
+        (self.tile_width, self.tile_depth)
+    }
+
+    // Sustituye al tile que hubiera en la misma celda y lo devuelve
+    pub fn add_terrain(&mut self, terrain: Terrain) -> Option<Terrain> {
+        self.tiles.insert((terrain.get_grid_x(), terrain.get_grid_z()), terrain)
+    }
+
+    pub fn _remove_terrain(&mut self, grid_x: i32, grid_z: i32) -> Option<Terrain> {
+        self.tiles.remove(&(grid_x, grid_z))
+    }
+
+    // Celda de la rejilla que contiene el punto del mundo, tenga terreno o no
+    pub fn get_grid_coords(&self, world_x: f32, world_z: f32) -> (i32, i32) {
+        ((world_x / self.tile_width).floor() as i32, (world_z / self.tile_depth).floor() as i32)
+    }
+
+    pub fn get_tile(&self, grid_x: i32, grid_z: i32) -> Option<&Terrain> {
+        self.tiles.get(&(grid_x, grid_z))
+    }
+
+    pub fn _get_tile_mut(&mut self, grid_x: i32, grid_z: i32) -> Option<&mut Terrain> {
+        self.tiles.get_mut(&(grid_x, grid_z))
+    }
+
+    // Tile que contiene el punto del mundo
+    pub fn get_terrain(&self, world_x: f32, world_z: f32) -> Option<&Terrain> {
+        let (grid_x, grid_z) = self.get_grid_coords(world_x, world_z);
+        self.get_tile(grid_x, grid_z)
+    }
+
+    pub fn get_terrains(&self) -> Vec<&Terrain> {
+        self.tiles.values().collect()
+    }
+
+    pub fn _get_tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    // Altura en cualquier punto del mundo, None fuera de los tiles
+    pub fn get_height(&self, world_x: f32, world_z: f32) -> Option<f32> {
+        self.get_terrain(world_x, world_z)
+            .map(|terrain| terrain.get_height_of_terrain(world_x, world_z))
+    }
+
+    // Como Terrain::get_height_of_terrain: 0 donde no hay terreno
+    pub fn get_height_of_terrain(&self, world_x: f32, world_z: f32) -> f32 {
+        self.get_height(world_x, world_z).unwrap_or(0.0)
+    }
+
+    // Normal del terreno, None fuera de los tiles
+    pub fn _get_normal(&self, world_x: f32, world_z: f32) -> Option<Vector3<f32>> {
+        self.get_terrain(world_x, world_z)
+            .map(|terrain| terrain.get_normal_of_terrain(world_x, world_z))
+    }
+
+    // Hacia arriba donde no hay terreno
+    pub fn _get_normal_of_terrain(&self, world_x: f32, world_z: f32) -> Vector3<f32> {
+        self._get_normal(world_x, world_z).unwrap_or(vec3(0.0, 1.0, 0.0))
+    }
+
+    // Pendiente en grados, 0 donde no hay terreno
+    pub fn get_slope_of_terrain(&self, world_x: f32, world_z: f32) -> f32 {
+        self.get_terrain(world_x, world_z)
+            .map(|terrain| terrain.get_slope_of_terrain(world_x, world_z))
+            .unwrap_or(0.0)
+    }
+
+    // Recalcula las normales de todos los tiles usando las alturas de los vecinos en los bordes,
+    // para que no se vea la costura al iluminar. Vuelve a subir las mallas, así que conviene
+    // llamarlo una vez después de añadir todos los tiles
+    pub fn stitch_normals(&mut self, loader: &mut Loader) {
+        let normals: Vec<_> = self.tiles.iter()
+            .map(|(&cell, terrain)| {
+                (cell, terrain.calculate_normals(&|x, z| self.get_height(x, z)))
+            })
+            .collect();
+        for (cell, normals) in normals {
+            if let Some(terrain) = self.tiles.get_mut(&cell) {
+                terrain.set_normals(loader, normals);
+            }
+        }
+    }
+}
@@ -0,0 +1,873 @@
+// Descompresión en CPU de texturas por bloques 4x4 (BC1-BC5, BC7 y ETC2) a RGBA8.
+// Solo se usa cuando el driver no acepta el formato comprimido
+
+use crate::textures::compressed_texture::CompressedFormat;
+
+// Modificadores de ETC1/ETC2 en modo individual/diferencial, orden [a, b, -a, -b]
+const ETC_MODIFIERS: [[i32; 4]; 8] = [
+    [2, 8, -2, -8],
+    [5, 17, -5, -17],
+    [9, 29, -9, -29],
+    [13, 42, -13, -42],
+    [18, 60, -18, -60],
+    [24, 80, -24, -80],
+    [33, 106, -33, -106],
+    [47, 183, -47, -183],
+];
+
+// Distancias de los modos T y H de ETC2
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+// Modificadores del alfa EAC
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+// Modos de BC7 (el número de ceros antes del primer bit a 1 del bloque)
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    colour_bits: usize,
+    alpha_bits: usize,           // 0 = opaco
+    endpoint_p_bits: bool,       // un p-bit por extremo
+    shared_p_bits: bool,         // un p-bit por subset
+    index_bits: usize,
+    secondary_index_bits: usize, // modos 4 y 5: color y alfa con índices separados
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0,
+              colour_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false,
+              index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0,
+              colour_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true,
+              index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0,
+              colour_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false,
+              index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0,
+              colour_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false,
+              index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1,
+              colour_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false,
+              index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0,
+              colour_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false,
+              index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0,
+              colour_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false,
+              index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0,
+              colour_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false,
+              index_bits: 2, secondary_index_bits: 0 },
+];
+
+// Pesos de interpolación de BC7 (sobre 64) para índices de 2, 3 y 4 bits
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// Particiones de BC7 en dos subsets: bit i = subset del pixel i
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+// Particiones de BC7 en tres subsets, subset de cada pixel
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+// Pixel ancla (índice con un bit menos) del segundo subset con dos subsets, y del segundo y
+// tercero con tres. El del primer subset es siempre el pixel 0
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+const BC7_ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+const BC7_ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+// Descomprime un nivel de mipmap completo de width x height pixels a RGBA8
+pub fn decompress(format: CompressedFormat, data: &[u8], width: u32, height: u32)
+                  -> Result<Vec<u8>, String> {
+    let width = width as usize;
+    let height = height as usize;
+    let block_size = format.get_block_size();
+    if block_size == 0 {
+        return Err(format!("El formato {:?} no está comprimido por bloques", format));
+    }
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    if data.len() < blocks_x * blocks_y * block_size {
+        return Err(format!("Faltan datos para un nivel de {}x{} en {:?}", width, height, format));
+    }
+
+    let mut rgba: Vec<u8> = vec![0; width * height * 4];
+    let mut block_pixels = [[0u8; 4]; 16];
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let offset = (by * blocks_x + bx) * block_size;
+            let block = &data[offset..offset + block_size];
+            match format {
+                CompressedFormat::Bc1 => decode_bc1(block, &mut block_pixels, false),
+                CompressedFormat::Bc1Alpha => decode_bc1(block, &mut block_pixels, true),
+                CompressedFormat::Bc2 => decode_bc2(block, &mut block_pixels),
+                CompressedFormat::Bc3 => decode_bc3(block, &mut block_pixels),
+                CompressedFormat::Bc4 => decode_bc4(block, &mut block_pixels),
+                CompressedFormat::Bc5 => decode_bc5(block, &mut block_pixels),
+                CompressedFormat::Bc7 => decode_bc7(block, &mut block_pixels),
+                CompressedFormat::Etc2Rgb => decode_etc2_rgb(block, &mut block_pixels),
+                CompressedFormat::Etc2Rgba => {
+                    decode_etc2_rgb(&block[8..16], &mut block_pixels);
+                    decode_eac_alpha(&block[0..8], &mut block_pixels);
+                }
+                _ => return Err(format!("No se puede descomprimir {:?} en CPU", format)),
+            }
+
+            // Copia el bloque a la imagen, recortando los bloques del borde
+            for y in 0..4 {
+                for x in 0..4 {
+                    let px = bx * 4 + x;
+                    let py = by * 4 + y;
+                    if px < width && py < height {
+                        let dst = (py * width + px) * 4;
+                        rgba[dst..dst + 4].copy_from_slice(&block_pixels[y * 4 + x]);
+                    }
+                }
+            }
+        }
+    }
+    Ok(rgba)
+}
+
+fn read_u16_le(data: &[u8]) -> u16 {
+    data[0] as u16 | (data[1] as u16) << 8
+}
+
+fn read_u32_le(data: &[u8]) -> u32 {
+    data[0] as u32 | (data[1] as u32) << 8 | (data[2] as u32) << 16 | (data[3] as u32) << 24
+}
+
+// RGB565 -> RGB888
+fn expand_565(colour: u16) -> [i32; 3] {
+    let r = ((colour >> 11) & 31) as i32;
+    let g = ((colour >> 5) & 63) as i32;
+    let b = (colour & 31) as i32;
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+fn clamp_u8(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+// Bloque de color BC1. En BC2/BC3 el color siempre usa cuatro colores (force_four)
+fn decode_bc1_colour(block: &[u8], pixels: &mut [[u8; 4]; 16], punch_through: bool) {
+    let c0 = read_u16_le(&block[0..2]);
+    let c1 = read_u16_le(&block[2..4]);
+    let e0 = expand_565(c0);
+    let e1 = expand_565(c1);
+    let mut palette = [[0u8; 4]; 4];
+    palette[0] = [e0[0] as u8, e0[1] as u8, e0[2] as u8, 255];
+    palette[1] = [e1[0] as u8, e1[1] as u8, e1[2] as u8, 255];
+    if c0 > c1 || !punch_through {
+        for i in 0..3 {
+            palette[2][i] = ((2 * e0[i] + e1[i]) / 3) as u8;
+            palette[3][i] = ((e0[i] + 2 * e1[i]) / 3) as u8;
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for i in 0..3 {
+            palette[2][i] = ((e0[i] + e1[i]) / 2) as u8;
+        }
+        palette[2][3] = 255;
+        palette[3] = [0, 0, 0, 0]; // negro transparente
+    }
+
+    let indices = read_u32_le(&block[4..8]);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+fn decode_bc1(block: &[u8], pixels: &mut [[u8; 4]; 16], alpha: bool) {
+    decode_bc1_colour(block, pixels, true);
+    if !alpha {
+        // BC1 sin alfa: el cuarto color es negro opaco
+        for pixel in pixels.iter_mut() {
+            pixel[3] = 255;
+        }
+    }
+}
+
+fn decode_bc2(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    decode_bc1_colour(&block[8..16], pixels, false);
+    let alpha = read_u32_le(&block[0..4]) as u64 | (read_u32_le(&block[4..8]) as u64) << 32;
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = (((alpha >> (4 * i)) & 0xF) * 17) as u8;
+    }
+}
+
+// Bloque de 8 bytes de BC3 (alfa), BC4 y BC5: dos extremos y 16 índices de 3 bits
+fn decode_interpolated_channel(block: &[u8]) -> [u8; 16] {
+    let v0 = block[0] as i32;
+    let v1 = block[1] as i32;
+    let mut palette = [0i32; 8];
+    palette[0] = v0;
+    palette[1] = v1;
+    if v0 > v1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as i32) * v0 + i as i32 * v1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as i32) * v0 + i as i32 * v1) / 5;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits: u64 = 0;
+    for i in 0..6 {
+        bits |= (block[2 + i] as u64) << (8 * i);
+    }
+    let mut values = [0u8; 16];
+    for i in 0..16 {
+        values[i] = palette[((bits >> (3 * i)) & 7) as usize] as u8;
+    }
+    values
+}
+
+fn decode_bc3(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    decode_bc1_colour(&block[8..16], pixels, false);
+    let alpha = decode_interpolated_channel(&block[0..8]);
+    for i in 0..16 {
+        pixels[i][3] = alpha[i];
+    }
+}
+
+// BC4 se lee como (R, 0, 0, 1), igual que lo haría el sampler con el formato comprimido
+fn decode_bc4(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let red = decode_interpolated_channel(&block[0..8]);
+    for i in 0..16 {
+        pixels[i] = [red[i], 0, 0, 255];
+    }
+}
+
+fn decode_bc5(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let red = decode_interpolated_channel(&block[0..8]);
+    let green = decode_interpolated_channel(&block[8..16]);
+    for i in 0..16 {
+        pixels[i] = [red[i], green[i], 0, 255];
+    }
+}
+
+// Bloque BC7 de 16 bytes, los bits se leen desde el menos significativo del primer byte:
+// modo, partición, rotación, selección de índices, extremos (R de todos, luego G, B y A),
+// p-bits e índices
+fn decode_bc7(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let mode_number = block[0].trailing_zeros() as usize;
+    if mode_number >= BC7_MODES.len() {
+        // Modo reservado, el hardware lo decodifica como negro transparente
+        *pixels = [[0; 4]; 16];
+        return;
+    }
+    let mode = &BC7_MODES[mode_number];
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&block[0..16]);
+    let bits = u128::from_le_bytes(bytes);
+    let mut position = mode_number + 1;
+    let mut read = |count: usize| -> u32 {
+        let value = ((bits >> position) & ((1u128 << count) - 1)) as u32;
+        position += count;
+        value
+    };
+
+    let partition = read(mode.partition_bits) as usize;
+    let rotation = read(mode.rotation_bits);
+    let index_selection = read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    let channels = if mode.alpha_bits > 0 { 4 } else { 3 };
+    for channel in 0..channels {
+        let channel_bits = if channel < 3 { mode.colour_bits } else { mode.alpha_bits };
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = read(channel_bits);
+        }
+    }
+    let mut p_bits = [0u32; 6];
+    if mode.endpoint_p_bits {
+        for p_bit in p_bits.iter_mut().take(endpoint_count) {
+            *p_bit = read(1);
+        }
+    } else if mode.shared_p_bits {
+        for subset in 0..mode.subsets {
+            let p_bit = read(1);
+            p_bits[subset * 2] = p_bit;
+            p_bits[subset * 2 + 1] = p_bit;
+        }
+    }
+
+    // Extremos a 8 bits: el p-bit va debajo del valor y los bits altos se repiten abajo
+    let has_p_bit = mode.endpoint_p_bits || mode.shared_p_bits;
+    for (endpoint, p_bit) in endpoints.iter_mut().zip(p_bits.iter()).take(endpoint_count) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            if channel == 3 && mode.alpha_bits == 0 {
+                *value = 255;
+                continue;
+            }
+            let mut precision = if channel < 3 { mode.colour_bits } else { mode.alpha_bits };
+            if has_p_bit {
+                *value = *value << 1 | p_bit;
+                precision += 1;
+            }
+            *value <<= 8 - precision;
+            *value |= *value >> precision;
+        }
+    }
+
+    // Subset de cada pixel y pixels ancla, cuyo índice se guarda con un bit menos
+    let subset_of = |i: usize| -> usize {
+        match mode.subsets {
+            1 => 0,
+            2 => ((BC7_PARTITIONS_2[partition] >> i) & 1) as usize,
+            _ => BC7_PARTITIONS_3[partition][i] as usize,
+        }
+    };
+    let is_anchor = |i: usize| -> bool {
+        match mode.subsets {
+            1 => i == 0,
+            2 => i == 0 || i == BC7_ANCHORS_2[partition] as usize,
+            _ => i == 0 || i == BC7_ANCHORS_3_SECOND[partition] as usize ||
+                i == BC7_ANCHORS_3_THIRD[partition] as usize,
+        }
+    };
+    let mut indices = [0u32; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        *index = read(mode.index_bits - is_anchor(i) as usize);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (i, index) in secondary_indices.iter_mut().enumerate() {
+            *index = read(mode.secondary_index_bits - (i == 0) as usize);
+        }
+    }
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let subset = subset_of(i);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        // En los modos 4 y 5 el color y el alfa usan índices distintos; en el 4 el bit de
+        // selección intercambia cuáles son los de 2 y los de 3 bits
+        let ((colour_index, colour_bits), (alpha_index, alpha_bits)) =
+            if mode.secondary_index_bits == 0 {
+                ((indices[i], mode.index_bits), (indices[i], mode.index_bits))
+            } else if index_selection == 0 {
+                ((indices[i], mode.index_bits), (secondary_indices[i], mode.secondary_index_bits))
+            } else {
+                ((secondary_indices[i], mode.secondary_index_bits), (indices[i], mode.index_bits))
+            };
+        let colour_weight = bc7_weight(colour_bits, colour_index);
+        let alpha_weight = bc7_weight(alpha_bits, alpha_index);
+        for channel in 0..4 {
+            let weight = if channel < 3 { colour_weight } else { alpha_weight };
+            pixel[channel] = ((e0[channel] * (64 - weight) + e1[channel] * weight + 32) >> 6) as u8;
+        }
+        // Rotación: el alfa se intercambia con R, G o B
+        if rotation > 0 {
+            pixel.swap(3, rotation as usize - 1);
+        }
+    }
+}
+
+fn bc7_weight(bits: usize, index: u32) -> u32 {
+    match bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    }
+}
+
+fn extend_4(value: u8) -> i32 {
+    ((value << 4) | value) as i32
+}
+
+fn extend_5(value: u8) -> i32 {
+    ((value << 3) | (value >> 2)) as i32
+}
+
+fn extend_6(value: u8) -> i32 {
+    ((value << 2) | (value >> 4)) as i32
+}
+
+fn extend_7(value: u8) -> i32 {
+    ((value << 1) | (value >> 6)) as i32
+}
+
+// Entero de 3 bits con signo
+fn signed_3(value: u8) -> i32 {
+    let value = (value & 7) as i32;
+    if value >= 4 { value - 8 } else { value }
+}
+
+// Bloque ETC2 RGB (8 bytes, big endian). Los índices de pixel van por columnas: i = x * 4 + y
+fn decode_etc2_rgb(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let msb = (block[4] as u32) << 8 | block[5] as u32;
+    let lsb = (block[6] as u32) << 8 | block[7] as u32;
+    let pixel_index = |x: usize, y: usize| -> usize {
+        let i = x * 4 + y;
+        ((((msb >> i) & 1) << 1) | ((lsb >> i) & 1)) as usize
+    };
+
+    let differential = block[3] & 2 != 0;
+    if differential {
+        let r = (block[0] >> 3) as i32 + signed_3(block[0]);
+        let g = (block[1] >> 3) as i32 + signed_3(block[1]);
+        let b = (block[2] >> 3) as i32 + signed_3(block[2]);
+        if !(0..=31).contains(&r) {
+            decode_etc2_t(block, pixels, &pixel_index);
+            return;
+        }
+        if !(0..=31).contains(&g) {
+            decode_etc2_h(block, pixels, &pixel_index);
+            return;
+        }
+        if !(0..=31).contains(&b) {
+            decode_etc2_planar(block, pixels);
+            return;
+        }
+    }
+
+    // Modos individual y diferencial de ETC1
+    let (base1, base2) = if differential {
+        let r1 = block[0] >> 3;
+        let g1 = block[1] >> 3;
+        let b1 = block[2] >> 3;
+        let r2 = (r1 as i32 + signed_3(block[0])) as u8;
+        let g2 = (g1 as i32 + signed_3(block[1])) as u8;
+        let b2 = (b1 as i32 + signed_3(block[2])) as u8;
+        ([extend_5(r1), extend_5(g1), extend_5(b1)], [extend_5(r2), extend_5(g2), extend_5(b2)])
+    } else {
+        ([extend_4(block[0] >> 4), extend_4(block[1] >> 4), extend_4(block[2] >> 4)],
+         [extend_4(block[0] & 0xF), extend_4(block[1] & 0xF), extend_4(block[2] & 0xF)])
+    };
+    let table1 = (block[3] >> 5) as usize;
+    let table2 = ((block[3] >> 2) & 7) as usize;
+    let flip = block[3] & 1 != 0;
+
+    for y in 0..4 {
+        for x in 0..4 {
+            let second = if flip { y >= 2 } else { x >= 2 };
+            let (base, table) = if second { (base2, table2) } else { (base1, table1) };
+            let modifier = ETC_MODIFIERS[table][pixel_index(x, y)];
+            pixels[y * 4 + x] = [clamp_u8(base[0] + modifier), clamp_u8(base[1] + modifier),
+                clamp_u8(base[2] + modifier), 255];
+        }
+    }
+}
+
+fn decode_etc2_t(block: &[u8], pixels: &mut [[u8; 4]; 16], pixel_index: &dyn Fn(usize, usize) -> usize) {
+    let r1 = ((block[0] >> 3) & 3) << 2 | (block[0] & 3);
+    let c1 = [extend_4(r1), extend_4(block[1] >> 4), extend_4(block[1] & 0xF)];
+    let c2 = [extend_4(block[2] >> 4), extend_4(block[2] & 0xF), extend_4(block[3] >> 4)];
+    let distance = ETC_DISTANCES[(((block[3] >> 2) & 3) << 1 | (block[3] & 1)) as usize];
+
+    let paint = [
+        c1,
+        [c2[0] + distance, c2[1] + distance, c2[2] + distance],
+        c2,
+        [c2[0] - distance, c2[1] - distance, c2[2] - distance],
+    ];
+    fill_paint_colours(pixels, &paint, pixel_index);
+}
+
+fn decode_etc2_h(block: &[u8], pixels: &mut [[u8; 4]; 16], pixel_index: &dyn Fn(usize, usize) -> usize) {
+    let r1 = (block[0] >> 3) & 0xF;
+    let g1 = (block[0] & 7) << 1 | ((block[1] >> 4) & 1);
+    let b1 = ((block[1] >> 3) & 1) << 3 | (block[1] & 3) << 1 | (block[2] >> 7);
+    let r2 = (block[2] >> 3) & 0xF;
+    let g2 = (block[2] & 7) << 1 | (block[3] >> 7);
+    let b2 = (block[3] >> 3) & 0xF;
+
+    let value1 = (r1 as u32) << 8 | (g1 as u32) << 4 | b1 as u32;
+    let value2 = (r2 as u32) << 8 | (g2 as u32) << 4 | b2 as u32;
+    let index = ((block[3] >> 2) & 1) << 2 | (block[3] & 1) << 1 | (value1 >= value2) as u8;
+    let distance = ETC_DISTANCES[index as usize];
+
+    let c1 = [extend_4(r1), extend_4(g1), extend_4(b1)];
+    let c2 = [extend_4(r2), extend_4(g2), extend_4(b2)];
+    let paint = [
+        [c1[0] + distance, c1[1] + distance, c1[2] + distance],
+        [c1[0] - distance, c1[1] - distance, c1[2] - distance],
+        [c2[0] + distance, c2[1] + distance, c2[2] + distance],
+        [c2[0] - distance, c2[1] - distance, c2[2] - distance],
+    ];
+    fill_paint_colours(pixels, &paint, pixel_index);
+}
+
+fn fill_paint_colours(pixels: &mut [[u8; 4]; 16], paint: &[[i32; 3]; 4],
+                      pixel_index: &dyn Fn(usize, usize) -> usize) {
+    for y in 0..4 {
+        for x in 0..4 {
+            let colour = paint[pixel_index(x, y)];
+            pixels[y * 4 + x] = [clamp_u8(colour[0]), clamp_u8(colour[1]), clamp_u8(colour[2]), 255];
+        }
+    }
+}
+
+// Modo planar: tres colores (origen, horizontal y vertical) interpolados en el bloque
+fn decode_etc2_planar(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let ro = extend_6((block[0] >> 1) & 0x3F);
+    let go = extend_7((block[0] & 1) << 6 | (block[1] >> 1) & 0x3F);
+    let bo = extend_6((block[1] & 1) << 5 | ((block[2] >> 3) & 3) << 3 | (block[2] & 3) << 1 |
+        (block[3] >> 7));
+    let rh = extend_6(((block[3] >> 2) & 0x1F) << 1 | (block[3] & 1));
+    let gh = extend_7(block[4] >> 1);
+    let bh = extend_6((block[4] & 1) << 5 | (block[5] >> 3));
+    let rv = extend_6((block[5] & 7) << 3 | (block[6] >> 5));
+    let gv = extend_7((block[6] & 0x1F) << 2 | (block[7] >> 6));
+    let bv = extend_6(block[7] & 0x3F);
+
+    for y in 0..4 {
+        for x in 0..4 {
+            let (xi, yi) = (x as i32, y as i32);
+            let r = (xi * (rh - ro) + yi * (rv - ro) + 4 * ro + 2) >> 2;
+            let g = (xi * (gh - go) + yi * (gv - go) + 4 * go + 2) >> 2;
+            let b = (xi * (bh - bo) + yi * (bv - bo) + 4 * bo + 2) >> 2;
+            pixels[y * 4 + x] = [clamp_u8(r), clamp_u8(g), clamp_u8(b), 255];
+        }
+    }
+}
+
+// Alfa EAC de ETC2 RGBA (8 bytes, big endian)
+fn decode_eac_alpha(block: &[u8], pixels: &mut [[u8; 4]; 16]) {
+    let base = block[0] as i32;
+    let multiplier = (block[1] >> 4) as i32;
+    let table = (block[1] & 0xF) as usize;
+    let mut bits: u64 = 0;
+    for &byte in &block[2..8] {
+        bits = bits << 8 | byte as u64;
+    }
+    for x in 0..4 {
+        for y in 0..4 {
+            let i = x * 4 + y;
+            let index = ((bits >> (45 - 3 * i)) & 7) as usize;
+            pixels[y * 4 + x][3] = clamp_u8(base + EAC_MODIFIERS[table][index] * multiplier);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED_565: u16 = 0xF800;
+    const BLUE_565: u16 = 0x001F;
+
+    // Bloque BC1 con los dos colores y un índice de 2 bits por pixel
+    fn bc1_block(c0: u16, c1: u16, indices: u32) -> Vec<u8> {
+        let mut block = vec![];
+        block.extend_from_slice(&c0.to_le_bytes());
+        block.extend_from_slice(&c1.to_le_bytes());
+        block.extend_from_slice(&indices.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn bc1_decodes_endpoints_and_interpolated_colours() {
+        // Fila 0 con los índices 0, 1, 2 y 3, el resto con el 0
+        let block = bc1_block(RED_565, BLUE_565, 0b11_10_01_00);
+        let rgba = decompress(CompressedFormat::Bc1, &block, 4, 4).unwrap();
+        assert_eq!(&rgba[0..4], &[255, 0, 0, 255]);
+        assert_eq!(&rgba[4..8], &[0, 0, 255, 255]);
+        assert_eq!(&rgba[8..12], &[170, 0, 85, 255]);
+        assert_eq!(&rgba[12..16], &[85, 0, 170, 255]);
+        assert_eq!(&rgba[60..64], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc1_alpha_has_transparent_black() {
+        // c0 <= c1: tres colores y el índice 3 es transparente
+        let block = bc1_block(BLUE_565, RED_565, 0b11);
+        let rgba = decompress(CompressedFormat::Bc1Alpha, &block, 4, 4).unwrap();
+        assert_eq!(&rgba[0..4], &[0, 0, 0, 0]);
+        let opaque = decompress(CompressedFormat::Bc1, &block, 4, 4).unwrap();
+        assert_eq!(&opaque[0..4], &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn edge_blocks_are_cropped() {
+        // 5x2: dos bloques en x, del segundo solo la primera columna
+        let mut data = bc1_block(RED_565, RED_565, 0);
+        data.extend(bc1_block(BLUE_565, BLUE_565, 0));
+        let rgba = decompress(CompressedFormat::Bc1, &data, 5, 2).unwrap();
+        assert_eq!(rgba.len(), 5 * 2 * 4);
+        assert_eq!(&rgba[12..16], &[255, 0, 0, 255]);
+        assert_eq!(&rgba[16..20], &[0, 0, 255, 255]);
+        assert_eq!(&rgba[36..40], &[0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn rejects_missing_data_and_unsupported_formats() {
+        let block = bc1_block(RED_565, BLUE_565, 0);
+        assert!(decompress(CompressedFormat::Bc1, &block, 8, 4).is_err());
+        assert!(decompress(CompressedFormat::Rgba8, &block, 1, 1).is_err());
+        assert!(decompress(CompressedFormat::Bc7, &[0; 8], 4, 4).is_err());
+    }
+
+    // Escribe los campos de un bloque BC7 en orden, desde el bit menos significativo
+    struct Bc7Writer {
+        bits: u128,
+        position: usize,
+    }
+
+    impl Bc7Writer {
+        // El modo n son n ceros y un uno
+        fn new(mode: usize) -> Bc7Writer {
+            let mut writer = Bc7Writer { bits: 0, position: 0 };
+            writer.write(1 << mode, mode + 1);
+            writer
+        }
+
+        fn write(&mut self, value: u32, count: usize) {
+            self.bits |= (value as u128) << self.position;
+            self.position += count;
+        }
+
+        fn finish(&self) -> [[u8; 4]; 16] {
+            assert_eq!(self.position, 128);
+            let mut pixels = [[0u8; 4]; 16];
+            decode_bc7(&self.bits.to_le_bytes(), &mut pixels);
+            pixels
+        }
+    }
+
+    #[test]
+    fn bc7_mode_6_interpolates_with_4_bit_indices() {
+        let mut block = Bc7Writer::new(6);
+        // Extremos RGBA de 7 bits: negro y blanco, y sus p-bits
+        for _ in 0..4 {
+            block.write(0, 7);
+            block.write(127, 7);
+        }
+        block.write(0, 1);
+        block.write(1, 1);
+        // El pixel 0 es el ancla, su índice tiene 3 bits
+        block.write(0, 3);
+        for i in 1..16 {
+            block.write(i, 4);
+        }
+        let pixels = block.finish();
+        let expected = [0, 16, 36, 52, 68, 84, 104, 120, 135, 151, 171, 187, 203, 219, 239, 255];
+        for (pixel, &value) in pixels.iter().zip(expected.iter()) {
+            assert_eq!(pixel, &[value; 4]);
+        }
+
+        let mut data = block.bits.to_le_bytes().to_vec();
+        data.extend_from_slice(&data.clone());
+        let rgba = decompress(CompressedFormat::Bc7, &data, 6, 2).unwrap();
+        assert_eq!(&rgba[4 * 4..5 * 4], &[0; 4]);
+        assert_eq!(&rgba[5 * 4..6 * 4], &[16; 4]);
+        assert_eq!(&rgba[7 * 4..8 * 4], &[84; 4]);
+    }
+
+    #[test]
+    fn bc7_two_subsets_use_the_partition_anchor() {
+        // Modo 1, partición 17: los pixels 1, 2, 3 y 7 son del subset 1 y el ancla es el 2
+        let mut block = Bc7Writer::new(1);
+        block.write(17, 6);
+        for _ in 0..3 {
+            // Subset 0 gris constante, subset 1 de 1 a 127 (7 bits con el p-bit a 1)
+            block.write(32, 6);
+            block.write(32, 6);
+            block.write(0, 6);
+            block.write(63, 6);
+        }
+        block.write(0, 1);
+        block.write(1, 1);
+        let indices = [2, 7, 3, 5, 6, 6, 6, 1, 6, 6, 6, 6, 6, 6, 6, 6];
+        for (i, &index) in indices.iter().enumerate() {
+            block.write(index, if i == 0 || i == 2 { 2 } else { 3 });
+        }
+        let pixels = block.finish();
+        assert_eq!(pixels[0], [129, 129, 129, 255]);
+        assert_eq!(pixels[15], [129, 129, 129, 255]);
+        assert_eq!(pixels[1], [255, 255, 255, 255]);
+        assert_eq!(pixels[2], [109, 109, 109, 255]);
+        assert_eq!(pixels[3], [184, 184, 184, 255]);
+        assert_eq!(pixels[7], [38, 38, 38, 255]);
+    }
+
+    #[test]
+    fn bc7_three_subsets_follow_the_partition_table() {
+        // Modo 0, partición 15, el subset s tiene el canal s a 15 (247 en 8 bits) y lo demás a 0
+        let mut block = Bc7Writer::new(0);
+        block.write(15, 4);
+        for channel in 0..3 {
+            for subset in 0..3 {
+                let value = if subset == channel { 15 } else { 0 };
+                block.write(value, 4);
+                block.write(value, 4);
+            }
+        }
+        block.write(0, 6);
+        block.write(0, 45);
+        let pixels = block.finish();
+        for (i, pixel) in pixels.iter().enumerate() {
+            let mut expected = [0, 0, 0, 255];
+            expected[BC7_PARTITIONS_3[15][i] as usize] = 247;
+            assert_eq!(pixel, &expected, "pixel {}", i);
+        }
+    }
+
+    #[test]
+    fn bc7_separate_alpha_indices_and_rotation() {
+        // Modo 5: rojo constante y alfa de 0 a 255 con índices propios
+        let mode_5 = |rotation: u32| {
+            let mut block = Bc7Writer::new(5);
+            block.write(rotation, 2);
+            for &value in &[127, 127, 0, 0, 0, 0] {
+                block.write(value, 7);
+            }
+            block.write(0, 8);
+            block.write(255, 8);
+            block.write(0, 31);
+            block.write(0, 1);
+            for i in 1..16 {
+                block.write(i % 4, 2);
+            }
+            block.finish()
+        };
+        assert_eq!(mode_5(0)[1], [255, 0, 0, 84]);
+        assert_eq!(mode_5(0)[3], [255, 0, 0, 255]);
+        assert_eq!(mode_5(1)[1], [84, 0, 0, 255]);
+        assert_eq!(mode_5(3)[2], [255, 0, 171, 0]);
+
+        // Modo 4: el bit de selección decide si el color usa los índices de 2 o de 3 bits
+        let mode_4 = |index_selection: u32| {
+            let mut block = Bc7Writer::new(4);
+            block.write(0, 2);
+            block.write(index_selection, 1);
+            for _ in 0..3 {
+                block.write(0, 5);
+                block.write(31, 5);
+            }
+            block.write(63, 6);
+            block.write(63, 6);
+            block.write(0, 1);
+            block.write(1, 2);
+            block.write(0, 28);
+            block.write(0, 2);
+            block.write(2, 3);
+            block.write(0, 42);
+            block.finish()
+        };
+        assert_eq!(mode_4(0)[1], [84, 84, 84, 255]);
+        assert_eq!(mode_4(1)[1], [72, 72, 72, 255]);
+    }
+
+    #[test]
+    fn bc7_reserved_mode_is_transparent_black() {
+        let mut pixels = [[9u8; 4]; 16];
+        decode_bc7(&[0; 16], &mut pixels);
+        assert_eq!(pixels, [[0; 4]; 16]);
+    }
+}
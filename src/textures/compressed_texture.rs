@@ -0,0 +1,486 @@
+use gl;
+use gl::types::*;
+
+use std::fs;
+
+// GL_EXT_texture_compression_s3tc y GL_EXT_texture_sRGB, no son core
+const COMPRESSED_RGB_S3TC_DXT1: GLenum = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1: GLenum = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: GLenum = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: GLenum = 0x8C4F;
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const DDS_HEADER_SIZE: usize = 124;
+const DDS_DX10_HEADER_SIZE: usize = 20;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+const KTX2_IDENTIFIER: [u8; 12] =
+    [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const KTX2_HEADER_SIZE: usize = 80;
+const KTX2_LEVEL_INDEX_SIZE: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressedFormat {
+    Bc1,      // DXT1 sin alfa
+    Bc1Alpha, // DXT1 con alfa de 1 bit
+    Bc2,      // DXT3
+    Bc3,      // DXT5
+    Bc4,      // un canal (R)
+    Bc5,      // dos canales (RG), normal maps
+    Bc7,
+    Etc2Rgb,
+    Etc2Rgba, // ETC2 + alfa EAC
+    Rgba8,    // sin comprimir, para contenedores con mipmaps ya hechos
+}
+
+impl CompressedFormat {
+    // Bytes por bloque de 4x4 pixels, 0 si no está comprimido
+    pub fn get_block_size(&self) -> usize {
+        match self {
+            CompressedFormat::Bc1 | CompressedFormat::Bc1Alpha | CompressedFormat::Bc4 |
+            CompressedFormat::Etc2Rgb => 8,
+            CompressedFormat::Bc2 | CompressedFormat::Bc3 | CompressedFormat::Bc5 |
+            CompressedFormat::Bc7 | CompressedFormat::Etc2Rgba => 16,
+            CompressedFormat::Rgba8 => 0,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.get_block_size() != 0
+    }
+
+    // Tamaño en bytes de un nivel de width x height. Satura en vez de desbordar con los tamaños
+    // absurdos de una cabecera rota
+    pub fn get_level_size(&self, width: u32, height: u32) -> usize {
+        if self.is_compressed() {
+            let blocks_x = (width.max(1) as usize).div_ceil(4);
+            let blocks_y = (height.max(1) as usize).div_ceil(4);
+            blocks_x.saturating_mul(blocks_y).saturating_mul(self.get_block_size())
+        } else {
+            (width.max(1) as usize).saturating_mul(height.max(1) as usize).saturating_mul(4)
+        }
+    }
+
+    // Internal format para glCompressedTexImage2D (o glTexImage2D si es Rgba8).
+    // BC4 y BC5 no tienen variante sRGB
+    pub fn to_gl(self, srgb: bool) -> GLenum {
+        match (self, srgb) {
+            (CompressedFormat::Bc1, false) => COMPRESSED_RGB_S3TC_DXT1,
+            (CompressedFormat::Bc1, true) => COMPRESSED_SRGB_S3TC_DXT1,
+            (CompressedFormat::Bc1Alpha, false) => COMPRESSED_RGBA_S3TC_DXT1,
+            (CompressedFormat::Bc1Alpha, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            (CompressedFormat::Bc2, false) => COMPRESSED_RGBA_S3TC_DXT3,
+            (CompressedFormat::Bc2, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT3,
+            (CompressedFormat::Bc3, false) => COMPRESSED_RGBA_S3TC_DXT5,
+            (CompressedFormat::Bc3, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            (CompressedFormat::Bc4, _) => gl::COMPRESSED_RED_RGTC1,
+            (CompressedFormat::Bc5, _) => gl::COMPRESSED_RG_RGTC2,
+            (CompressedFormat::Bc7, false) => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            (CompressedFormat::Bc7, true) => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            (CompressedFormat::Etc2Rgb, false) => gl::COMPRESSED_RGB8_ETC2,
+            (CompressedFormat::Etc2Rgb, true) => gl::COMPRESSED_SRGB8_ETC2,
+            (CompressedFormat::Etc2Rgba, false) => gl::COMPRESSED_RGBA8_ETC2_EAC,
+            (CompressedFormat::Etc2Rgba, true) => gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+            (CompressedFormat::Rgba8, false) => gl::RGBA8,
+            (CompressedFormat::Rgba8, true) => gl::SRGB8_ALPHA8,
+        }
+    }
+}
+
+// Textura de un contenedor KTX2 o DDS: formato y cadena de mipmaps tal cual viene en el fichero.
+// El nivel 0 es el más grande
+pub struct CompressedTexture {
+    width: u32,
+    height: u32,
+    format: CompressedFormat,
+    srgb: bool, // el propio fichero dice que está en sRGB
+    levels: Vec<Vec<u8>>,
+}
+
+impl CompressedTexture {
+    // Comprueba si la ruta es de un contenedor que entiende load()
+    pub fn is_container(path: &str) -> bool {
+        let path = path.to_lowercase();
+        path.ends_with(".dds") || path.ends_with(".ktx2")
+    }
+
+    pub fn load(path: &str) -> Result<CompressedTexture, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Could not load texture {}: {}", path, e))?;
+        let texture = if bytes.starts_with(&KTX2_IDENTIFIER) {
+            CompressedTexture::parse_ktx2(&bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            CompressedTexture::parse_dds(&bytes)
+        } else {
+            Err("no es un fichero KTX2 ni DDS".to_string())
+        };
+        texture.map_err(|e| format!("Could not load texture {}: {}", path, e))
+    }
+
+
+    pub fn get_format(&self) -> CompressedFormat {
+        self.format
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.srgb
+    }
+
+    pub fn get_levels(&self) -> &Vec<Vec<u8>> {
+        &self.levels
+    }
+
+    // Dimensiones del nivel de mipmap
+    pub fn get_level_dimensions(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    fn parse_dds(bytes: &[u8]) -> Result<CompressedTexture, String> {
+        if bytes.len() < DDS_MAGIC.len() + DDS_HEADER_SIZE {
+            return Err("cabecera DDS incompleta".to_string());
+        }
+        let header = &bytes[DDS_MAGIC.len()..];
+        if read_u32(header, 0) as usize != DDS_HEADER_SIZE {
+            return Err("tamaño de cabecera DDS incorrecto".to_string());
+        }
+        let flags = read_u32(header, 4);
+        let height = read_u32(header, 8);
+        let width = read_u32(header, 12);
+        let mip_map_count = if flags & DDSD_MIPMAPCOUNT != 0 { read_u32(header, 24).max(1) } else { 1 };
+        let pixel_flags = read_u32(header, 76);
+        let four_cc = &header[80..84];
+        let caps2 = read_u32(header, 108);
+        if caps2 & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 {
+            return Err("solo se admiten texturas 2D, no cubemaps ni volúmenes".to_string());
+        }
+        check_level_count(mip_map_count, width, height)?;
+
+        let mut data_offset = DDS_MAGIC.len() + DDS_HEADER_SIZE;
+        let mut swap_red_blue = false;
+        let (format, srgb) = if pixel_flags & DDPF_FOURCC != 0 {
+            match four_cc {
+                b"DXT1" => (CompressedFormat::Bc1Alpha, false),
+                b"DXT2" | b"DXT3" => (CompressedFormat::Bc2, false),
+                b"DXT4" | b"DXT5" => (CompressedFormat::Bc3, false),
+                b"ATI1" | b"BC4U" => (CompressedFormat::Bc4, false),
+                b"ATI2" | b"BC5U" => (CompressedFormat::Bc5, false),
+                b"DX10" => {
+                    if bytes.len() < data_offset + DDS_DX10_HEADER_SIZE {
+                        return Err("cabecera DX10 incompleta".to_string());
+                    }
+                    let dxt10 = &bytes[data_offset..];
+                    // resourceDimension 3 = TEXTURE2D
+                    if read_u32(dxt10, 4) != 3 || read_u32(dxt10, 12) > 1 {
+                        return Err("solo se admiten texturas 2D sin arrays".to_string());
+                    }
+                    data_offset += DDS_DX10_HEADER_SIZE;
+                    dxgi_format(read_u32(dxt10, 0))?
+                }
+                _ => return Err(format!("FourCC {:?} no soportado",
+                                        String::from_utf8_lossy(four_cc))),
+            }
+        } else if pixel_flags & DDPF_RGB != 0 && read_u32(header, 84) == 32 {
+            // RGBA8 sin comprimir, en orden RGBA o BGRA según las máscaras
+            match (read_u32(header, 88), read_u32(header, 96)) {
+                (0x000000FF, 0x00FF0000) => (CompressedFormat::Rgba8, false),
+                (0x00FF0000, 0x000000FF) => {
+                    swap_red_blue = true;
+                    (CompressedFormat::Rgba8, false)
+                }
+                _ => return Err("máscaras de color DDS no soportadas".to_string()),
+            }
+        } else {
+            return Err("formato de pixel DDS no soportado".to_string());
+        };
+
+        // En DDS los niveles van seguidos, del más grande al más pequeño
+        let mut levels: Vec<Vec<u8>> = vec![];
+        for level in 0..mip_map_count {
+            let size = format.get_level_size((width >> level).max(1), (height >> level).max(1));
+            if bytes.len() < data_offset.saturating_add(size) {
+                return Err(format!("faltan datos del nivel de mipmap {}", level));
+            }
+            let mut data = bytes[data_offset..data_offset + size].to_vec();
+            if swap_red_blue {
+                for pixel in data.chunks_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            levels.push(data);
+            data_offset += size;
+        }
+
+        Ok(CompressedTexture { width, height, format, srgb, levels })
+    }
+
+    fn parse_ktx2(bytes: &[u8]) -> Result<CompressedTexture, String> {
+        if bytes.len() < KTX2_HEADER_SIZE {
+            return Err("cabecera KTX2 incompleta".to_string());
+        }
+        let vk_format = read_u32(bytes, 12);
+        let width = read_u32(bytes, 20);
+        let height = read_u32(bytes, 24);
+        let depth = read_u32(bytes, 28);
+        let layer_count = read_u32(bytes, 32);
+        let face_count = read_u32(bytes, 36);
+        let level_count = read_u32(bytes, 40).max(1); // 0 = el loader genera los mipmaps
+        let supercompression = read_u32(bytes, 44);
+
+        if supercompression != 0 {
+            return Err(format!("supercompresión {} no soportada (se necesita Basis/Zstd)",
+                               supercompression));
+        }
+        if depth > 1 || layer_count > 1 || face_count != 1 {
+            return Err("solo se admiten texturas 2D, no cubemaps, arrays ni volúmenes".to_string());
+        }
+        let (format, srgb) = vk_format_to_format(vk_format)?;
+        check_level_count(level_count, width, height)?;
+
+        if bytes.len() < KTX2_HEADER_SIZE + level_count as usize * KTX2_LEVEL_INDEX_SIZE {
+            return Err("índice de niveles KTX2 incompleto".to_string());
+        }
+        // En KTX2 el índice de niveles empieza por el nivel 0 (el más grande)
+        let mut levels: Vec<Vec<u8>> = vec![];
+        for level in 0..level_count as usize {
+            let entry = KTX2_HEADER_SIZE + level * KTX2_LEVEL_INDEX_SIZE;
+            let offset = read_u64(bytes, entry) as usize;
+            let length = read_u64(bytes, entry + 8) as usize;
+            let expected = format.get_level_size((width >> level).max(1), (height >> level).max(1));
+            let end = offset.checked_add(length);
+            if length < expected || end.is_none_or(|end| bytes.len() < end) {
+                return Err(format!("faltan datos del nivel de mipmap {}", level));
+            }
+            levels.push(bytes[offset..offset + expected].to_vec());
+        }
+
+        Ok(CompressedTexture { width, height, format, srgb, levels })
+    }
+}
+
+// Una cabecera rota puede pedir más niveles de los que hay (y width >> level desborda con 32 o
+// más). Con tamaño 0 no hay ningún nivel válido
+fn check_level_count(level_count: u32, width: u32, height: u32) -> Result<(), String> {
+    let max_levels = 32 - width.max(height).leading_zeros();
+    if level_count > max_levels {
+        return Err(format!("{} niveles de mipmap para {}x{}, como mucho {}", level_count, width,
+                           height, max_levels));
+    }
+    Ok(())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    bytes[offset] as u32 | (bytes[offset + 1] as u32) << 8 | (bytes[offset + 2] as u32) << 16 |
+        (bytes[offset + 3] as u32) << 24
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    read_u32(bytes, offset) as u64 | (read_u32(bytes, offset + 4) as u64) << 32
+}
+
+// DXGI_FORMAT de la cabecera DX10 de DDS
+fn dxgi_format(dxgi: u32) -> Result<(CompressedFormat, bool), String> {
+    match dxgi {
+        28 => Ok((CompressedFormat::Rgba8, false)),
+        29 => Ok((CompressedFormat::Rgba8, true)),
+        71 => Ok((CompressedFormat::Bc1Alpha, false)),
+        72 => Ok((CompressedFormat::Bc1Alpha, true)),
+        74 => Ok((CompressedFormat::Bc2, false)),
+        75 => Ok((CompressedFormat::Bc2, true)),
+        77 => Ok((CompressedFormat::Bc3, false)),
+        78 => Ok((CompressedFormat::Bc3, true)),
+        80 => Ok((CompressedFormat::Bc4, false)),
+        83 => Ok((CompressedFormat::Bc5, false)),
+        98 => Ok((CompressedFormat::Bc7, false)),
+        99 => Ok((CompressedFormat::Bc7, true)),
+        _ => Err(format!("DXGI_FORMAT {} no soportado", dxgi)),
+    }
+}
+
+// VkFormat de la cabecera KTX2
+fn vk_format_to_format(vk_format: u32) -> Result<(CompressedFormat, bool), String> {
+    match vk_format {
+        37 => Ok((CompressedFormat::Rgba8, false)),
+        43 => Ok((CompressedFormat::Rgba8, true)),
+        131 => Ok((CompressedFormat::Bc1, false)),
+        132 => Ok((CompressedFormat::Bc1, true)),
+        133 => Ok((CompressedFormat::Bc1Alpha, false)),
+        134 => Ok((CompressedFormat::Bc1Alpha, true)),
+        135 => Ok((CompressedFormat::Bc2, false)),
+        136 => Ok((CompressedFormat::Bc2, true)),
+        137 => Ok((CompressedFormat::Bc3, false)),
+        138 => Ok((CompressedFormat::Bc3, true)),
+        139 => Ok((CompressedFormat::Bc4, false)),
+        141 => Ok((CompressedFormat::Bc5, false)),
+        145 => Ok((CompressedFormat::Bc7, false)),
+        146 => Ok((CompressedFormat::Bc7, true)),
+        147 => Ok((CompressedFormat::Etc2Rgb, false)),
+        148 => Ok((CompressedFormat::Etc2Rgb, true)),
+        151 => Ok((CompressedFormat::Etc2Rgba, false)),
+        152 => Ok((CompressedFormat::Etc2Rgba, true)),
+        _ => Err(format!("VkFormat {} no soportado", vk_format)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(bytes: &mut [u8], offset: usize, value: u64) {
+        bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    // Magic y cabecera DDS con FourCC, sin datos
+    fn dds(width: u32, height: u32, mip_map_count: u32, four_cc: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = DDS_MAGIC.to_vec();
+        bytes.resize(DDS_MAGIC.len() + DDS_HEADER_SIZE, 0);
+        let header = DDS_MAGIC.len();
+        put_u32(&mut bytes, header, DDS_HEADER_SIZE as u32);
+        put_u32(&mut bytes, header + 4, DDSD_MIPMAPCOUNT);
+        put_u32(&mut bytes, header + 8, height);
+        put_u32(&mut bytes, header + 12, width);
+        put_u32(&mut bytes, header + 24, mip_map_count);
+        put_u32(&mut bytes, header + 76, DDPF_FOURCC);
+        bytes[header + 80..header + 84].copy_from_slice(four_cc);
+        bytes
+    }
+
+    // Cabecera KTX2 y el índice de niveles; cada nivel es (offset, length)
+    fn ktx2(vk_format: u32, width: u32, height: u32, levels: &[(u64, u64)]) -> Vec<u8> {
+        let mut bytes = vec![0; KTX2_HEADER_SIZE + levels.len() * KTX2_LEVEL_INDEX_SIZE];
+        bytes[0..12].copy_from_slice(&KTX2_IDENTIFIER);
+        put_u32(&mut bytes, 12, vk_format);
+        put_u32(&mut bytes, 20, width);
+        put_u32(&mut bytes, 24, height);
+        put_u32(&mut bytes, 36, 1);
+        put_u32(&mut bytes, 40, levels.len() as u32);
+        for (level, &(offset, length)) in levels.iter().enumerate() {
+            let entry = KTX2_HEADER_SIZE + level * KTX2_LEVEL_INDEX_SIZE;
+            put_u64(&mut bytes, entry, offset);
+            put_u64(&mut bytes, entry + 8, length);
+        }
+        bytes
+    }
+
+    #[test]
+    fn dds_levels_round_trip() {
+        // 8x8 BC1: 4 bloques y después un bloque por nivel hasta 1x1
+        let mut bytes = dds(8, 8, 4, b"DXT1");
+        let data: Vec<u8> = (0..56).collect();
+        bytes.extend_from_slice(&data);
+        let texture = CompressedTexture::parse_dds(&bytes).unwrap();
+        assert_eq!(texture.get_format(), CompressedFormat::Bc1Alpha);
+        assert_eq!(texture.get_level_dimensions(0), (8, 8));
+        assert_eq!(texture.get_levels(),
+                   &vec![data[0..32].to_vec(), data[32..40].to_vec(), data[40..48].to_vec(),
+                         data[48..56].to_vec()]);
+        assert_eq!(texture.get_level_dimensions(3), (1, 1));
+    }
+
+    #[test]
+    fn dds_bgra_is_swapped_to_rgba() {
+        let mut bytes = dds(1, 1, 1, b"\0\0\0\0");
+        let header = DDS_MAGIC.len();
+        put_u32(&mut bytes, header + 76, DDPF_RGB);
+        put_u32(&mut bytes, header + 84, 32);
+        put_u32(&mut bytes, header + 88, 0x00FF0000);
+        put_u32(&mut bytes, header + 96, 0x000000FF);
+        bytes.extend_from_slice(&[1, 2, 3, 4]);
+        let texture = CompressedTexture::parse_dds(&bytes).unwrap();
+        assert_eq!(texture.get_format(), CompressedFormat::Rgba8);
+        assert_eq!(texture.get_levels()[0], vec![3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn dds_rejects_malformed_headers() {
+        let valid = |mip_map_count: u32| {
+            let mut bytes = dds(8, 8, mip_map_count, b"DXT1");
+            bytes.resize(bytes.len() + 56, 0);
+            bytes
+        };
+        assert!(CompressedTexture::parse_dds(&valid(4)).is_ok());
+        // 8x8 tiene como mucho 4 niveles; con 40, width >> level desbordaría
+        assert!(CompressedTexture::parse_dds(&valid(5)).is_err());
+        assert!(CompressedTexture::parse_dds(&valid(40)).is_err());
+
+        let mut wrong_size = valid(1);
+        put_u32(&mut wrong_size, DDS_MAGIC.len(), 100);
+        assert!(CompressedTexture::parse_dds(&wrong_size).is_err());
+
+        let mut cube_map = valid(1);
+        put_u32(&mut cube_map, DDS_MAGIC.len() + 108, DDSCAPS2_CUBEMAP);
+        assert!(CompressedTexture::parse_dds(&cube_map).is_err());
+
+        let mut truncated = dds(8, 8, 4, b"DXT1");
+        truncated.resize(truncated.len() + 55, 0);
+        assert!(CompressedTexture::parse_dds(&truncated).is_err());
+
+        assert!(CompressedTexture::parse_dds(&dds(8, 8, 1, b"XXXX")).is_err());
+        assert!(CompressedTexture::parse_dds(&valid(1)[..64]).is_err());
+        // Sin tamaño no hay ningún nivel válido
+        assert!(CompressedTexture::parse_dds(&dds(0, 0, 1, b"DXT1")).is_err());
+    }
+
+    #[test]
+    fn ktx2_levels_round_trip() {
+        // 4x4 BC1 con dos niveles de un bloque cada uno, el pequeño primero en el fichero
+        let start = (KTX2_HEADER_SIZE + 2 * KTX2_LEVEL_INDEX_SIZE) as u64;
+        let mut bytes = ktx2(131, 4, 4, &[(start + 8, 8), (start, 8)]);
+        bytes.extend((0..16).map(|value| value as u8));
+        let texture = CompressedTexture::parse_ktx2(&bytes).unwrap();
+        assert_eq!(texture.get_format(), CompressedFormat::Bc1);
+        assert!(!texture.is_srgb());
+        assert_eq!(texture.get_levels(), &vec![(8..16).collect::<Vec<u8>>(),
+                                               (0..8).collect::<Vec<u8>>()]);
+    }
+
+    #[test]
+    fn ktx2_rejects_malformed_headers() {
+        let start = (KTX2_HEADER_SIZE + KTX2_LEVEL_INDEX_SIZE) as u64;
+        let with_data = |mut bytes: Vec<u8>| {
+            bytes.resize(bytes.len() + 8, 0);
+            bytes
+        };
+        assert!(CompressedTexture::parse_ktx2(&with_data(ktx2(131, 4, 4, &[(start, 8)]))).is_ok());
+        // offset + length desborda
+        let overflow = with_data(ktx2(131, 4, 4, &[(u64::MAX - 4, 8)]));
+        assert!(CompressedTexture::parse_ktx2(&overflow).is_err());
+        // Nivel más corto de lo que ocupa el bloque
+        assert!(CompressedTexture::parse_ktx2(&with_data(ktx2(131, 4, 4, &[(start, 4)]))).is_err());
+        // Formato desconocido
+        assert!(CompressedTexture::parse_ktx2(&with_data(ktx2(1, 4, 4, &[(start, 8)]))).is_err());
+
+        let mut too_many_levels = with_data(ktx2(131, 4, 4, &[(start, 8)]));
+        put_u32(&mut too_many_levels, 40, 40);
+        assert!(CompressedTexture::parse_ktx2(&too_many_levels).is_err());
+
+        let mut cube_map = with_data(ktx2(131, 4, 4, &[(start, 8)]));
+        put_u32(&mut cube_map, 36, 6);
+        assert!(CompressedTexture::parse_ktx2(&cube_map).is_err());
+
+        let mut supercompressed = with_data(ktx2(131, 4, 4, &[(start, 8)]));
+        put_u32(&mut supercompressed, 44, 1);
+        assert!(CompressedTexture::parse_ktx2(&supercompressed).is_err());
+
+        assert!(CompressedTexture::parse_ktx2(&ktx2(131, 4, 4, &[(start, 8)])[..90]).is_err());
+    }
+
+    #[test]
+    fn level_sizes_round_up_to_whole_blocks() {
+        assert_eq!(CompressedFormat::Bc1.get_level_size(1, 1), 8);
+        assert_eq!(CompressedFormat::Bc3.get_level_size(5, 9), 2 * 3 * 16);
+        assert_eq!(CompressedFormat::Rgba8.get_level_size(3, 2), 24);
+        // Sin desbordar con tamaños absurdos
+        assert!(CompressedFormat::Bc1.get_level_size(u32::MAX, u32::MAX) > 0);
+    }
+}
@@ -1,4 +1,7 @@
+pub mod block_decoder;
+pub mod compressed_texture;
 pub mod model_texture;
 pub mod terrain_texture;
 pub mod terrain_texture_pack;
-pub mod texture_data;
\ No newline at end of file
+pub mod texture_data;
+pub mod texture_options;
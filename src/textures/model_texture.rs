@@ -1,3 +1,5 @@
+type V3CG = cgmath::Vector3<f32>;
+
 #[derive(Debug, Clone, Copy)]
 pub struct ModelTexture {
     texture_id: u32,
@@ -6,6 +8,7 @@ pub struct ModelTexture {
     has_transparency: bool,
     use_fake_lighting: bool,
     number_of_rows: i32, // Para atlas de textura
+    colour: V3CG,        // Multiplica a la textura en el shader (uniform modelColour)
 }
 
 impl ModelTexture {
@@ -17,6 +20,7 @@ impl ModelTexture {
             has_transparency: false,//Transparencia
             use_fake_lighting: false,//Mala iluminación
             number_of_rows: 1,// Numero de filas del fichero atlas de textura
+            colour: cgmath::vec3(1.0, 1.0, 1.0),
         }
     }
     pub fn get_number_of_rows(&self) -> i32 {
@@ -49,7 +53,7 @@ impl ModelTexture {
         self.shine_damper
     }
 
-    pub fn _set_shine_damper(&mut self, shine_damper: f32) {
+    pub fn set_shine_damper(&mut self, shine_damper: f32) {
         self.shine_damper = shine_damper;
     }
 
@@ -57,7 +61,15 @@ impl ModelTexture {
         self.reflectivity
     }
 
-    pub fn _set_reflectivity(&mut self, reflectivity: f32) {
+    pub fn set_reflectivity(&mut self, reflectivity: f32) {
         self.reflectivity = reflectivity;
     }
+
+    pub fn get_colour(&self) -> V3CG {
+        self.colour
+    }
+
+    pub fn set_colour(&mut self, colour: V3CG) {
+        self.colour = colour;
+    }
 }
\ No newline at end of file
@@ -24,4 +24,15 @@ impl TextureData {
     pub fn get_buffer(&self) -> Vec<u8> {
         self.buffer.clone()
     }
+
+    // Sin copiar el buffer
+    pub fn get_pixels(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+
+    // Bytes por pixel: 1 gris, 2 gris+alfa, 3 RGB, 4 RGBA
+    pub fn get_channels(&self) -> usize {
+        let pixels = self.width as usize * self.height as usize;
+        self.buffer.len().checked_div(pixels).unwrap_or(0)
+    }
 }
\ No newline at end of file
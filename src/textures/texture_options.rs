@@ -0,0 +1,103 @@
+use gl;
+use gl::types::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureWrap {
+    Repeat,
+    #[allow(dead_code)] // ninguna textura del juego lo usa todavía
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+impl TextureWrap {
+    pub fn to_gl(self) -> GLint {
+        match self {
+            TextureWrap::Repeat => gl::REPEAT as GLint,
+            TextureWrap::MirroredRepeat => gl::MIRRORED_REPEAT as GLint,
+            TextureWrap::ClampToEdge => gl::CLAMP_TO_EDGE as GLint,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFilter {
+    Nearest,
+    // Lineal sin mipmaps
+    Bilinear,
+    // Lineal entre mipmaps, los mipmaps se generan al cargar
+    Trilinear,
+}
+
+// Opciones de importación de Loader::load_texture_with_options
+#[derive(Debug, Clone, Copy)]
+pub struct TextureOptions {
+    srgb: bool,
+    premultiply_alpha: bool,
+    wrap: TextureWrap,
+    filter: TextureFilter,
+    lod_bias: f32,
+    anisotropy: f32, // 1.0 = sin filtrado anisótropo, se limita al máximo del driver
+}
+
+impl TextureOptions {
+    // Lo que hacía load_texture: repeat, trilinear y bias -0.4, espacio de color lineal
+    pub fn new() -> TextureOptions {
+        TextureOptions {
+            srgb: false,
+            premultiply_alpha: false,
+            wrap: TextureWrap::Repeat,
+            filter: TextureFilter::Trilinear,
+            lod_bias: -0.4,
+            anisotropy: 1.0,
+        }
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        self.srgb
+    }
+
+    pub fn _set_srgb(&mut self, srgb: bool) {
+        self.srgb = srgb;
+    }
+
+    pub fn is_premultiply_alpha(&self) -> bool {
+        self.premultiply_alpha
+    }
+
+    // Con KTX2/DDS comprimidos obliga a descomprimir en CPU y subir la textura como RGBA8
+    pub fn _set_premultiply_alpha(&mut self, premultiply_alpha: bool) {
+        self.premultiply_alpha = premultiply_alpha;
+    }
+
+    pub fn get_wrap(&self) -> TextureWrap {
+        self.wrap
+    }
+
+    pub fn set_wrap(&mut self, wrap: TextureWrap) {
+        self.wrap = wrap;
+    }
+
+    pub fn get_filter(&self) -> TextureFilter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: TextureFilter) {
+        self.filter = filter;
+    }
+
+    pub fn get_lod_bias(&self) -> f32 {
+        self.lod_bias
+    }
+
+    pub fn set_lod_bias(&mut self, lod_bias: f32) {
+        self.lod_bias = lod_bias;
+    }
+
+    pub fn get_anisotropy(&self) -> f32 {
+        self.anisotropy
+    }
+
+    pub fn set_anisotropy(&mut self, anisotropy: f32) {
+        self.anisotropy = anisotropy;
+    }
+}
@@ -0,0 +1,238 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::obj_converter::model_data::ModelData;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlyFormat {
+    #[allow(dead_code)] // para mirar el fichero a mano; el juego exporta en binario
+    Ascii,
+    BinaryLittleEndian,
+}
+
+// Escribe la malla como OBJ: posiciones, uv y normales, con el mismo índice para los tres en
+// cada esquina. La v de las uv se vuelve a invertir, así OBJFileLoader lee la misma malla
+pub fn export_obj(data: &ModelData, path: &str) -> Result<(), String> {
+    check_data(data).map_err(|e| format!("Could not export mesh {}: {}", path, e))?;
+    write_file(path, |writer| {
+        writeln!(writer, "# {} vertices, {} triangles", data.get_vertices().len() / 3,
+                 data.get_indices().len() / 3)?;
+        for p in data.get_vertices().chunks(3) {
+            writeln!(writer, "v {} {} {}", p[0], p[1], p[2])?;
+        }
+        for uv in data.get_texture_coords().chunks(2) {
+            writeln!(writer, "vt {} {}", uv[0], 1.0 - uv[1])?;
+        }
+        for n in data.get_normals().chunks(3) {
+            writeln!(writer, "vn {} {} {}", n[0], n[1], n[2])?;
+        }
+        // Sin smoothing groups el loader no recalcula nada: las normales ya vienen en vn
+        for triangle in data.get_indices().chunks(3) {
+            let (a, b, c) = (triangle[0] + 1, triangle[1] + 1, triangle[2] + 1);
+            writeln!(writer, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c)?;
+        }
+        Ok(())
+    })
+}
+
+// Escribe la malla como PLY con posición, normal y uv (s, t) por vértice y caras de 3 índices
+pub fn export_ply(data: &ModelData, path: &str, format: PlyFormat) -> Result<(), String> {
+    check_data(data).map_err(|e| format!("Could not export mesh {}: {}", path, e))?;
+    let vertex_count = data.get_vertices().len() / 3;
+    let triangle_count = data.get_indices().len() / 3;
+    write_file(path, |writer| {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format {} 1.0", match format {
+            PlyFormat::Ascii => "ascii",
+            PlyFormat::BinaryLittleEndian => "binary_little_endian",
+        })?;
+        writeln!(writer, "element vertex {}", vertex_count)?;
+        for property in ["x", "y", "z", "nx", "ny", "nz", "s", "t"].iter() {
+            writeln!(writer, "property float {}", property)?;
+        }
+        writeln!(writer, "element face {}", triangle_count)?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        for v in 0..vertex_count {
+            let p = &data.get_vertices()[v * 3..v * 3 + 3];
+            let n = &data.get_normals()[v * 3..v * 3 + 3];
+            let uv = &data.get_texture_coords()[v * 2..v * 2 + 2];
+            let values = [p[0], p[1], p[2], n[0], n[1], n[2], uv[0], 1.0 - uv[1]];
+            match format {
+                PlyFormat::Ascii => {
+                    let line: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                    writeln!(writer, "{}", line.join(" "))?;
+                }
+                PlyFormat::BinaryLittleEndian => {
+                    for value in values.iter() {
+                        writer.write_all(&value.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        for triangle in data.get_indices().chunks(3) {
+            match format {
+                PlyFormat::Ascii => {
+                    writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+                }
+                PlyFormat::BinaryLittleEndian => {
+                    writer.write_all(&[3])?;
+                    for index in triangle {
+                        writer.write_all(&index.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+// Las dos exportaciones escriben una normal y una uv por vértice y triángulos completos
+fn check_data(data: &ModelData) -> Result<(), String> {
+    let vertex_count = data.get_vertices().len() / 3;
+    if !data.get_vertices().len().is_multiple_of(3) {
+        return Err(format!("{} floats de posición, no es múltiplo de 3",
+                           data.get_vertices().len()));
+    }
+    if data.get_normals().len() != vertex_count * 3 {
+        return Err(format!("{} floats de normales para {} vértices",
+                           data.get_normals().len(), vertex_count));
+    }
+    if data.get_texture_coords().len() != vertex_count * 2 {
+        return Err(format!("{} floats de uv para {} vértices",
+                           data.get_texture_coords().len(), vertex_count));
+    }
+    if !data.get_indices().len().is_multiple_of(3) {
+        return Err(format!("{} índices, no es múltiplo de 3", data.get_indices().len()));
+    }
+    match data.get_indices().iter().find(|&&index| index as usize >= vertex_count) {
+        Some(index) => Err(format!("índice {} fuera de rango (hay {} vértices)",
+                                   index, vertex_count)),
+        None => Ok(()),
+    }
+}
+
+fn write_file<F>(path: &str, write: F) -> Result<(), String>
+    where F: FnOnce(&mut BufWriter<File>) -> io::Result<()> {
+    File::create(path)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()
+        })
+        .map_err(|e| format!("Could not write mesh {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    use crate::obj_converter::model_data::test_grid;
+    use crate::obj_converter::obj_file_loader::OBJFileLoader;
+
+    fn temp_path(name: &str) -> String {
+        let name = format!("mesh_exporter_{}_{}", std::process::id(), name);
+        env::temp_dir().join(name).to_string_lossy().into_owned()
+    }
+
+    // La rejilla de los tests con una normal distinta en cada vértice, para que se note si el
+    // loader las recalcula o las cambia de vértice
+    fn curved_grid() -> ModelData {
+        let grid = test_grid(3, 0.5);
+        let normals: Vec<f32> = grid.get_vertices().chunks(3).flat_map(|p| {
+            let length = (p[0] * p[0] + 1.0 + p[2] * p[2]).sqrt();
+            vec![-p[0] / length, 1.0 / length, -p[2] / length]
+        }).collect();
+        ModelData::new(grid.get_vertices().clone(), grid.get_texture_coords().clone(), normals,
+                       grid.get_indices().clone(), 0.0)
+    }
+
+    fn corner(data: &ModelData, index: u32) -> Vec<f32> {
+        let i = index as usize;
+        let mut corner = data.get_vertices()[i * 3..i * 3 + 3].to_vec();
+        corner.extend_from_slice(&data.get_texture_coords()[i * 2..i * 2 + 2]);
+        corner.extend_from_slice(&data.get_normals()[i * 3..i * 3 + 3]);
+        corner
+    }
+
+    // Triángulos como listas de esquinas (posición, uv, normal), ordenados para comparar mallas
+    // aunque el loader haya reordenado vértices y triángulos
+    fn triangles(data: &ModelData) -> Vec<Vec<Vec<f32>>> {
+        let mut triangles: Vec<Vec<Vec<f32>>> = data.get_indices().chunks(3)
+            .map(|triangle| triangle.iter().map(|&index| corner(data, index)).collect())
+            .collect();
+        triangles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        triangles
+    }
+
+    #[test]
+    fn obj_round_trip_keeps_positions_uvs_and_normals() {
+        let data = curved_grid();
+        let path = temp_path("round_trip.obj");
+        export_obj(&data, &path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let loaded = OBJFileLoader::load_obj(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // En el fichero la v va invertida: la uv (0, 0) del primer vértice se escribe como (0, 1)
+        assert_eq!(text.lines().find(|line| line.starts_with("vt ")), Some("vt 0 1"));
+        assert_eq!(loaded.get_vertices().len(), data.get_vertices().len());
+        let (expected, actual) = (triangles(&data), triangles(&loaded));
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().flatten().flatten().zip(expected.iter().flatten().flatten()) {
+            assert!((a - e).abs() < 1e-6, "{} != {}", a, e);
+        }
+    }
+
+    #[test]
+    fn ply_header_and_size_match_both_formats() {
+        let data = test_grid(3, 0.5);
+        let (vertex_count, triangle_count) = (9, 8);
+        let header = ["ply", "", "element vertex 9", "property float x", "property float y",
+            "property float z", "property float nx", "property float ny", "property float nz",
+            "property float s", "property float t", "element face 8",
+            "property list uchar uint vertex_indices", "end_header"];
+
+        for &(format, name) in [(PlyFormat::Ascii, "ascii"),
+                                (PlyFormat::BinaryLittleEndian, "binary_little_endian")].iter() {
+            let path = temp_path(&format!("{}.ply", name));
+            export_ply(&data, &path, format).unwrap();
+            let bytes = fs::read(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            let end = b"end_header\n";
+            let header_size = bytes.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+            let text = String::from_utf8(bytes[..header_size].to_vec()).unwrap();
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(lines.len(), header.len());
+            assert_eq!(lines[1], format!("format {} 1.0", name));
+            for (line, expected) in lines.iter().zip(header.iter()).filter(|(_, e)| !e.is_empty()) {
+                assert_eq!(line, expected);
+            }
+
+            let body = &bytes[header_size..];
+            match format {
+                PlyFormat::Ascii => {
+                    let body = String::from_utf8(body.to_vec()).unwrap();
+                    let lines: Vec<&str> = body.lines().collect();
+                    assert_eq!(lines.len(), vertex_count + triangle_count);
+                    // Primer vértice: posición, normal y uv con la t invertida
+                    assert_eq!(lines[0], "0 0 0 0 1 0 0 1");
+                    assert_eq!(lines[vertex_count], "3 0 1 3");
+                }
+                PlyFormat::BinaryLittleEndian => {
+                    // 8 floats por vértice; por cara el contador y tres índices de 32 bits
+                    assert_eq!(body.len(), vertex_count * 8 * 4 + triangle_count * (1 + 3 * 4));
+                    let t = f32::from_le_bytes([body[28], body[29], body[30], body[31]]);
+                    assert_eq!(t, 1.0);
+                    let face = &body[vertex_count * 32..vertex_count * 32 + 13];
+                    assert_eq!(face[0], 3);
+                    assert_eq!(u32::from_le_bytes([face[9], face[10], face[11], face[12]]), 3);
+                }
+            }
+        }
+    }
+}
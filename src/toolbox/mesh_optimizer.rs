@@ -0,0 +1,403 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::obj_converter::model_data::ModelData;
+
+// Tamaño de la caché LRU que modela la ordenación de Forsyth
+const FORSYTH_CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+// Caché FIFO con la que se mide el ACMR, parecida a la de las GPUs actuales
+pub const ACMR_CACHE_SIZE: usize = 16;
+// Cuánto puede empeorar el ACMR al reordenar para el overdraw (1.05 = un 5%)
+pub const OVERDRAW_THRESHOLD: f32 = 1.05;
+
+// ACMR (fallos de caché de vértices por triángulo) antes y después de optimizar. Va de 0.5
+// (imposible de mejorar) a 3 (ningún vértice reaprovechado)
+#[derive(Debug, Clone, Copy)]
+pub struct OptimizationReport {
+    acmr_before: f32,
+    acmr_after: f32,
+}
+
+impl OptimizationReport {
+    pub fn _get_acmr_before(&self) -> f32 {
+        self.acmr_before
+    }
+
+    pub fn _get_acmr_after(&self) -> f32 {
+        self.acmr_after
+    }
+}
+
+impl fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ACMR {:.3} -> {:.3}", self.acmr_before, self.acmr_after)
+    }
+}
+
+// Reordena los triángulos para la caché de vértices y, si overdraw, por clusters de fuera a
+// dentro; después reordena los vértices en el orden en que se usan
+pub fn optimize(data: &ModelData, overdraw: bool) -> (ModelData, OptimizationReport) {
+    let vertex_count = data.get_vertices().len() / 3;
+    let acmr_before = calculate_acmr(data.get_indices(), vertex_count, ACMR_CACHE_SIZE);
+    let mut indices = optimize_vertex_cache(data.get_indices(), vertex_count);
+    if overdraw {
+        indices = optimize_overdraw(&indices, data.get_vertices(), OVERDRAW_THRESHOLD);
+    }
+    let acmr_after = calculate_acmr(&indices, vertex_count, ACMR_CACHE_SIZE);
+    (reorder_vertices(data, &indices), OptimizationReport { acmr_before, acmr_after })
+}
+
+// Fallos de una caché FIFO de cache_size vértices por triángulo
+pub fn calculate_acmr(indices: &[u32], vertex_count: usize, cache_size: usize) -> f32 {
+    if indices.len() < 3 {
+        return 0.0;
+    }
+    // Momento en que entró cada vértice en la caché; está dentro si entró hace menos de
+    // cache_size fallos
+    let mut timestamps: Vec<usize> = vec![0; vertex_count];
+    let mut misses = 0;
+    for &index in indices {
+        let entered = timestamps[index as usize];
+        if entered == 0 || misses + 1 - entered > cache_size {
+            misses += 1;
+            timestamps[index as usize] = misses;
+        }
+    }
+    misses as f32 / (indices.len() / 3) as f32
+}
+
+// Ordenación de triángulos de Tom Forsyth ("Linear-Speed Vertex Cache Optimisation"): cada
+// vértice puntúa por su posición en una caché LRU simulada y por los triángulos que le quedan,
+// y se emite el triángulo de mayor puntuación entre los de los vértices en caché
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    let mut vertex_triangles: Vec<Vec<usize>> = vec![vec![]; vertex_count];
+    for (corner, &index) in indices.iter().enumerate() {
+        vertex_triangles[index as usize].push(corner / 3);
+    }
+    let mut vertex_scores: Vec<f32> = vertex_triangles.iter()
+        .map(|triangles| vertex_score(None, triangles.len()))
+        .collect();
+    let mut emitted = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(triangle_count * 3);
+    // Sin candidatos en la caché se sigue por el primer triángulo pendiente en orden original
+    let mut cursor = 0;
+    let mut best: Option<usize> = None;
+
+    for _ in 0..triangle_count {
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                while emitted[cursor] {
+                    cursor += 1;
+                }
+                cursor
+            }
+        };
+        emitted[triangle] = true;
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        output.extend_from_slice(corners);
+
+        for &vertex in corners {
+            let triangles = &mut vertex_triangles[vertex as usize];
+            if let Some(position) = triangles.iter().position(|&t| t == triangle) {
+                triangles.swap_remove(position);
+            }
+        }
+        // Los vértices del triángulo pasan al principio de la caché
+        let mut new_cache: Vec<u32> = corners.to_vec();
+        new_cache.extend(cache.iter().filter(|vertex| !corners.contains(vertex)));
+
+        for (position, &vertex) in new_cache.iter().enumerate() {
+            let in_cache = if position < FORSYTH_CACHE_SIZE { Some(position) } else { None };
+            vertex_scores[vertex as usize] =
+                vertex_score(in_cache, vertex_triangles[vertex as usize].len());
+        }
+        best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for &vertex in new_cache.iter() {
+            for &t in vertex_triangles[vertex as usize].iter() {
+                let score: f32 = indices[t * 3..t * 3 + 3].iter()
+                    .map(|&v| vertex_scores[v as usize])
+                    .sum();
+                if score > best_score {
+                    best_score = score;
+                    best = Some(t);
+                }
+            }
+        }
+        new_cache.truncate(FORSYTH_CACHE_SIZE);
+        cache = new_cache;
+    }
+    output
+}
+
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: usize) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        // Los tres del último triángulo puntúan igual, da igual el orden en que se emitieron
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (FORSYTH_CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+        None => 0.0,
+    };
+    // Los vértices con pocos triángulos pendientes se priorizan para no dejarlos sueltos
+    cache_score + VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+// Reordenación de Sander, Nehab y Barczak ("Fast Triangle Reordering for Vertex Locality and
+// Reduced Overdraw"): parte la lista ya optimizada para la caché en clusters y los dibuja de
+// los más exteriores y orientados hacia fuera a los interiores, para que el test de
+// profundidad descarte más fragmentos. threshold limita cuánto puede empeorar el ACMR
+pub fn optimize_overdraw(indices: &[u32], positions: &[f32], threshold: f32) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return indices.to_vec();
+    }
+    let vertex_count = positions.len() / 3;
+    let clusters = split_clusters(indices, vertex_count, threshold);
+
+    let point = |index: u32| {
+        let p = &positions[index as usize * 3..index as usize * 3 + 3];
+        [p[0], p[1], p[2]]
+    };
+    // Centroide de la malla ponderado por área
+    let mut mesh_center = [0.0f32; 3];
+    let mut mesh_area = 0.0;
+    let mut cluster_data: Vec<([f32; 3], [f32; 3], f32)> = vec![];
+    for (cluster, &start) in clusters.iter().enumerate() {
+        let end = clusters.get(cluster + 1).cloned().unwrap_or(triangle_count);
+        let mut center = [0.0f32; 3];
+        let mut normal = [0.0f32; 3];
+        let mut area = 0.0;
+        for t in start..end {
+            let (a, b, c) = (point(indices[t * 3]), point(indices[t * 3 + 1]),
+                             point(indices[t * 3 + 2]));
+            let n = cross(sub(b, a), sub(c, a));
+            let triangle_area = length(n) * 0.5;
+            for axis in 0..3 {
+                center[axis] += (a[axis] + b[axis] + c[axis]) / 3.0 * triangle_area;
+                normal[axis] += n[axis];
+            }
+            area += triangle_area;
+        }
+        for (total, value) in mesh_center.iter_mut().zip(center.iter()) {
+            *total += value;
+        }
+        mesh_area += area;
+        cluster_data.push((center, normal, area));
+    }
+    if mesh_area > 0.0 {
+        for value in mesh_center.iter_mut() {
+            *value /= mesh_area;
+        }
+    }
+
+    // Clusters con la normal apuntando en dirección contraria al centro primero
+    let mut sort_keys: Vec<(f32, usize)> = cluster_data.iter().enumerate()
+        .map(|(c, &(center, normal, area))| {
+            let normal_length = length(normal);
+            if area <= 0.0 || normal_length <= 0.0 {
+                return (0.0, c);
+            }
+            let offset = sub([center[0] / area, center[1] / area, center[2] / area], mesh_center);
+            (dot(offset, normal) / normal_length, c)
+        })
+        .collect();
+    sort_keys.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    let mut output = Vec::with_capacity(indices.len());
+    for (_, c) in sort_keys {
+        let end = clusters.get(c + 1).cloned().unwrap_or(triangle_count);
+        output.extend_from_slice(&indices[clusters[c] * 3..end * 3]);
+    }
+    output
+}
+
+// Primer triángulo de cada cluster. La caché se simula vacía al principio de cada cluster,
+// porque después de reordenarlos no se sabe qué cluster irá delante. Un cluster termina donde
+// los tres vértices fallan (ahí la caché ya estaba fría) o donde su ACMR no supera threshold
+// veces el de toda la malla
+fn split_clusters(indices: &[u32], vertex_count: usize, threshold: f32) -> Vec<usize> {
+    let triangle_count = indices.len() / 3;
+    let target = calculate_acmr(indices, vertex_count, ACMR_CACHE_SIZE) * threshold;
+    let mut timestamps: Vec<usize> = vec![0; vertex_count];
+    let mut total_misses = 0;
+    // Fallos al empezar el cluster, lo que entró antes ya no está en la caché
+    let mut flush = 0;
+    let mut clusters = vec![0];
+    let mut cluster_start = 0;
+    for t in 0..triangle_count {
+        let mut triangle_misses = 0;
+        for &index in indices[t * 3..t * 3 + 3].iter() {
+            let entered = timestamps[index as usize];
+            if entered <= flush || total_misses + 1 - entered > ACMR_CACHE_SIZE {
+                total_misses += 1;
+                triangle_misses += 1;
+                timestamps[index as usize] = total_misses;
+            }
+        }
+        if t > cluster_start && triangle_misses == 3 {
+            clusters.push(t);
+            cluster_start = t;
+            flush = total_misses - 3;
+        }
+        let cluster_triangles = t + 1 - cluster_start;
+        let cluster_acmr = (total_misses - flush) as f32 / cluster_triangles as f32;
+        if t + 1 < triangle_count && cluster_triangles > 1 && cluster_acmr <= target {
+            clusters.push(t + 1);
+            cluster_start = t + 1;
+            flush = total_misses;
+        }
+    }
+    clusters
+}
+
+// Vértices en el orden en que los usan los índices, así las lecturas de los VBOs son
+// secuenciales. Se quitan los vértices que no usa ningún triángulo
+pub fn _optimize_vertex_fetch(data: &ModelData) -> ModelData {
+    reorder_vertices(data, data.get_indices())
+}
+
+fn reorder_vertices(data: &ModelData, indices: &[u32]) -> ModelData {
+    let has_tangents = data.has_tangents();
+    let mut remap: Vec<Option<u32>> = vec![None; data.get_vertices().len() / 3];
+    let mut vertices = Vec::with_capacity(data.get_vertices().len());
+    let mut texture_coords = Vec::with_capacity(data.get_texture_coords().len());
+    let mut normals = Vec::with_capacity(data.get_normals().len());
+    let mut tangents = Vec::with_capacity(data.get_tangents().len());
+    let new_indices = indices.iter().map(|&index| {
+        let v = index as usize;
+        *remap[v].get_or_insert_with(|| {
+            vertices.extend_from_slice(&data.get_vertices()[v * 3..v * 3 + 3]);
+            texture_coords.extend_from_slice(&data.get_texture_coords()[v * 2..v * 2 + 2]);
+            normals.extend_from_slice(&data.get_normals()[v * 3..v * 3 + 3]);
+            if has_tangents {
+                tangents.extend_from_slice(&data.get_tangents()[v * 4..v * 4 + 4]);
+            }
+            (vertices.len() / 3 - 1) as u32
+        })
+    }).collect();
+    ModelData::with_tangents(vertices, texture_coords, normals, tangents, new_indices,
+                             data.get_furthest_point())
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn length(v: [f32; 3]) -> f32 {
+    dot(v, v).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::obj_converter::model_data::test_grid;
+
+    // Rejilla de size x size vértices con los triángulos desordenados
+    fn scrambled_grid(size: usize) -> ModelData {
+        let grid = test_grid(size, 0.01);
+        let triangles: Vec<&[u32]> = grid.get_indices().chunks(3).collect();
+        let count = triangles.len();
+        let indices = (0..count).flat_map(|i| triangles[i * 7919 % count].to_vec()).collect();
+        ModelData::new(grid.get_vertices().clone(), grid.get_texture_coords().clone(),
+                       grid.get_normals().clone(), indices, 0.0)
+    }
+
+    // Triángulos como posiciones, girados para empezar por la menor y ordenados, para comparar
+    // mallas con distinto orden de vértices y de triángulos sin perder el sentido de giro
+    fn triangle_set(data: &ModelData) -> Vec<Vec<[u32; 3]>> {
+        let position = |index: u32| {
+            let p = &data.get_vertices()[index as usize * 3..index as usize * 3 + 3];
+            [p[0].to_bits(), p[1].to_bits(), p[2].to_bits()]
+        };
+        let mut set: Vec<Vec<[u32; 3]>> = data.get_indices().chunks(3).map(|triangle| {
+            let mut corners: Vec<[u32; 3]> = triangle.iter().map(|&index| position(index)).collect();
+            let first = (0..3).min_by_key(|&i| corners[i]).unwrap();
+            corners.rotate_left(first);
+            corners
+        }).collect();
+        set.sort();
+        set
+    }
+
+    #[test]
+    fn acmr_of_known_orders() {
+        assert_eq!(calculate_acmr(&[0, 1], 2, 16), 0.0);
+        assert_eq!(calculate_acmr(&[0, 1, 2], 3, 16), 3.0);
+        assert_eq!(calculate_acmr(&[0, 1, 2, 2, 1, 3], 4, 16), 2.0);
+        assert_eq!(calculate_acmr(&[0, 1, 2, 0, 1, 2], 3, 16), 1.5);
+        // Con 3 entradas la caché FIFO ya ha echado al primer triángulo cuando vuelve
+        let indices = [0, 1, 2, 3, 4, 5, 0, 1, 2];
+        assert_eq!(calculate_acmr(&indices, 6, 16), 2.0);
+        assert_eq!(calculate_acmr(&indices, 6, 3), 3.0);
+    }
+
+    #[test]
+    fn vertex_cache_order_keeps_the_triangles() {
+        let data = scrambled_grid(20);
+        let vertex_count = data.get_vertices().len() / 3;
+        let indices = optimize_vertex_cache(data.get_indices(), vertex_count);
+        let mut before: Vec<[u32; 3]> = data.get_indices().chunks(3)
+            .map(|t| { let mut t = [t[0], t[1], t[2]]; t.sort(); t }).collect();
+        let mut after: Vec<[u32; 3]> = indices.chunks(3)
+            .map(|t| { let mut t = [t[0], t[1], t[2]]; t.sort(); t }).collect();
+        before.sort();
+        after.sort();
+        assert_eq!(before, after);
+        let acmr_before = calculate_acmr(data.get_indices(), vertex_count, ACMR_CACHE_SIZE);
+        let acmr_after = calculate_acmr(&indices, vertex_count, ACMR_CACHE_SIZE);
+        assert!(acmr_after < acmr_before);
+        assert!(acmr_after < 1.0);
+    }
+
+    #[test]
+    fn optimize_reports_and_keeps_the_mesh() {
+        let data = scrambled_grid(20);
+        for &overdraw in &[false, true] {
+            let (optimized, report) = optimize(&data, overdraw);
+            assert!(report._get_acmr_after() < report._get_acmr_before());
+            assert_eq!(triangle_set(&optimized), triangle_set(&data));
+            // Los vértices quedan en el orden en que se usan
+            let mut next = 0;
+            for &index in optimized.get_indices() {
+                assert!(index <= next);
+                if index == next {
+                    next += 1;
+                }
+            }
+            assert_eq!(next as usize, optimized.get_vertices().len() / 3);
+        }
+    }
+
+    #[test]
+    fn vertex_fetch_drops_unused_vertices() {
+        let mut vertices = vec![9.0; 3];
+        vertices.extend_from_slice(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let data = ModelData::new(vertices, vec![0.0; 8], vec![0.0; 12], vec![3, 1, 2], 0.0);
+        let fetched = _optimize_vertex_fetch(&data);
+        assert_eq!(fetched.get_indices(), &[0, 1, 2]);
+        assert_eq!(fetched.get_vertices(), &[0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(fetched.get_texture_coords().len(), 6);
+        assert_eq!(fetched.get_normals().len(), 9);
+    }
+}
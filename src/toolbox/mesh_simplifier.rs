@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+
+use crate::obj_converter::model_data::ModelData;
+
+// Peso de los planos que sujetan los bordes abiertos, para que no se encojan
+const BOUNDARY_WEIGHT: f64 = 10.0;
+// Coseno mínimo entre la normal de un triángulo antes y después de un colapso
+const MIN_NORMAL_DOT: f64 = 0.2;
+
+// Cuádrica de error (Garland-Heckbert): matriz simétrica 4x4 guardada como
+// a², ab, ac, ad, b², bc, bd, c², cd, d²
+type Quadric = [f64; 10];
+type Point = [f64; 3];
+
+// Simplifica la malla por colapso de aristas con error cuadrático hasta dejar ratio * triángulos
+// (o hasta que no se pueda colapsar nada más sin romperla). Los vértices solo se mueven a la
+// posición de otro vértice, así que uv, normales y tangentes se conservan sin interpolar. Las
+// copias de un vértice con la misma posición y distintos atributos (costuras, normales planas)
+// se colapsan juntas
+pub fn simplify(data: &ModelData, ratio: f32) -> ModelData {
+    let mut simplifier = Simplifier::new(data);
+    let triangle_count = data.get_indices().len() / 3;
+    // Al menos un triángulo, un nivel de detalle vacío no se vería
+    let target = ((triangle_count as f32 * ratio.clamp(0.0, 1.0)).round() as usize).max(1);
+    simplifier.run(target);
+    simplifier.build(data)
+}
+
+// Una malla por ratio (respecto a la original, de mayor a menor). Cada nivel se simplifica a
+// partir del anterior, que es más rápido
+pub fn build_lod_chain(data: &ModelData, ratios: &[f32]) -> Vec<ModelData> {
+    let mut levels: Vec<ModelData> = vec![];
+    let mut previous_ratio = 1.0;
+    for &ratio in ratios {
+        let level = match levels.last() {
+            Some(previous) => simplify(previous, ratio / previous_ratio),
+            None => simplify(data, ratio),
+        };
+        levels.push(level);
+        previous_ratio = ratio.max(f32::EPSILON);
+    }
+    levels
+}
+
+struct Simplifier {
+    points: Vec<Point>,             // posiciones únicas
+    position_of: Vec<usize>,        // posición de cada vértice
+    attributes_of: Vec<usize>,      // vértice original del que copia uv, normal y tangente
+    triangles: Vec<[u32; 3]>,       // índices de vértice
+    alive: Vec<bool>,
+    alive_count: usize,
+    position_triangles: Vec<Vec<usize>>, // puede tener triángulos que ya no usan la posición
+    position_alive: Vec<bool>,
+    quadrics: Vec<Quadric>,
+}
+
+impl Simplifier {
+    fn new(data: &ModelData) -> Simplifier {
+        // Vértices con la misma posición (costuras de uv o normales) comparten cuádrica
+        let vertices = data.get_vertices();
+        let mut welded: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut points: Vec<Point> = vec![];
+        let position_of: Vec<usize> = vertices.chunks(3).map(|p| {
+            let key = [p[0].to_bits(), p[1].to_bits(), p[2].to_bits()];
+            *welded.entry(key).or_insert_with(|| {
+                points.push([p[0] as f64, p[1] as f64, p[2] as f64]);
+                points.len() - 1
+            })
+        }).collect();
+
+        let triangles: Vec<[u32; 3]> = data.get_indices().chunks(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        let mut simplifier = Simplifier {
+            position_triangles: vec![vec![]; points.len()],
+            position_alive: vec![true; points.len()],
+            quadrics: vec![[0.0; 10]; points.len()],
+            alive: vec![true; triangles.len()],
+            alive_count: triangles.len(),
+            attributes_of: (0..position_of.len()).collect(),
+            points,
+            position_of,
+            triangles,
+        };
+
+        let mut edges: HashMap<(usize, usize), (usize, u32)> = HashMap::new();
+        for t in 0..simplifier.triangles.len() {
+            let p = simplifier.get_positions(t);
+            if p[0] == p[1] || p[1] == p[2] || p[0] == p[2] {
+                simplifier.alive[t] = false;
+                simplifier.alive_count -= 1;
+                continue;
+            }
+            let (normal, area) = simplifier.get_normal(&[simplifier.points[p[0]],
+                simplifier.points[p[1]], simplifier.points[p[2]]]);
+            let plane = plane_quadric(normal, &simplifier.points[p[0]], area);
+            for c in 0..3 {
+                simplifier.position_triangles[p[c]].push(t);
+                add(&mut simplifier.quadrics[p[c]], &plane);
+                let edge = (p[c].min(p[(c + 1) % 3]), p[c].max(p[(c + 1) % 3]));
+                edges.entry(edge).or_insert((t, 0)).1 += 1;
+            }
+        }
+
+        // Bordes abiertos: plano perpendicular al triángulo que contiene la arista
+        for (&(a, b), &(t, count)) in edges.iter() {
+            if count != 1 {
+                continue;
+            }
+            let p = simplifier.get_positions(t);
+            let (face_normal, _) = simplifier.get_normal(&[simplifier.points[p[0]],
+                simplifier.points[p[1]], simplifier.points[p[2]]]);
+            let edge = sub(&simplifier.points[b], &simplifier.points[a]);
+            let length2 = dot(&edge, &edge);
+            let (normal, _) = normalize(cross(&edge, &face_normal));
+            let plane = plane_quadric(normal, &simplifier.points[a], length2 * BOUNDARY_WEIGHT);
+            add(&mut simplifier.quadrics[a], &plane);
+            add(&mut simplifier.quadrics[b], &plane);
+        }
+        simplifier
+    }
+
+    // Pasadas de colapsos ordenados por error. En cada pasada una posición solo participa en un
+    // colapso, después se recalculan los costes
+    fn run(&mut self, target: usize) {
+        while self.alive_count > target {
+            let mut candidates: Vec<(f64, usize, usize)> = self.get_edges().into_iter()
+                .map(|(a, b)| {
+                    let mut quadric = self.quadrics[a];
+                    add(&mut quadric, &self.quadrics[b]);
+                    let to_b = evaluate(&quadric, &self.points[b]);
+                    let to_a = evaluate(&quadric, &self.points[a]);
+                    if to_b <= to_a { (to_b, a, b) } else { (to_a, b, a) }
+                })
+                .collect();
+            candidates.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(std::cmp::Ordering::Equal));
+
+            let mut touched = vec![false; self.points.len()];
+            let mut collapsed = 0;
+            for (_, from, to) in candidates {
+                if self.alive_count <= target {
+                    break;
+                }
+                if touched[from] || touched[to] {
+                    continue;
+                }
+                if let Some(wedges) = self.check_collapse(from, to) {
+                    self.collapse(from, to, wedges);
+                    touched[from] = true;
+                    touched[to] = true;
+                    collapsed += 1;
+                }
+            }
+            if collapsed == 0 {
+                break;
+            }
+        }
+    }
+
+    // Aristas (por posición) de los triángulos vivos
+    fn get_edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = Vec::with_capacity(self.alive_count * 3);
+        for t in 0..self.triangles.len() {
+            if !self.alive[t] {
+                continue;
+            }
+            let p = self.get_positions(t);
+            for c in 0..3 {
+                edges.push((p[c].min(p[(c + 1) % 3]), p[c].max(p[(c + 1) % 3])));
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    }
+
+    // Devuelve a qué vértice de to pasa cada copia de from que tiene pareja en los triángulos de
+    // la arista, o None si el colapso rompería la malla: triángulos que se dan la vuelta o
+    // aristas que pasarían a tener más de dos triángulos
+    fn check_collapse(&self, from: usize, to: usize) -> Option<HashMap<u32, u32>> {
+        let triangles = self.get_triangles(from);
+        let mut wedges: HashMap<u32, u32> = HashMap::new();
+        let mut opposite: Vec<usize> = vec![];
+        for &t in triangles.iter() {
+            let p = self.get_positions(t);
+            if let Some(c_to) = (0..3).find(|&c| p[c] == to) {
+                let c_from = (0..3).find(|&c| p[c] == from).unwrap();
+                wedges.entry(self.triangles[t][c_from]).or_insert(self.triangles[t][c_to]);
+                opposite.push(p[3 - c_from - c_to]);
+            }
+        }
+        if opposite.is_empty() {
+            return None;
+        }
+
+        // Condición de enlace: los vecinos comunes son solo los de los triángulos que desaparecen
+        let neighbours_from = self.get_neighbours(from);
+        let neighbours_to = self.get_neighbours(to);
+        if neighbours_from.iter()
+            .any(|n| *n != to && neighbours_to.contains(n) && !opposite.contains(n)) {
+            return None;
+        }
+
+        for &t in triangles.iter() {
+            let p = self.get_positions(t);
+            if p.contains(&to) {
+                continue;
+            }
+            let before = [self.points[p[0]], self.points[p[1]], self.points[p[2]]];
+            let mut after = before;
+            for c in 0..3 {
+                if p[c] == from {
+                    after[c] = self.points[to];
+                }
+            }
+            let (normal_before, _) = self.get_normal(&before);
+            let (normal_after, area_after) = self.get_normal(&after);
+            if area_after <= 0.0 || dot(&normal_before, &normal_after) < MIN_NORMAL_DOT {
+                return None;
+            }
+        }
+        Some(wedges)
+    }
+
+    // Las copias de from sin pareja pasan a ser vértices nuevos en to con sus propios atributos
+    fn collapse(&mut self, from: usize, to: usize, mut wedges: HashMap<u32, u32>) {
+        for t in self.get_triangles(from) {
+            if self.get_positions(t).contains(&to) {
+                self.alive[t] = false;
+                self.alive_count -= 1;
+                continue;
+            }
+            for c in 0..3 {
+                let vertex = self.triangles[t][c];
+                if self.position_of[vertex as usize] != from {
+                    continue;
+                }
+                let position_of = &mut self.position_of;
+                let attributes_of = &mut self.attributes_of;
+                self.triangles[t][c] = *wedges.entry(vertex).or_insert_with(|| {
+                    position_of.push(to);
+                    attributes_of.push(attributes_of[vertex as usize]);
+                    (position_of.len() - 1) as u32
+                });
+            }
+            self.position_triangles[to].push(t);
+        }
+        let quadric = self.quadrics[from];
+        add(&mut self.quadrics[to], &quadric);
+        self.position_alive[from] = false;
+        self.position_triangles[from].clear();
+    }
+
+    // Triángulos vivos que usan la posición
+    fn get_triangles(&self, position: usize) -> Vec<usize> {
+        let mut triangles: Vec<usize> = self.position_triangles[position].iter().cloned()
+            .filter(|&t| self.alive[t] && self.get_positions(t).contains(&position))
+            .collect();
+        triangles.sort_unstable();
+        triangles.dedup();
+        triangles
+    }
+
+    fn get_neighbours(&self, position: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.get_triangles(position).iter()
+            .flat_map(|&t| self.get_positions(t).to_vec())
+            .filter(|&p| p != position)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    fn get_positions(&self, t: usize) -> [usize; 3] {
+        let triangle = self.triangles[t];
+        [self.position_of[triangle[0] as usize], self.position_of[triangle[1] as usize],
+            self.position_of[triangle[2] as usize]]
+    }
+
+    // Normal unitaria y área del triángulo
+    fn get_normal(&self, corners: &[Point; 3]) -> (Point, f64) {
+        let normal = cross(&sub(&corners[1], &corners[0]), &sub(&corners[2], &corners[0]));
+        let (unit, length) = normalize(normal);
+        (unit, length * 0.5)
+    }
+
+    // Malla con los triángulos vivos y solo los vértices que usan, en orden de aparición
+    fn build(&self, data: &ModelData) -> ModelData {
+        let has_tangents = data.has_tangents();
+        let mut remap: Vec<Option<u32>> = vec![None; self.position_of.len()];
+        let mut vertices = vec![];
+        let mut texture_coords = vec![];
+        let mut normals = vec![];
+        let mut tangents = vec![];
+        let mut indices = Vec::with_capacity(self.alive_count * 3);
+        for t in 0..self.triangles.len() {
+            if !self.alive[t] {
+                continue;
+            }
+            for &vertex in self.triangles[t].iter() {
+                let index = match remap[vertex as usize] {
+                    Some(index) => index,
+                    None => {
+                        let index = (vertices.len() / 3) as u32;
+                        let point = self.points[self.position_of[vertex as usize]];
+                        vertices.extend(point.iter().map(|&coordinate| coordinate as f32));
+                        let v = self.attributes_of[vertex as usize];
+                        texture_coords.extend_from_slice(&data.get_texture_coords()[v * 2..v * 2 + 2]);
+                        normals.extend_from_slice(&data.get_normals()[v * 3..v * 3 + 3]);
+                        if has_tangents {
+                            tangents.extend_from_slice(&data.get_tangents()[v * 4..v * 4 + 4]);
+                        }
+                        remap[vertex as usize] = Some(index);
+                        index
+                    }
+                };
+                indices.push(index);
+            }
+        }
+        ModelData::with_tangents(vertices, texture_coords, normals, tangents, indices,
+                                 data.get_furthest_point())
+    }
+}
+
+fn plane_quadric(normal: Point, point: &Point, weight: f64) -> Quadric {
+    let [a, b, c] = normal;
+    let d = -dot(&normal, point);
+    [a * a * weight, a * b * weight, a * c * weight, a * d * weight,
+        b * b * weight, b * c * weight, b * d * weight,
+        c * c * weight, c * d * weight,
+        d * d * weight]
+}
+
+fn add(quadric: &mut Quadric, other: &Quadric) {
+    for i in 0..10 {
+        quadric[i] += other[i];
+    }
+}
+
+// vᵀ Q v con v = (x, y, z, 1)
+fn evaluate(q: &Quadric, p: &Point) -> f64 {
+    let [x, y, z] = *p;
+    q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x +
+        q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y +
+        q[7] * z * z + 2.0 * q[8] * z +
+        q[9]
+}
+
+fn sub(a: &Point, b: &Point) -> Point {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: &Point, b: &Point) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &Point, b: &Point) -> Point {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+// Vector unitario y longitud original (0 si es nulo)
+fn normalize(v: Point) -> (Point, f64) {
+    let length = dot(&v, &v).sqrt();
+    if length > 0.0 { ([v[0] / length, v[1] / length, v[2] / length], length) } else { (v, 0.0) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::obj_converter::model_data::test_grid;
+
+    fn triangle_count(data: &ModelData) -> usize {
+        data.get_indices().len() / 3
+    }
+
+    fn position(data: &ModelData, index: u32) -> [f32; 3] {
+        let i = index as usize * 3;
+        [data.get_vertices()[i], data.get_vertices()[i + 1], data.get_vertices()[i + 2]]
+    }
+
+    #[test]
+    fn reduces_to_the_ratio_without_breaking_the_mesh() {
+        let data = test_grid(9, 0.0);
+        let simplified = simplify(&data, 0.25);
+        assert!(triangle_count(&simplified) <= triangle_count(&data) / 4);
+        assert!(triangle_count(&simplified) >= 2);
+        let vertex_count = simplified.get_vertices().len() / 3;
+        assert_eq!(simplified.get_texture_coords().len(), vertex_count * 2);
+        assert_eq!(simplified.get_normals().len(), vertex_count * 3);
+        for triangle in simplified.get_indices().chunks(3) {
+            assert!(triangle.iter().all(|&index| (index as usize) < vertex_count));
+            // Ningún triángulo se da la vuelta ni queda degenerado
+            let [a, b, c] = [position(&simplified, triangle[0]), position(&simplified, triangle[1]),
+                position(&simplified, triangle[2])];
+            let normal_y = (b[2] - a[2]) * (c[0] - a[0]) - (b[0] - a[0]) * (c[2] - a[2]);
+            assert!(normal_y > 0.0);
+        }
+        // Los vértices no se mueven a posiciones nuevas y los bordes no encogen
+        for index in 0..vertex_count as u32 {
+            let [x, y, z] = position(&simplified, index);
+            assert_eq!((x.fract(), y, z.fract()), (0.0, 0.0, 0.0));
+        }
+        assert_eq!(simplified.get_bounding_box().get_min(), data.get_bounding_box().get_min());
+        assert_eq!(simplified.get_bounding_box().get_max(), data.get_bounding_box().get_max());
+    }
+
+    #[test]
+    fn ratio_limits() {
+        let data = test_grid(5, 0.0);
+        assert_eq!(triangle_count(&simplify(&data, 1.0)), triangle_count(&data));
+        assert!(triangle_count(&simplify(&data, 0.0)) >= 1);
+    }
+
+    #[test]
+    fn lod_chain_gets_smaller() {
+        let data = test_grid(9, 0.0);
+        let levels = build_lod_chain(&data, &[0.5, 0.25, 0.1]);
+        assert_eq!(levels.len(), 3);
+        let counts: Vec<usize> = levels.iter().map(triangle_count).collect();
+        assert!(counts[0] < triangle_count(&data));
+        assert!(counts[1] < counts[0] && counts[2] < counts[1]);
+    }
+}
@@ -1,5 +1,8 @@
 pub mod maths;
 pub mod mouse;
 pub mod teclado;
-pub mod png_loader;
-pub mod mouse_picker;
\ No newline at end of file
+pub mod mouse_picker;
+pub mod tangent_generator;
+pub mod mesh_simplifier;
+pub mod mesh_optimizer;
+pub mod mesh_exporter;
\ No newline at end of file
@@ -6,7 +6,7 @@ use std::ops::Add;
 
 use crate::entities::camera::Camera;
 use crate::render_engine::display_manager::*;
-use crate::terrains::terrain::Terrain;
+use crate::terrains::terrain_world::TerrainWorld;
 use crate::toolbox::maths::*;
 use crate::toolbox::mouse::Mouse;
 
@@ -25,18 +25,16 @@ pub struct MousePicker {
     projection_matrix: M4CG,
     view_matrix: M4CG,
 
-    terrain: Option<Terrain>,
     current_terrain_point: Option<V3CG>,
 
 }
 
 impl MousePicker {
-    pub fn new(camera: &mut Camera, projection_matrix: M4CG, terrain: &Terrain) -> MousePicker {
+    pub fn new(camera: &mut Camera, projection_matrix: M4CG) -> MousePicker {
         MousePicker {
             current_ray: vec3(0.0, 0.0, 0.0),
             projection_matrix,
             view_matrix: create_view_matrix(camera),
-            terrain: Some(terrain.clone()),
             current_terrain_point: None,
         }
     }
@@ -49,13 +47,14 @@ impl MousePicker {
         self.current_ray
     }
 
-    pub fn update(&mut self, camera: &mut Camera) {
+    // El rayo se prueba contra los tiles de world por los que pasa
+    pub fn update(&mut self, camera: &mut Camera, world: &TerrainWorld) {
         self.view_matrix = create_view_matrix(camera);
         self.current_ray = self.calculate_mouse_ray(&camera.mouse);
 
-        if self.intersection_in_range(0.0, RAY_RANGE, self.current_ray, camera) {
+        if self.intersection_in_range(0.0, RAY_RANGE, self.current_ray, camera, world) {
             self.current_terrain_point =
-                self.binary_search(0, 0.0, RAY_RANGE, self.current_ray, camera);
+                self.binary_search(0, 0.0, RAY_RANGE, self.current_ray, camera, world);
         } else {
             self.current_terrain_point = None;
         }
@@ -107,49 +106,29 @@ impl MousePicker {
         start.add(scaled_ray)
     }
 
-    fn binary_search(
-        &self, count: i32, start: f32, finish: f32, ray: V3CG, camera: &Camera) -> Option<V3CG> {
+    fn binary_search(&self, count: i32, start: f32, finish: f32, ray: V3CG, camera: &Camera,
+                     world: &TerrainWorld) -> Option<V3CG> {
         let half: f32 = start + ((finish - start) / 2.0);
         if count >= RECURSION_COUNT {
             let end_point = self.get_point_on_ray(ray, half, camera);
-            let terrain: Option<&Terrain> = self.get_terrain(end_point.x, end_point.z);
-            if terrain.is_some() {
-                return Some(end_point);
-            } else {
-                return None;
-            }
+            return world.get_terrain(end_point.x, end_point.z).map(|_| end_point);
         }
-        if self.intersection_in_range(start, half, ray, camera) {
-            return self.binary_search(count + 1, start, half, ray, camera);
+        if self.intersection_in_range(start, half, ray, camera, world) {
+            self.binary_search(count + 1, start, half, ray, camera, world)
         } else {
-            return self.binary_search(count + 1, half, finish, ray, camera);
+            self.binary_search(count + 1, half, finish, ray, camera, world)
         }
     }
 
-    fn intersection_in_range(&self, start: f32, finish: f32, ray: V3CG, camera: &Camera) -> bool {
+    fn intersection_in_range(&self, start: f32, finish: f32, ray: V3CG, camera: &Camera,
+                             world: &TerrainWorld) -> bool {
         let start_point: V3CG = self.get_point_on_ray(ray, start, camera);
         let end_point: V3CG = self.get_point_on_ray(ray, finish, camera);
-        if !self.is_under_ground(start_point) && self.is_under_ground(end_point) {
-            return true;
-        } else {
-            return false;
-        }
-    }
-
-    fn is_under_ground(&self, test_point: V3CG) -> bool {
-        let terrain: Option<&Terrain> = self.get_terrain(test_point.x, test_point.z);
-        let mut height: f32 = 0.0;
-        if terrain.is_some() {
-            height = terrain.unwrap().get_height_of_terrain(test_point.x, test_point.z);
-        }
-        if (test_point.y < height) {
-            return true;
-        } else {
-            return false;
-        }
+        !self.is_under_ground(start_point, world) && self.is_under_ground(end_point, world)
     }
 
-    fn get_terrain(&self, world_x: f32, world_z: f32) -> Option<&Terrain> {
-        self.terrain.as_ref()
+    fn is_under_ground(&self, test_point: V3CG, world: &TerrainWorld) -> bool {
+        let height = world.get_height_of_terrain(test_point.x, test_point.z);
+        test_point.y < height
     }
 }
\ No newline at end of file
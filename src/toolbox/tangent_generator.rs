@@ -0,0 +1,178 @@
+use mikktspace::Geometry;
+use std::collections::HashMap;
+
+use crate::obj_converter::model_data::ModelData;
+
+// Vista de ModelData para mikktspace, que devuelve una tangente por esquina de triángulo
+struct MikkGeometry<'a> {
+    data: &'a ModelData,
+    corner_tangents: Vec<[f32; 4]>,
+}
+
+impl<'a> MikkGeometry<'a> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.data.get_indices()[face * 3 + vert] as usize
+    }
+}
+
+impl<'a> Geometry for MikkGeometry<'a> {
+    fn num_faces(&self) -> usize {
+        self.data.get_indices().len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        let v = self.vertex(face, vert) * 3;
+        let positions = self.data.get_vertices();
+        [positions[v], positions[v + 1], positions[v + 2]]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        let v = self.vertex(face, vert) * 3;
+        let normals = self.data.get_normals();
+        [normals[v], normals[v + 1], normals[v + 2]]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let v = self.vertex(face, vert) * 2;
+        let texture_coords = self.data.get_texture_coords();
+        [texture_coords[v], texture_coords[v + 1]]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.corner_tangents[face * 3 + vert] = tangent;
+    }
+}
+
+// Tangentes MikkTSpace (xyz y en w el signo de la bitangente, bitangente = w * normal x tangente)
+// a partir de las uv. Un vértice compartido por triángulos cuyas tangentes no coinciden (costuras
+// de uv, zonas espejadas) se duplica, así cada copia tiene la suya. Sin triángulos devuelve la
+// malla tal cual, sin tangentes. El informe de optimización se conserva
+pub fn generate_tangents(data: &ModelData) -> ModelData {
+    let mut geometry = MikkGeometry {
+        data,
+        corner_tangents: vec![[1.0, 0.0, 0.0, 1.0]; data.get_indices().len()],
+    };
+    if data.get_indices().is_empty() || !mikktspace::generate_tangents(&mut geometry) {
+        return data.clone();
+    }
+    let corner_tangents = geometry.corner_tangents;
+
+    let mut vertices = data.get_vertices().clone();
+    let mut texture_coords = data.get_texture_coords().clone();
+    let mut normals = data.get_normals().clone();
+    let vertex_count = vertices.len() / 3;
+    let mut tangents: Vec<f32> = [1.0, 0.0, 0.0, 1.0].iter().cloned().cycle()
+        .take(vertex_count * 4).collect();
+    let mut indices = Vec::with_capacity(data.get_indices().len());
+
+    // Copias de cada vértice original según su tangente. La primera usa el vértice original
+    let mut copies: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    let mut used = vec![false; vertex_count];
+    for (corner, &index) in data.get_indices().iter().enumerate() {
+        let tangent = corner_tangents[corner];
+        let bits = [tangent[0].to_bits(), tangent[1].to_bits(), tangent[2].to_bits(),
+            tangent[3].to_bits()];
+        let v = index as usize;
+        let new_index = *copies.entry((index, bits)).or_insert_with(|| {
+            let new_index = if !used[v] {
+                used[v] = true;
+                v
+            } else {
+                vertices.extend_from_slice(&data.get_vertices()[v * 3..v * 3 + 3]);
+                texture_coords.extend_from_slice(&data.get_texture_coords()[v * 2..v * 2 + 2]);
+                normals.extend_from_slice(&data.get_normals()[v * 3..v * 3 + 3]);
+                tangents.extend_from_slice(&[0.0; 4]);
+                tangents.len() / 4 - 1
+            };
+            tangents[new_index * 4..new_index * 4 + 4].copy_from_slice(&tangent);
+            new_index as u32
+        });
+        indices.push(new_index);
+    }
+    let mut result = ModelData::with_tangents(vertices, texture_coords, normals, tangents, indices,
+                                              data.get_furthest_point());
+    if let Some(report) = data.get_optimization_report() {
+        result.set_optimization_report(report);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Quad de x0 a x1 en z = 0 mirando a +z, con la u de u0 a u1 y la v igual a la y
+    fn quad(x0: f32, x1: f32, u0: f32, u1: f32) -> (Vec<f32>, Vec<f32>) {
+        (vec![x0, 0.0, 0.0, x1, 0.0, 0.0, x1, 1.0, 0.0, x0, 1.0, 0.0],
+         vec![u0, 0.0, u1, 0.0, u1, 1.0, u0, 1.0])
+    }
+
+    fn tangent(data: &ModelData, vertex: u32) -> [f32; 4] {
+        let t = &data.get_tangents()[vertex as usize * 4..vertex as usize * 4 + 4];
+        [t[0], t[1], t[2], t[3]]
+    }
+
+    fn assert_tangent(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn flat_quad_tangent_follows_u() {
+        let (vertices, texture_coords) = quad(0.0, 1.0, 0.0, 1.0);
+        let normals = [0.0, 0.0, 1.0].repeat(4);
+        let data = ModelData::new(vertices, texture_coords, normals, vec![0, 1, 2, 0, 2, 3], 1.0);
+        let result = generate_tangents(&data);
+        assert_eq!(result.get_vertices().len(), 4 * 3);
+        for &index in result.get_indices() {
+            assert_tangent(tangent(&result, index), [1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn mirrored_uv_seam_splits_vertices() {
+        // A la izquierda de x = 0 la u va al revés: la costura comparte posición y uv, pero
+        // la tangente apunta a -x y la bitangente sigue siendo +y, así que w cambia de signo
+        let (mut vertices, mut texture_coords) = quad(-1.0, 0.0, 1.0, 0.0);
+        let (right_vertices, right_coords) = quad(0.0, 1.0, 0.0, 1.0);
+        vertices.extend_from_slice(&right_vertices[3..9]);
+        texture_coords.extend_from_slice(&right_coords[2..6]);
+        let normals = [0.0, 0.0, 1.0].repeat(6);
+        // Izquierda: 0 1 2 3; derecha: 1 4 5 2
+        let indices = vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2];
+        let data = ModelData::new(vertices, texture_coords, normals, indices, 1.0);
+        let result = generate_tangents(&data);
+
+        // Los dos vértices de la costura se duplican
+        assert_eq!(result.get_vertices().len(), 8 * 3);
+        let indices = result.get_indices();
+        for &index in indices[..6].iter() {
+            assert_tangent(tangent(&result, index), [-1.0, 0.0, 0.0, -1.0]);
+        }
+        for &index in indices[6..].iter() {
+            assert_tangent(tangent(&result, index), [1.0, 0.0, 0.0, 1.0]);
+        }
+        // Cada lado de la costura usa su propia copia, en la misma posición
+        let position = |index: u32| {
+            let v = index as usize * 3;
+            &result.get_vertices()[v..v + 3]
+        };
+        assert_ne!(indices[1], indices[6]);
+        assert_eq!(position(indices[1]), position(indices[6]));
+        assert_ne!(indices[2], indices[11]);
+        assert_eq!(position(indices[2]), position(indices[11]));
+    }
+
+    #[test]
+    fn without_triangles_the_input_is_kept() {
+        let data = ModelData::new(vec![0.0; 3], vec![0.0; 2], vec![0.0, 0.0, 1.0], vec![], 0.0);
+        let result = generate_tangents(&data);
+        assert!(!result.has_tangents());
+        assert_eq!(result.get_vertices(), data.get_vertices());
+    }
+}